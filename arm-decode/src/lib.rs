@@ -633,7 +633,7 @@ impl Processor {
 
     #[inline]
     pub fn is_cond_instr(&self, instr: u32) -> bool {
-        self.cond_bits(instr) == 0b1111
+        self.cond_bits(instr) != 0b1111
     }
 
     #[inline]
@@ -0,0 +1,82 @@
+//! Audio sinks the output of the sound unit is pushed to, see [`crate::Core::set_audio_sink`].
+
+mod ring;
+pub use ring::{RingBufferConsumer, RingBufferSink};
+
+mod wav;
+pub use wav::{decode_wav, WavSink};
+
+use crate::spu::NATIVE_SAMPLE_RATE;
+use crate::Resampler;
+
+/// Receives interleaved stereo samples at its own sample rate.
+pub trait AudioSink {
+    fn sample_rate(&self) -> u32;
+
+    /// Stereo frames buffered but not played yet, `None` for sinks that don't play in real
+    /// time. Used to adjust the resampling rate so the buffer neither runs dry nor overflows.
+    fn buffered(&self) -> Option<usize> {
+        None
+    }
+
+    fn push(&mut self, samples: &[i16]);
+}
+
+/// Frames collected before they are resampled and pushed to the sink, about 15ms.
+const FLUSH_LEN: usize = 512 * 2;
+
+/// Maximum deviation from the nominal rate of the dynamic rate control.
+const MAX_RATE_DELTA: f64 = 0.005;
+
+/// A sink attached to the core with the state to feed it.
+pub(crate) struct SinkOutput {
+    sink: Box<dyn AudioSink>,
+    resampler: Resampler,
+    /// Buffered frames the rate control aims for, a fraction of a second.
+    target_buffered: usize,
+    pending: Vec<i16>,
+    resampled: Vec<i16>,
+}
+
+impl SinkOutput {
+    pub fn new(sink: Box<dyn AudioSink>) -> Self {
+        let rate = sink.sample_rate();
+        Self {
+            sink,
+            resampler: Resampler::new(NATIVE_SAMPLE_RATE, rate as f64),
+            target_buffered: rate as usize / 20,
+            pending: Vec::with_capacity(FLUSH_LEN),
+            resampled: Vec::new(),
+        }
+    }
+
+    pub fn push_frame(&mut self, frame: [i16; 2]) {
+        self.pending.extend(frame);
+        if self.pending.len() >= FLUSH_LEN {
+            self.flush();
+        }
+    }
+
+    pub fn flush(&mut self) {
+        let rate = self.sink.sample_rate() as f64;
+        let out_rate = match self.sink.buffered() {
+            // produce slightly more samples while the buffer is below the target, and less
+            // while it's above.
+            Some(buffered) => {
+                let fill = (buffered as f64 / (2 * self.target_buffered) as f64).min(1.0);
+                rate * (1.0 + MAX_RATE_DELTA * (1.0 - 2.0 * fill))
+            }
+            None => rate,
+        };
+        self.resampler.set_rates(NATIVE_SAMPLE_RATE, out_rate);
+        self.resampled.clear();
+        self.resampler.process(&self.pending, &mut self.resampled);
+        self.pending.clear();
+        self.sink.push(&self.resampled);
+    }
+
+    pub fn into_sink(mut self) -> Box<dyn AudioSink> {
+        self.flush();
+        self.sink
+    }
+}
@@ -0,0 +1,64 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use super::AudioSink;
+
+/// Buffers samples for an audio callback running on another thread, which reads them through
+/// a [`RingBufferConsumer`]. The oldest samples are dropped when it's full.
+pub struct RingBufferSink {
+    buf: Arc<Mutex<VecDeque<i16>>>,
+    capacity: usize,
+    sample_rate: u32,
+}
+
+#[derive(Clone)]
+pub struct RingBufferConsumer {
+    buf: Arc<Mutex<VecDeque<i16>>>,
+}
+
+impl RingBufferSink {
+    /// A sink holding up to `capacity` stereo frames, and the consumer reading from it.
+    pub fn new(sample_rate: u32, capacity: usize) -> (Self, RingBufferConsumer) {
+        let buf = Arc::new(Mutex::new(VecDeque::with_capacity(capacity * 2)));
+        let consumer = RingBufferConsumer { buf: buf.clone() };
+        let sink = Self {
+            buf,
+            capacity: capacity * 2,
+            sample_rate,
+        };
+        (sink, consumer)
+    }
+}
+
+impl AudioSink for RingBufferSink {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn buffered(&self) -> Option<usize> {
+        Some(self.buf.lock().unwrap().len() / 2)
+    }
+
+    fn push(&mut self, samples: &[i16]) {
+        let mut buf = self.buf.lock().unwrap();
+        buf.extend(samples);
+        let len = buf.len();
+        let overflow = len.saturating_sub(self.capacity);
+        // drop whole frames so the channels stay in order.
+        buf.drain(..overflow.next_multiple_of(2).min(len));
+    }
+}
+
+impl RingBufferConsumer {
+    /// Fill `out` with interleaved stereo samples, padding with silence on underrun. Returns
+    /// the samples that were available.
+    pub fn pop(&self, out: &mut [i16]) -> usize {
+        let mut buf = self.buf.lock().unwrap();
+        let len = out.len().min(buf.len());
+        for (dst, src) in out.iter_mut().zip(buf.drain(..len)) {
+            *dst = src;
+        }
+        out[len..].fill(0);
+        len
+    }
+}
@@ -0,0 +1,158 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use super::AudioSink;
+use crate::{Error, Result};
+
+/// Writes 16 bit stereo PCM into a WAV file, the sizes in the header are filled in when it's
+/// dropped or finished.
+pub struct WavSink {
+    file: BufWriter<File>,
+    sample_rate: u32,
+    data_len: u32,
+    /// First error while writing, reported by [`WavSink::finish`].
+    error: Option<io::Error>,
+    /// The header was filled in, by [`WavSink::finish`] before it's dropped.
+    finished: bool,
+}
+
+impl WavSink {
+    const HEADER_LEN: u32 = 44;
+
+    pub fn create(path: impl AsRef<Path>, sample_rate: u32) -> io::Result<Self> {
+        let mut sink = Self {
+            file: BufWriter::new(File::create(path)?),
+            sample_rate,
+            data_len: 0,
+            error: None,
+            finished: false,
+        };
+        sink.write_header()?;
+        Ok(sink)
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        let channels = 2u16;
+        let block_align = channels * 2;
+        let file = &mut self.file;
+        file.write_all(b"RIFF")?;
+        file.write_all(&(Self::HEADER_LEN - 8 + self.data_len).to_le_bytes())?;
+        file.write_all(b"WAVEfmt ")?;
+        file.write_all(&16u32.to_le_bytes())?;
+        // PCM.
+        file.write_all(&1u16.to_le_bytes())?;
+        file.write_all(&channels.to_le_bytes())?;
+        file.write_all(&self.sample_rate.to_le_bytes())?;
+        file.write_all(&(self.sample_rate * block_align as u32).to_le_bytes())?;
+        file.write_all(&block_align.to_le_bytes())?;
+        file.write_all(&16u16.to_le_bytes())?;
+        file.write_all(b"data")?;
+        file.write_all(&self.data_len.to_le_bytes())
+    }
+
+    fn finish_mut(&mut self) -> io::Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+        self.finished = true;
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+        self.file.seek(SeekFrom::Start(0))?;
+        self.write_header()?;
+        self.file.seek(SeekFrom::End(0))?;
+        self.file.flush()
+    }
+
+    /// Fill in the header and flush the file.
+    pub fn finish(mut self) -> io::Result<()> {
+        self.finish_mut()
+    }
+}
+
+impl AudioSink for WavSink {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn push(&mut self, samples: &[i16]) {
+        if self.error.is_some() {
+            return;
+        }
+        let bytes: Vec<u8> = samples
+            .iter()
+            .flat_map(|sample| sample.to_le_bytes())
+            .collect();
+        match self.file.write_all(&bytes) {
+            Ok(()) => self.data_len += bytes.len() as u32,
+            Err(err) => self.error = Some(err),
+        }
+    }
+}
+
+impl Drop for WavSink {
+    fn drop(&mut self) {
+        let _ = self.finish_mut();
+    }
+}
+
+/// Decode a PCM WAV file with 8 or 16 bit samples into mono 16 bit samples and its sample rate.
+pub fn decode_wav(data: &[u8]) -> Result<(Vec<i16>, u32)> {
+    fn err(msg: &str) -> Error {
+        Error::Audio(format!("invalid wav file: {msg}"))
+    }
+    fn read16(data: &[u8], offs: usize) -> u16 {
+        u16::from_le_bytes([data[offs], data[offs + 1]])
+    }
+    fn read32(data: &[u8], offs: usize) -> u32 {
+        u32::from_le_bytes(data[offs..offs + 4].try_into().unwrap())
+    }
+
+    if data.len() < 12 || &data[..4] != b"RIFF" || &data[8..12] != b"WAVE" {
+        return Err(err("missing RIFF header"));
+    }
+    let mut format = None;
+    let mut samples = None;
+    let mut offs = 12;
+    while offs + 8 <= data.len() {
+        let id = &data[offs..offs + 4];
+        let len = read32(data, offs + 4) as usize;
+        let body = &data[offs + 8..(offs + 8 + len).min(data.len())];
+        match id {
+            b"fmt " if body.len() >= 16 => {
+                let (tag, channels, rate, bits) = (
+                    read16(body, 0),
+                    read16(body, 2),
+                    read32(body, 4),
+                    read16(body, 14),
+                );
+                if tag != 1 || channels == 0 || !matches!(bits, 8 | 16) {
+                    return Err(err("only 8 or 16 bit PCM is supported"));
+                }
+                format = Some((channels as usize, rate, bits));
+            }
+            b"data" => samples = Some(body),
+            _ => {}
+        }
+        // chunks are padded to an even length.
+        offs += 8 + len + (len & 0b1);
+    }
+    let (channels, rate, bits) = format.ok_or_else(|| err("missing fmt chunk"))?;
+    let samples = samples.ok_or_else(|| err("missing data chunk"))?;
+
+    let frame_len = channels * bits as usize / 8;
+    let mono = samples
+        .chunks_exact(frame_len)
+        .map(|frame| {
+            let sum: i32 = (0..channels)
+                .map(|channel| match bits {
+                    8 => (frame[channel] as i32 - 0x80) << 8,
+                    _ => read16(frame, channel * 2) as i16 as i32,
+                })
+                .sum();
+            (sum / channels as i32) as i16
+        })
+        .collect();
+    Ok((mono, rate))
+}
@@ -6,17 +6,19 @@ pub use ptrs::{adr_to_page, masks, Attr, PtrTable};
 
 mod fallback;
 
+mod io;
+
 use crate::{Core, Engine};
 
 macro_rules! def_read {
-    ($($fn_ident:ident, $ty:ty, $fallback:path;)*) => {
+    ($cpu:ident; $($fn_ident:ident, $ty:ty, $fallback:path;)*) => {
         $(
             #[inline(always)]
             fn $fn_ident<A: Access, E: Engine>(
                 core: &mut Core<E>,
                 adr: u32
             ) -> $ty {
-                if let Some(ptr) = core.arm9.bus_ptrs.read(adr) {
+                if let Some(ptr) = core.$cpu.bus_ptrs.read(adr) {
                     unsafe {
                         let mask = core::mem::size_of::<$ty>() - 1;
                         let mask = PtrTable::PG_MASK as usize & !mask;
@@ -32,7 +34,7 @@ macro_rules! def_read {
 }
 
 macro_rules! def_write {
-    ($($fn_ident:ident, $ty:ty, $write_fn:ident, $fallback:path;)*) => {
+    ($cpu:ident; $($fn_ident:ident, $ty:ty, $write_fn:ident, $fallback:path;)*) => {
         $(
             #[inline(always)]
             fn $fn_ident<A: Access, E: Engine>(
@@ -40,7 +42,7 @@ macro_rules! def_write {
                 adr: u32,
                 val: $ty
             ) {
-                if let Some(ptr) = core.arm9.bus_ptrs.$write_fn(adr) {
+                if let Some(ptr) = core.$cpu.bus_ptrs.$write_fn(adr) {
                     unsafe {
                         let mask = core::mem::size_of::<$ty>() - 1;
                         let mask = PtrTable::PG_MASK as usize & !mask;
@@ -116,12 +118,14 @@ pub(crate) mod arm9 {
     use super::*;
 
     def_read! {
+        arm9;
         __read8, u8, fallback::arm9::read8::<E, A>;
         __read16, u16, fallback::arm9::read16::<E, A>;
         __read32, u32, fallback::arm9:: read32::<E, A>;
     }
 
     def_write! {
+        arm9;
         __write8, u8, write8, fallback::arm9::write8::<E, A>;
         __write16, u16, write32_16, fallback::arm9::write16::<E, A>;
         __write32, u32, write32_16, fallback::arm9::write32::<E, A>;
@@ -134,12 +138,14 @@ pub(crate) mod arm7 {
     use super::*;
 
     def_read! {
+        arm7;
         __read8, u8, fallback::arm7::read8::<E, A>;
         __read16, u16, fallback::arm7::read16::<E, A>;
         __read32, u32, fallback::arm7:: read32::<E, A>;
     }
 
     def_write! {
+        arm7;
         __write8, u8, write8, fallback::arm7::write8::<E, A>;
         __write16, u16, write32_16, fallback::arm7::write16::<E, A>;
         __write32, u32, write32_16, fallback::arm7::write32::<E, A>;
@@ -1,40 +1,86 @@
-use crate::{bus::Access, Core, Engine};
+use crate::bus::{io, Access};
+use crate::{wifi, Core, Engine};
+
+/// Accesses at and above this go to the wifi unit instead of the IO registers.
+const WIFI_START: u32 = 0x04800000;
 
 pub fn read8<E: Engine, A: Access>(core: &mut Core<E>, adr: u32) -> u8 {
-    if A::CPU {
-        warn!(core.arm9.logger, "fallback {adr:08X}");
+    match adr >> 24 {
+        0x04 if adr >= WIFI_START => (wifi::read::<E, A>(core, adr) >> ((adr & 1) << 3)) as u8,
+        0x04 => io::arm7::read8::<E, A>(core, adr),
+        _ => {
+            if A::CPU {
+                warn!(core.arm7.logger, "fallback {adr:08X}");
+            }
+            u8::MAX
+        }
     }
-    u8::MAX
 }
 
 pub fn read16<E: Engine, A: Access>(core: &mut Core<E>, adr: u32) -> u16 {
-    if A::CPU {
-        warn!(core.arm9.logger, "fallback {adr:08X}");
+    match adr >> 24 {
+        0x04 if adr >= WIFI_START => wifi::read::<E, A>(core, adr),
+        0x04 => io::arm7::read16::<E, A>(core, adr),
+        _ => {
+            if A::CPU {
+                warn!(core.arm7.logger, "fallback {adr:08X}");
+            }
+            u16::MAX
+        }
     }
-    u16::MAX
 }
 
 pub fn read32<E: Engine, A: Access>(core: &mut Core<E>, adr: u32) -> u32 {
-    if A::CPU {
-        warn!(core.arm9.logger, "fallback {adr:08X}");
+    match adr >> 24 {
+        0x04 if adr >= WIFI_START => {
+            wifi::read::<E, A>(core, adr) as u32 | (wifi::read::<E, A>(core, adr | 2) as u32) << 16
+        }
+        0x04 => io::arm7::read32::<E, A>(core, adr),
+        _ => {
+            if A::CPU {
+                warn!(core.arm7.logger, "fallback {adr:08X}");
+            }
+            u32::MAX
+        }
     }
-    u32::MAX
 }
 
 pub fn write8<E: Engine, A: Access>(core: &mut Core<E>, adr: u32, val: u8) {
-    if A::CPU {
-        warn!(core.arm9.logger, "fallback {adr:08X}");
+    match adr >> 24 {
+        // the wifi unit ignores byte writes.
+        0x04 if adr >= WIFI_START => {}
+        0x04 => io::arm7::write8::<E, A>(core, adr, val),
+        _ => {
+            if A::CPU {
+                warn!(core.arm7.logger, "fallback {adr:08X}");
+            }
+        }
     }
 }
 
 pub fn write16<E: Engine, A: Access>(core: &mut Core<E>, adr: u32, val: u16) {
-    if A::CPU {
-        warn!(core.arm9.logger, "fallback {adr:08X}");
+    match adr >> 24 {
+        0x04 if adr >= WIFI_START => wifi::write(core, adr, val),
+        0x04 => io::arm7::write16::<E, A>(core, adr, val),
+        _ => {
+            if A::CPU {
+                warn!(core.arm7.logger, "fallback {adr:08X}");
+            }
+        }
     }
 }
 
 pub fn write32<E: Engine, A: Access>(core: &mut Core<E>, adr: u32, val: u32) {
-    if A::CPU {
-        warn!(core.arm9.logger, "fallback {adr:08X}");
+    match adr >> 24 {
+        0x04 if adr >= WIFI_START => {
+            wifi::write(core, adr, val as u16);
+            wifi::write(core, adr | 2, (val >> 16) as u16);
+        }
+        0x04 => io::arm7::write32::<E, A>(core, adr, val),
+        _ => {
+            if A::CPU {
+                warn!(core.arm7.logger, "fallback {adr:08X}");
+            }
+        }
     }
 }
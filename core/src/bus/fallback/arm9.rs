@@ -1,40 +1,71 @@
-use crate::{bus::Access, Core, Engine};
+use crate::bus::{io, Access};
+use crate::{Core, Engine};
 
 pub fn read8<E: Engine, A: Access>(core: &mut Core<E>, adr: u32) -> u8 {
-    if A::CPU {
-        warn!(core.arm9.logger, "fallback {adr:08X}");
+    match adr >> 24 {
+        0x04 => io::arm9::read8::<E, A>(core, adr),
+        _ => {
+            if A::CPU {
+                warn!(core.arm9.logger, "fallback {adr:08X}");
+            }
+            u8::MAX
+        }
     }
-    u8::MAX
 }
 
 pub fn read16<E: Engine, A: Access>(core: &mut Core<E>, adr: u32) -> u16 {
-    if A::CPU {
-        warn!(core.arm9.logger, "fallback {adr:08X}");
+    match adr >> 24 {
+        0x04 => io::arm9::read16::<E, A>(core, adr),
+        _ => {
+            if A::CPU {
+                warn!(core.arm9.logger, "fallback {adr:08X}");
+            }
+            u16::MAX
+        }
     }
-    u16::MAX
 }
 
 pub fn read32<E: Engine, A: Access>(core: &mut Core<E>, adr: u32) -> u32 {
-    if A::CPU {
-        warn!(core.arm9.logger, "fallback {adr:08X}");
+    match adr >> 24 {
+        0x04 => io::arm9::read32::<E, A>(core, adr),
+        _ => {
+            if A::CPU {
+                warn!(core.arm9.logger, "fallback {adr:08X}");
+            }
+            u32::MAX
+        }
     }
-    u32::MAX
 }
 
 pub fn write8<E: Engine, A: Access>(core: &mut Core<E>, adr: u32, val: u8) {
-    if A::CPU {
-        warn!(core.arm9.logger, "fallback {adr:08X}");
+    match adr >> 24 {
+        0x04 => io::arm9::write8::<E, A>(core, adr, val),
+        _ => {
+            if A::CPU {
+                warn!(core.arm9.logger, "fallback {adr:08X}");
+            }
+        }
     }
 }
 
 pub fn write16<E: Engine, A: Access>(core: &mut Core<E>, adr: u32, val: u16) {
-    if A::CPU {
-        warn!(core.arm9.logger, "fallback {adr:08X}");
+    match adr >> 24 {
+        0x04 => io::arm9::write16::<E, A>(core, adr, val),
+        _ => {
+            if A::CPU {
+                warn!(core.arm9.logger, "fallback {adr:08X}");
+            }
+        }
     }
 }
 
 pub fn write32<E: Engine, A: Access>(core: &mut Core<E>, adr: u32, val: u32) {
-    if A::CPU {
-        warn!(core.arm9.logger, "fallback {adr:08X}");
+    match adr >> 24 {
+        0x04 => io::arm9::write32::<E, A>(core, adr, val),
+        _ => {
+            if A::CPU {
+                warn!(core.arm9.logger, "fallback {adr:08X}");
+            }
+        }
     }
 }
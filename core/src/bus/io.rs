@@ -0,0 +1,41 @@
+//! IO registers at 0x04000000, every access is turned into a (masked) access of the word
+//! containing it so a register only has to be handled once.
+
+macro_rules! impl_io_access_fns {
+    () => {
+        pub fn read32<E: Engine, A: Access>(core: &mut Core<E>, adr: u32) -> u32 {
+            read::<E, A>(core, adr & !0b11)
+        }
+
+        pub fn read16<E: Engine, A: Access>(core: &mut Core<E>, adr: u32) -> u16 {
+            (read::<E, A>(core, adr & !0b11) >> ((adr & 0b10) << 3)) as u16
+        }
+
+        pub fn read8<E: Engine, A: Access>(core: &mut Core<E>, adr: u32) -> u8 {
+            (read::<E, A>(core, adr & !0b11) >> ((adr & 0b11) << 3)) as u8
+        }
+
+        pub fn write32<E: Engine, A: Access>(core: &mut Core<E>, adr: u32, val: u32) {
+            write::<E, A>(core, adr & !0b11, val, u32::MAX)
+        }
+
+        pub fn write16<E: Engine, A: Access>(core: &mut Core<E>, adr: u32, val: u16) {
+            let shift = (adr & 0b10) << 3;
+            write::<E, A>(core, adr & !0b11, (val as u32) << shift, 0xFFFF << shift)
+        }
+
+        pub fn write8<E: Engine, A: Access>(core: &mut Core<E>, adr: u32, val: u8) {
+            let shift = (adr & 0b11) << 3;
+            write::<E, A>(core, adr & !0b11, (val as u32) << shift, 0xFF << shift)
+        }
+    };
+}
+
+pub mod arm7;
+pub mod arm9;
+
+/// Replace the bits of `old` selected by `mask` with the ones of `val`.
+#[inline(always)]
+fn masked(old: u32, val: u32, mask: u32) -> u32 {
+    (old & !mask) | (val & mask)
+}
@@ -0,0 +1,186 @@
+use super::masked;
+use crate::bus::Access;
+use crate::{dma, gamecard, ipc, keypad, power, rtc, spi, timers};
+use crate::{Core, Engine};
+
+impl_io_access_fns!();
+
+fn read<E: Engine, A: Access>(core: &mut Core<E>, adr: u32) -> u32 {
+    match adr {
+        0x040000B0..=0x040000DC => {
+            let index = (adr as usize - 0x040000B0) / 12;
+            match (adr - 0x040000B0) % 12 {
+                0 => dma::sad::<E, false>(core, index),
+                4 => dma::dad::<E, false>(core, index),
+                _ => dma::cnt::<E, false>(core, index),
+            }
+        }
+        0x04000100..=0x0400010C => {
+            let index = (adr as usize >> 2) & 0b11;
+            let counter = timers::counter::<E, false>(core, index) as u32;
+            counter | (timers::cnt::<E, false>(core, index) as u32) << 16
+        }
+        0x04000130 => {
+            let keyinput = core.keypad.keyinput() as u32;
+            keyinput | (keypad::keycnt::<E, false>(core) as u32) << 16
+        }
+        0x04000134 => (core.keypad.extkeyin() as u32) << 16,
+        0x04000138 => rtc::cnt(core) as u32,
+        0x04000180 => ipc::sync::<E, false>(core) as u32,
+        0x04000184 => ipc::fifo_cnt::<E, false>(core) as u32,
+        0x040001C0 => spi::cnt(core) as u32 | (spi::data(core) as u32) << 16,
+        0x04000300 => power::postflg::<E, false>(core) as u32,
+        0x04000304 => core.power.powcnt2() as u32,
+        0x04000400..=0x0400051C => core.spu.read(adr),
+        0x040001A0 => {
+            let auxspicnt = gamecard::auxspicnt::<E, false>(core) as u32;
+            auxspicnt | (gamecard::auxspidata::<E, false>(core) as u32) << 16
+        }
+        0x040001A4 => gamecard::romctrl::<E, false>(core),
+        0x04000204 => gamecard::exmemcnt::<E, false>(core) as u32,
+        0x04000208 => core.arm7.irq.ime(),
+        0x04000210 => core.arm7.irq.ie(),
+        0x04000214 => core.arm7.irq.if_(),
+        0x04100010 => {
+            if A::CPU {
+                gamecard::data::<E, false>(core)
+            } else {
+                gamecard::data_peek::<E, false>(core)
+            }
+        }
+        0x04100000 => {
+            if A::CPU {
+                ipc::fifo_recv::<E, false>(core)
+            } else {
+                ipc::fifo_peek::<E, false>(core)
+            }
+        }
+        _ => {
+            if A::CPU {
+                warn!(core.arm7.logger, "unhandled io read {adr:08X}");
+            }
+            0
+        }
+    }
+}
+
+fn write<E: Engine, A: Access>(core: &mut Core<E>, adr: u32, val: u32, mask: u32) {
+    match adr {
+        0x040000B0..=0x040000DC => {
+            let index = (adr as usize - 0x040000B0) / 12;
+            match (adr - 0x040000B0) % 12 {
+                0 => {
+                    let sad = dma::sad::<E, false>(core, index);
+                    dma::sad_set::<E, false>(core, index, masked(sad, val, mask));
+                }
+                4 => {
+                    let dad = dma::dad::<E, false>(core, index);
+                    dma::dad_set::<E, false>(core, index, masked(dad, val, mask));
+                }
+                _ => {
+                    let cnt = dma::cnt::<E, false>(core, index);
+                    dma::cnt_set::<E, false>(core, index, masked(cnt, val, mask));
+                }
+            }
+        }
+        0x04000100..=0x0400010C => {
+            let index = (adr as usize >> 2) & 0b11;
+            if mask & 0xFFFF != 0 {
+                let reload = timers::reload::<E, false>(core, index) as u32;
+                timers::reload_set::<E, false>(core, index, masked(reload, val, mask) as u16);
+            }
+            if mask >> 16 != 0 {
+                let cnt = timers::cnt::<E, false>(core, index) as u32;
+                timers::cnt_set::<E, false>(
+                    core,
+                    index,
+                    (masked(cnt << 16, val, mask) >> 16) as u16,
+                );
+            }
+        }
+        0x04000130 => {
+            if mask >> 16 != 0 {
+                let keycnt = (keypad::keycnt::<E, false>(core) as u32) << 16;
+                keypad::keycnt_set::<E, false>(core, (masked(keycnt, val, mask) >> 16) as u16);
+            }
+        }
+        0x04000138 => {
+            if mask & 0xFFFF != 0 {
+                let cnt = rtc::cnt(core) as u32;
+                rtc::cnt_set(core, masked(cnt, val, mask) as u16);
+            }
+        }
+        0x04000180 => {
+            if mask & 0xFFFF != 0 {
+                let sync = ipc::sync::<E, false>(core) as u32;
+                ipc::sync_set::<E, false>(core, masked(sync, val, mask) as u16);
+            }
+        }
+        0x04000184 => {
+            if mask & 0xFFFF != 0 {
+                let cnt = ipc::fifo_cnt::<E, false>(core) as u32;
+                ipc::fifo_cnt_set::<E, false>(core, masked(cnt, val, mask) as u16);
+            }
+        }
+        0x04000188 => ipc::fifo_send::<E, false>(core, val & mask),
+        0x040001C0 => {
+            if mask & 0xFFFF != 0 {
+                let cnt = spi::cnt(core) as u32;
+                spi::cnt_set(core, masked(cnt, val, mask) as u16);
+            }
+            if mask & 0xFF0000 != 0 {
+                spi::data_set(core, (val >> 16) as u8);
+            }
+        }
+        0x04000300 => {
+            if mask & 0xFF != 0 {
+                power::postflg_set::<E, false>(core, val as u8);
+            }
+            if mask & 0xFF00 != 0 {
+                power::haltcnt_set(core, (val >> 8) as u8);
+            }
+        }
+        0x04000304 => {
+            if mask & 0xFFFF != 0 {
+                let powcnt2 = core.power.powcnt2() as u32;
+                core.power.powcnt2_set(masked(powcnt2, val, mask) as u16);
+            }
+        }
+        0x04000400..=0x0400051C => core.spu.write(adr, val, mask),
+        0x040001A0 => {
+            if mask & 0xFFFF != 0 {
+                let auxspicnt = gamecard::auxspicnt::<E, false>(core) as u32;
+                gamecard::auxspicnt_set::<E, false>(core, masked(auxspicnt, val, mask) as u16);
+            }
+            if mask & 0xFF0000 != 0 {
+                gamecard::auxspidata_set::<E, false>(core, (val >> 16) as u8);
+            }
+        }
+        0x040001A4 => {
+            let romctrl = gamecard::romctrl::<E, false>(core);
+            gamecard::romctrl_set::<E, false>(core, masked(romctrl, val, mask));
+        }
+        0x040001A8 | 0x040001AC => {
+            let word = (adr as usize >> 2) & 0b1;
+            gamecard::command_set::<E, false>(core, word, val, mask);
+        }
+        0x040001B0..=0x040001B8 => gamecard::seed_set::<E, false>(core, adr, val, mask),
+        0x04000204 => {
+            if mask & 0xFFFF != 0 {
+                let exmemcnt = gamecard::exmemcnt::<E, false>(core) as u32;
+                gamecard::exmemcnt_set::<E, false>(core, masked(exmemcnt, val, mask) as u16);
+            }
+        }
+        0x04000208 => core
+            .arm7
+            .irq
+            .ime_set(masked(core.arm7.irq.ime(), val, mask)),
+        0x04000210 => core.arm7.irq.ie_set(masked(core.arm7.irq.ie(), val, mask)),
+        0x04000214 => core.arm7.irq.acknowledge(val & mask),
+        _ => {
+            if A::CPU {
+                warn!(core.arm7.logger, "unhandled io write {adr:08X} {val:08X}");
+            }
+        }
+    }
+}
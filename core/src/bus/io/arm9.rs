@@ -0,0 +1,171 @@
+use super::masked;
+use crate::bus::Access;
+use crate::{dma, gamecard, ipc, keypad, power, timers};
+use crate::{Core, Engine};
+
+impl_io_access_fns!();
+
+fn read<E: Engine, A: Access>(core: &mut Core<E>, adr: u32) -> u32 {
+    match adr {
+        0x040000B0..=0x040000DC => {
+            let index = (adr as usize - 0x040000B0) / 12;
+            match (adr - 0x040000B0) % 12 {
+                0 => dma::sad::<E, true>(core, index),
+                4 => dma::dad::<E, true>(core, index),
+                _ => dma::cnt::<E, true>(core, index),
+            }
+        }
+        0x040000E0..=0x040000EC => dma::fill(core, (adr as usize >> 2) & 0b11),
+        0x04000100..=0x0400010C => {
+            let index = (adr as usize >> 2) & 0b11;
+            let counter = timers::counter::<E, true>(core, index) as u32;
+            counter | (timers::cnt::<E, true>(core, index) as u32) << 16
+        }
+        0x04000130 => {
+            let keyinput = core.keypad.keyinput() as u32;
+            keyinput | (keypad::keycnt::<E, true>(core) as u32) << 16
+        }
+        0x04000180 => ipc::sync::<E, true>(core) as u32,
+        0x04000184 => ipc::fifo_cnt::<E, true>(core) as u32,
+        0x04000280..=0x040002BC => core.arm9.math.read(core.scheduler.now(), adr),
+        0x04000300 => power::postflg::<E, true>(core) as u32,
+        0x04000304 => core.power.powcnt1() as u32,
+        0x040001A0 => {
+            let auxspicnt = gamecard::auxspicnt::<E, true>(core) as u32;
+            auxspicnt | (gamecard::auxspidata::<E, true>(core) as u32) << 16
+        }
+        0x040001A4 => gamecard::romctrl::<E, true>(core),
+        0x04000204 => gamecard::exmemcnt::<E, true>(core) as u32,
+        0x04000208 => core.arm9.irq.ime(),
+        0x04000210 => core.arm9.irq.ie(),
+        0x04000214 => core.arm9.irq.if_(),
+        0x04100010 => {
+            if A::CPU {
+                gamecard::data::<E, true>(core)
+            } else {
+                gamecard::data_peek::<E, true>(core)
+            }
+        }
+        0x04100000 => {
+            if A::CPU {
+                ipc::fifo_recv::<E, true>(core)
+            } else {
+                ipc::fifo_peek::<E, true>(core)
+            }
+        }
+        _ => {
+            if A::CPU {
+                warn!(core.arm9.logger, "unhandled io read {adr:08X}");
+            }
+            0
+        }
+    }
+}
+
+fn write<E: Engine, A: Access>(core: &mut Core<E>, adr: u32, val: u32, mask: u32) {
+    match adr {
+        0x040000B0..=0x040000DC => {
+            let index = (adr as usize - 0x040000B0) / 12;
+            match (adr - 0x040000B0) % 12 {
+                0 => {
+                    let sad = dma::sad::<E, true>(core, index);
+                    dma::sad_set::<E, true>(core, index, masked(sad, val, mask));
+                }
+                4 => {
+                    let dad = dma::dad::<E, true>(core, index);
+                    dma::dad_set::<E, true>(core, index, masked(dad, val, mask));
+                }
+                _ => {
+                    let cnt = dma::cnt::<E, true>(core, index);
+                    dma::cnt_set::<E, true>(core, index, masked(cnt, val, mask));
+                }
+            }
+        }
+        0x040000E0..=0x040000EC => {
+            let index = (adr as usize >> 2) & 0b11;
+            let fill = dma::fill(core, index);
+            dma::fill_set(core, index, masked(fill, val, mask));
+        }
+        0x04000100..=0x0400010C => {
+            let index = (adr as usize >> 2) & 0b11;
+            if mask & 0xFFFF != 0 {
+                let reload = timers::reload::<E, true>(core, index) as u32;
+                timers::reload_set::<E, true>(core, index, masked(reload, val, mask) as u16);
+            }
+            if mask >> 16 != 0 {
+                let cnt = timers::cnt::<E, true>(core, index) as u32;
+                timers::cnt_set::<E, true>(
+                    core,
+                    index,
+                    (masked(cnt << 16, val, mask) >> 16) as u16,
+                );
+            }
+        }
+        0x04000130 => {
+            if mask >> 16 != 0 {
+                let keycnt = (keypad::keycnt::<E, true>(core) as u32) << 16;
+                keypad::keycnt_set::<E, true>(core, (masked(keycnt, val, mask) >> 16) as u16);
+            }
+        }
+        0x04000180 => {
+            if mask & 0xFFFF != 0 {
+                let sync = ipc::sync::<E, true>(core) as u32;
+                ipc::sync_set::<E, true>(core, masked(sync, val, mask) as u16);
+            }
+        }
+        0x04000184 => {
+            if mask & 0xFFFF != 0 {
+                let cnt = ipc::fifo_cnt::<E, true>(core) as u32;
+                ipc::fifo_cnt_set::<E, true>(core, masked(cnt, val, mask) as u16);
+            }
+        }
+        0x04000188 => ipc::fifo_send::<E, true>(core, val & mask),
+        0x04000280..=0x040002BC => core.arm9.math.write(core.scheduler.now(), adr, val, mask),
+        0x04000300 => {
+            if mask & 0xFF != 0 {
+                power::postflg_set::<E, true>(core, val as u8);
+            }
+        }
+        0x04000304 => {
+            if mask & 0xFFFF != 0 {
+                let powcnt1 = core.power.powcnt1() as u32;
+                core.power.powcnt1_set(masked(powcnt1, val, mask) as u16);
+            }
+        }
+        0x040001A0 => {
+            if mask & 0xFFFF != 0 {
+                let auxspicnt = gamecard::auxspicnt::<E, true>(core) as u32;
+                gamecard::auxspicnt_set::<E, true>(core, masked(auxspicnt, val, mask) as u16);
+            }
+            if mask & 0xFF0000 != 0 {
+                gamecard::auxspidata_set::<E, true>(core, (val >> 16) as u8);
+            }
+        }
+        0x040001A4 => {
+            let romctrl = gamecard::romctrl::<E, true>(core);
+            gamecard::romctrl_set::<E, true>(core, masked(romctrl, val, mask));
+        }
+        0x040001A8 | 0x040001AC => {
+            let word = (adr as usize >> 2) & 0b1;
+            gamecard::command_set::<E, true>(core, word, val, mask);
+        }
+        0x040001B0..=0x040001B8 => gamecard::seed_set::<E, true>(core, adr, val, mask),
+        0x04000204 => {
+            if mask & 0xFFFF != 0 {
+                let exmemcnt = gamecard::exmemcnt::<E, true>(core) as u32;
+                gamecard::exmemcnt_set::<E, true>(core, masked(exmemcnt, val, mask) as u16);
+            }
+        }
+        0x04000208 => core
+            .arm9
+            .irq
+            .ime_set(masked(core.arm9.irq.ime(), val, mask)),
+        0x04000210 => core.arm9.irq.ie_set(masked(core.arm9.irq.ie(), val, mask)),
+        0x04000214 => core.arm9.irq.acknowledge(val & mask),
+        _ => {
+            if A::CPU {
+                warn!(core.arm9.logger, "unhandled io write {adr:08X} {val:08X}");
+            }
+        }
+    }
+}
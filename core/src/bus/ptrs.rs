@@ -40,6 +40,12 @@ impl PtrTable {
         }
     }
 
+    /// An empty table built on the heap, it's too large for the stack of a test thread.
+    pub fn new_boxed() -> Box<Self> {
+        // no attributes and null pointers are all zero bits.
+        unsafe { Box::new_zeroed().assume_init() }
+    }
+
     #[inline]
     pub fn adr_to_page(adr: u32) -> usize {
         adr as usize >> Self::PG_SHIFT
@@ -1,7 +1,25 @@
 use std::path::Display;
 
+mod crypto;
+pub use crypto::{
+    decrypt_secure_area, destroy_secure_area_marker, encrypt_secure_area, key1_table, Key1, Key2,
+    SecureArea, KEY1_TABLE_LEN, SECURE_AREA_END, SECURE_AREA_START,
+};
+
+mod nitrofs;
+pub use nitrofs::{NitroEntry, NitroEntryKind, NitroFs, ROOT_DIR_ID};
+
+mod overlay;
+pub use overlay::{FileOverlay, VirtualRom};
+
+mod banner;
+pub use banner::{Banner, IconFrame, ANIMATED_VERSION, ICON_SIZE};
+
+mod builder;
+pub use builder::{RomBuilder, RomDir, RomFile, RomNode, Section};
+
 use crate::error::{Error, Result};
-use crate::mmap;
+use crate::{mmap, MainMemorySize};
 
 const CART_HEADER_LEN: usize = 0x200;
 
@@ -19,8 +37,13 @@ impl<'a> Cartridge<'a> {
         Ok(Self(data))
     }
 
-    pub fn validate(&self) -> Result<()> {
+    pub fn validate(&self, main_memory_size: MainMemorySize) -> Result<()> {
         let header = self.header();
+        let main_memory_end = mmap::main_memory_end(main_memory_size.bytes());
+
+        // the last 0x40200 bytes of main memory are reserved for the header and BIOS data.
+        let load_end = main_memory_end - 0x40200;
+        let load_size = load_end - mmap::MAIN_MEMORY_START;
 
         // arm9 loading.
         let arm9_rom = header.arm9_rom_offset();
@@ -55,29 +78,29 @@ impl<'a> Cartridge<'a> {
             };
         }
 
-        check_range!(arm9_entry, 0x2000000..0x23bfe00; "ARM9 entry address: outside of range");
+        check_range!(arm9_entry, 0x2000000..load_end; "ARM9 entry address: outside of range");
         check_range!(
             arm9_ram,
-            0x2000000..0x23bfe00;
+            0x2000000..load_end;
             "ARM9 RAM address: outside of range"
         );
-        check_range!(arm9_size, 0..=0x3bfe00; "ARM9 ROM size too large.");
+        check_range!(arm9_size, 0..=load_size; "ARM9 ROM size too large.");
 
         check_range!(
             arm7_entry,
-            0x2000000..0x23bfe00, 0x37f8000..0x3807e00;
+            0x2000000..load_end, 0x37f8000..0x3807e00;
             "ARM7 entry address: outside of range"
         );
         let arm7_second_area_start = 0x37f8000;
         check_range!(
             arm7_ram,
-            0x2000000..0x23bfe00, 0x37f8000..0x3807e00;
+            0x2000000..load_end, 0x37f8000..0x3807e00;
             "ARM7 RAM address: outside of range"
         );
         if arm7_ram >= arm7_second_area_start {
             check_range!(arm7_size, 0..=0xFE00; "ARM7 ROM size too large.");
         } else {
-            check_range!(arm7_size, 0..=0x3bfe00; "ARM7 ROM size too large.");
+            check_range!(arm7_size, 0..=load_size; "ARM7 ROM size too large.");
         }
 
         // make sure rom offsets are aligned and a above 0x4000.
@@ -111,7 +134,7 @@ impl<'a> Cartridge<'a> {
 
         // make sure that the loaded rom doesn't go outside the bounds of the main memory.
 
-        let main_memory_range = (mmap::MAIN_MEMORY_START..mmap::MAIN_MEMORY_END);
+        let main_memory_range = (mmap::MAIN_MEMORY_START..main_memory_end);
 
         let arm9_rom_load_end = arm9_ram + arm9_size;
         if !main_memory_range.contains(&arm9_rom_load_end) {
@@ -132,6 +155,10 @@ impl<'a> Cartridge<'a> {
         Ok(())
     }
 
+    pub fn data(&self) -> &'a [u8] {
+        self.0
+    }
+
     pub fn header(&self) -> CartridgeHeader<'a> {
         debug_assert!(self.0.len() >= CART_HEADER_LEN);
         CartridgeHeader(&self.0[0..CART_HEADER_LEN])
@@ -150,6 +177,29 @@ impl<'a> Cartridge<'a> {
         let size = header.arm7_size() as usize;
         &self.0[start..(start + size)]
     }
+
+    /// The file system with the files and directories of the game.
+    pub fn nitro_fs(&self) -> Result<NitroFs<'a>> {
+        NitroFs::new(self)
+    }
+
+    pub fn read_file(&self, path: &str) -> Result<&'a [u8]> {
+        self.nitro_fs()?.read_file(path)
+    }
+
+    /// The banner at the offset in the header, none for games without one.
+    pub fn banner(&self) -> Result<Option<Banner>> {
+        let offs = self.header().banner_offset() as usize;
+        if offs == 0 {
+            return Ok(None);
+        }
+        match self.0.get(offs..) {
+            Some(data) => Banner::parse(data).map(Some),
+            None => Err(Error::Cartridge(format!(
+                "the banner at '{offs:x}' is outside of the cartridge"
+            ))),
+        }
+    }
 }
 
 pub struct CartridgeHeader<'a>(&'a [u8]);
@@ -183,8 +233,14 @@ impl<'a> CartridgeHeader<'a> {
         self.0
     }
 
+    /// The title of up to 12 ASCII characters, padded with zeros.
     pub fn game_title(&self) -> &str {
-        todo!()
+        let title = &self.0[..12];
+        let title = match std::str::from_utf8(title) {
+            Ok(title) => title,
+            Err(err) => std::str::from_utf8(&title[..err.valid_up_to()]).unwrap(),
+        };
+        title.trim_end_matches('\0')
     }
 
     unsafe fn from_offs<T>(&self, offs: usize) -> T {
@@ -192,6 +248,8 @@ impl<'a> CartridgeHeader<'a> {
     }
 
     impl_header_fields!(
+        game_code, u32, 0x00c;
+        key2_seed_select, u8, 0x013;
         arm9_rom_offset, u32, 0x020;
         arm9_entry_address, u32, 0x024;
         arm9_ram_address, u32, 0x028;
@@ -200,5 +258,17 @@ impl<'a> CartridgeHeader<'a> {
         arm7_entry_address, u32, 0x034;
         arm7_ram_address, u32, 0x038;
         arm7_size, u32, 0x03c;
+        fnt_offset, u32, 0x040;
+        fnt_size, u32, 0x044;
+        fat_offset, u32, 0x048;
+        fat_size, u32, 0x04c;
+        arm9_overlay_offset, u32, 0x050;
+        arm9_overlay_size, u32, 0x054;
+        arm7_overlay_offset, u32, 0x058;
+        arm7_overlay_size, u32, 0x05c;
+        banner_offset, u32, 0x068;
+        secure_area_crc, u16, 0x06c;
+        rom_size, u32, 0x080;
+        header_crc, u16, 0x15e;
     );
 }
@@ -0,0 +1,298 @@
+//! The banner of a game with its titles and icon, shown by the firmware menu.
+
+use crate::error::{Error, Result};
+use crate::firmware::Language;
+
+/// Width and height of the icon.
+pub const ICON_SIZE: usize = 32;
+const ICON_BITMAP_LEN: usize = ICON_SIZE * ICON_SIZE / 2;
+const ICON_PALETTE_LEN: usize = 16 * 2;
+
+const ICON_BITMAP: usize = 0x20;
+const ICON_PALETTE: usize = 0x220;
+const TITLES: usize = 0x240;
+const TITLE_LEN: usize = 0x100;
+/// The DSi animated icon: 8 bitmaps, 8 palettes and a sequence of up to 64 frames.
+const ANIMATION_BITMAPS: usize = 0x1240;
+const ANIMATION_PALETTES: usize = 0x2240;
+const ANIMATION_SEQUENCE: usize = 0x2340;
+const ANIMATION_SEQUENCE_LEN: usize = 64;
+
+/// Version of banners with the DSi animated icon.
+pub const ANIMATED_VERSION: u16 = 0x103;
+
+/// A frame of the animated icon.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IconFrame {
+    /// RGBA pixels, row by row.
+    pub icon: Box<[u8]>,
+    /// Number of 60Hz frames it's shown for.
+    pub duration: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Banner {
+    pub version: u16,
+    /// Japanese, English, French, German, Italian and Spanish, then Chinese from version 2 and
+    /// Korean from version 3 on. The lines of a title are separated by '\n'.
+    pub titles: Vec<String>,
+    /// RGBA pixels of the icon, row by row.
+    pub icon: Box<[u8]>,
+    /// The animated icon of DSi banners, empty for others.
+    pub animation: Vec<IconFrame>,
+}
+
+impl Banner {
+    /// Length of a banner of `version`.
+    pub fn len_of(version: u16) -> usize {
+        match version {
+            2 => 0x940,
+            3 => 0xA40,
+            ANIMATED_VERSION => 0x23C0,
+            _ => 0x840,
+        }
+    }
+
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let version = match data.get(..2) {
+            Some(version) => u16::from_le_bytes(version.try_into().unwrap()),
+            None => return Err(Error::Cartridge("the banner is empty".into())),
+        };
+        if !matches!(version, 1 | 2 | 3 | ANIMATED_VERSION) {
+            return Err(Error::Cartridge(format!(
+                "unknown banner version '{version:x}'"
+            )));
+        }
+        let len = Self::len_of(version);
+        if data.len() < len {
+            return Err(Error::Cartridge(format!(
+                "the banner of version '{version:x}' has to be '{len:x}' bytes but got '{:x}'",
+                data.len()
+            )));
+        }
+
+        let title_count = match version {
+            1 => 6,
+            2 => 7,
+            _ => 8,
+        };
+        let titles = data[TITLES..TITLES + title_count * TITLE_LEN]
+            .chunks_exact(TITLE_LEN)
+            .map(|title| {
+                let units = title
+                    .chunks_exact(2)
+                    .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+                    .take_while(|&unit| unit != 0)
+                    .collect::<Vec<_>>();
+                String::from_utf16_lossy(&units)
+            })
+            .collect();
+
+        let icon = decode_icon(
+            &data[ICON_BITMAP..ICON_BITMAP + ICON_BITMAP_LEN],
+            &data[ICON_PALETTE..ICON_PALETTE + ICON_PALETTE_LEN],
+            false,
+            false,
+        );
+
+        let mut animation = Vec::new();
+        if version == ANIMATED_VERSION {
+            let sequence =
+                &data[ANIMATION_SEQUENCE..ANIMATION_SEQUENCE + ANIMATION_SEQUENCE_LEN * 2];
+            for frame in sequence.chunks_exact(2) {
+                let frame = u16::from_le_bytes([frame[0], frame[1]]);
+                if frame == 0 {
+                    break;
+                }
+                let bitmap = ANIMATION_BITMAPS + (frame >> 8 & 0b111) as usize * ICON_BITMAP_LEN;
+                let palette =
+                    ANIMATION_PALETTES + (frame >> 11 & 0b111) as usize * ICON_PALETTE_LEN;
+                animation.push(IconFrame {
+                    icon: decode_icon(
+                        &data[bitmap..bitmap + ICON_BITMAP_LEN],
+                        &data[palette..palette + ICON_PALETTE_LEN],
+                        get_bit!(frame, 14),
+                        get_bit!(frame, 15),
+                    ),
+                    duration: frame as u8,
+                });
+            }
+        }
+
+        Ok(Self {
+            version,
+            titles,
+            icon,
+            animation,
+        })
+    }
+
+    pub fn title(&self, language: Language) -> Option<&str> {
+        self.titles.get(language as usize).map(String::as_str)
+    }
+}
+
+/// Convert a bitmap of 4x4 tiles of 8x8 4bpp pixels to RGBA, colour 0 is transparent.
+fn decode_icon(bitmap: &[u8], palette: &[u8], flip_x: bool, flip_y: bool) -> Box<[u8]> {
+    let mut rgba = vec![0; ICON_SIZE * ICON_SIZE * 4].into_boxed_slice();
+    for y in 0..ICON_SIZE {
+        for x in 0..ICON_SIZE {
+            let tile = (y / 8) * (ICON_SIZE / 8) + x / 8;
+            let byte = bitmap[tile * 32 + (y % 8) * 4 + (x % 8) / 2];
+            let index = if x % 2 == 0 { byte & 0xF } else { byte >> 4 } as usize;
+            if index == 0 {
+                continue;
+            }
+            let colour = u16::from_le_bytes([palette[index * 2], palette[index * 2 + 1]]);
+            let expand = |shift: u16| {
+                let val = (colour >> shift & 0x1F) as u8;
+                val << 3 | val >> 2
+            };
+            let out_x = if flip_x { ICON_SIZE - 1 - x } else { x };
+            let out_y = if flip_y { ICON_SIZE - 1 - y } else { y };
+            let offs = (out_y * ICON_SIZE + out_x) * 4;
+            rgba[offs..offs + 4].copy_from_slice(&[expand(0), expand(5), expand(10), 0xFF]);
+        }
+    }
+    rgba
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::Cartridge;
+
+    const RED: u16 = 0x001F;
+    const GREEN: u16 = 0x03E0;
+    const BLUE: u16 = 0x7C00;
+
+    fn write_u16(data: &mut [u8], offs: usize, val: u16) {
+        data[offs..offs + 2].copy_from_slice(&val.to_le_bytes());
+    }
+
+    /// The offset of the 4bpp pixel at `x`, `y` in a bitmap of tiles.
+    fn pixel(x: usize, y: usize) -> usize {
+        ((y / 8) * 4 + x / 8) * 32 + (y % 8) * 4 + (x % 8) / 2
+    }
+
+    /// RGBA of the pixel at `x`, `y` of a decoded icon.
+    fn rgba(icon: &[u8], x: usize, y: usize) -> &[u8] {
+        let offs = (y * ICON_SIZE + x) * 4;
+        &icon[offs..offs + 4]
+    }
+
+    /// A banner of `version` with the titles "title <n>" on a second line after "é\u{3042}", an
+    /// icon with red at 9,1 and blue at 1,8, and for the animated version a green pixel at 0,0 of
+    /// bitmap 1 shown with palette 2, flipped horizontally for 5 frames, then vertically for 7.
+    fn banner(version: u16) -> Vec<u8> {
+        let mut data = vec![0; Banner::len_of(version)];
+        write_u16(&mut data, 0, version);
+        let titles = match version {
+            1 => 6,
+            2 => 7,
+            _ => 8,
+        };
+        for i in 0..titles {
+            let title = format!("é\u{3042}\ntitle {i}");
+            for (j, unit) in title.encode_utf16().enumerate() {
+                write_u16(&mut data, TITLES + i * TITLE_LEN + j * 2, unit);
+            }
+        }
+        data[ICON_BITMAP + pixel(9, 1)] = 0x10;
+        data[ICON_BITMAP + pixel(1, 8)] = 0x20;
+        write_u16(&mut data, ICON_PALETTE + 2, RED);
+        write_u16(&mut data, ICON_PALETTE + 4, BLUE);
+        if version == ANIMATED_VERSION {
+            data[ANIMATION_BITMAPS + ICON_BITMAP_LEN] = 0x01;
+            write_u16(
+                &mut data,
+                ANIMATION_PALETTES + 2 * ICON_PALETTE_LEN + 2,
+                GREEN,
+            );
+            write_u16(
+                &mut data,
+                ANIMATION_SEQUENCE,
+                5 | 1 << 8 | 2 << 11 | b!(14) as u16,
+            );
+            write_u16(
+                &mut data,
+                ANIMATION_SEQUENCE + 2,
+                7 | 1 << 8 | 2 << 11 | b!(15) as u16,
+            );
+        }
+        data
+    }
+
+    #[test]
+    fn titles_of_each_version() {
+        for (version, len, titles) in [
+            (1, 0x840, 6),
+            (2, 0x940, 7),
+            (3, 0xA40, 8),
+            (ANIMATED_VERSION, 0x23C0, 8),
+        ] {
+            assert_eq!(Banner::len_of(version), len);
+            let data = banner(version);
+            assert!(Banner::parse(&data[..len - 1]).is_err());
+            let banner = Banner::parse(&data).unwrap();
+            assert_eq!(banner.version, version);
+            assert_eq!(banner.titles.len(), titles);
+            assert_eq!(banner.title(Language::English), Some("é\u{3042}\ntitle 1"));
+            assert_eq!(banner.animation.is_empty(), version != ANIMATED_VERSION);
+        }
+        assert!(Banner::parse(&[]).is_err());
+        let mut unknown = banner(3);
+        unknown[0] = 4;
+        assert!(Banner::parse(&unknown).is_err());
+    }
+
+    #[test]
+    fn icon_is_decoded_from_tiles() {
+        let banner = Banner::parse(&banner(1)).unwrap();
+        assert_eq!(rgba(&banner.icon, 9, 1), [0xFF, 0, 0, 0xFF]);
+        assert_eq!(rgba(&banner.icon, 1, 8), [0, 0, 0xFF, 0xFF]);
+        // colour 0 is transparent.
+        assert_eq!(rgba(&banner.icon, 8, 1), [0; 4]);
+        assert_eq!(banner.icon.iter().filter(|&&val| val != 0).count(), 2 * 2);
+    }
+
+    #[test]
+    fn animated_frames_are_flipped() {
+        let banner = Banner::parse(&banner(ANIMATED_VERSION)).unwrap();
+        let green = [0, 0xFF, 0, 0xFF];
+        let [first, second] = &banner.animation[..] else {
+            panic!("expected 2 frames, got {}", banner.animation.len());
+        };
+        assert_eq!(first.duration, 5);
+        assert_eq!(rgba(&first.icon, ICON_SIZE - 1, 0), green);
+        assert_eq!(rgba(&first.icon, 0, 0), [0; 4]);
+        assert_eq!(second.duration, 7);
+        assert_eq!(rgba(&second.icon, 0, ICON_SIZE - 1), green);
+    }
+
+    #[test]
+    fn game_title_is_trimmed() {
+        let mut rom = vec![0; 0x200 + Banner::len_of(1)];
+        rom[..12].copy_from_slice(b"GAME\0\0\0\0\0\0\0\0");
+        rom[0x68..0x6C].copy_from_slice(&0x200u32.to_le_bytes());
+        rom[0x200..].copy_from_slice(&banner(1));
+        let cartridge = Cartridge::new(&rom).unwrap();
+        assert_eq!(cartridge.header().game_title(), "GAME");
+        assert_eq!(cartridge.banner().unwrap().unwrap().version, 1);
+
+        // the title ends at the first byte that isn't valid UTF-8.
+        rom[..12].copy_from_slice(b"FULL TITLE\xFFX");
+        assert_eq!(
+            Cartridge::new(&rom).unwrap().header().game_title(),
+            "FULL TITLE"
+        );
+        rom[..12].copy_from_slice(b"TWELVE CHARS");
+        assert_eq!(
+            Cartridge::new(&rom).unwrap().header().game_title(),
+            "TWELVE CHARS"
+        );
+
+        rom[0x68..0x6C].fill(0);
+        assert_eq!(Cartridge::new(&rom).unwrap().banner().unwrap(), None);
+    }
+}
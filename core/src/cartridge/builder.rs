@@ -0,0 +1,816 @@
+//! Packing the header, binaries, overlays, banner and file system of a game into a ROM.
+
+use std::ops::Range;
+
+use super::{
+    Banner, Cartridge, CartridgeHeader, NitroEntry, NitroEntryKind, SecureArea, ROOT_DIR_ID,
+};
+use crate::crc::crc16;
+use crate::error::{Error, Result};
+
+/// Sections that don't keep their offset are placed at the end of the ROM with this alignment.
+const SECTION_ALIGN: usize = 0x200;
+/// The header is followed by a reserved area up to the ARM9 binary.
+const RESERVED_END: usize = 0x4000;
+const OVERLAY_ENTRY_LEN: usize = 0x20;
+const OVERLAY_FILE_ID: usize = 0x18;
+/// The SDK puts 12 bytes starting with this after the ARM9 binary, which aren't part of its size.
+const ARM9_FOOTER_MAGIC: u32 = 0xDEC0_0621;
+const ARM9_FOOTER_LEN: usize = 12;
+
+/// Data of the ROM with the offset it had, which it keeps when building as long as it fits
+/// there.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Section {
+    pub data: Vec<u8>,
+    pub offset: Option<u32>,
+}
+
+impl Section {
+    pub fn new(data: Vec<u8>) -> Self {
+        Self { data, offset: None }
+    }
+
+    fn extract(rom: &[u8], range: Range<usize>) -> Result<Self> {
+        let data = rom.get(range.clone()).ok_or_else(|| {
+            error(format!(
+                "'{:x}..{:x}' is outside of the ROM",
+                range.start, range.end
+            ))
+        })?;
+        Ok(Self {
+            data: data.to_vec(),
+            offset: Some(range.start as u32),
+        })
+    }
+
+    /// Replace the data, keeping the offset only if the data still fits.
+    pub fn set_data(&mut self, data: Vec<u8>) {
+        if data.len() > self.data.len() {
+            self.offset = None;
+        }
+        self.data = data;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RomFile {
+    pub name: String,
+    pub data: Section,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RomNode {
+    Dir(RomDir),
+    File(RomFile),
+}
+
+/// A directory of the file system. The IDs are kept when building if they are still consistent,
+/// otherwise all directories and files are renumbered.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RomDir {
+    pub name: String,
+    pub id: Option<u16>,
+    /// The files of the directory have consecutive IDs starting at this one.
+    pub first_file_id: Option<u16>,
+    pub entries: Vec<RomNode>,
+}
+
+impl RomDir {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            ..Default::default()
+        }
+    }
+
+    fn node_mut(&mut self, name: &str) -> Option<&mut RomNode> {
+        self.entries.iter_mut().find(|node| match node {
+            RomNode::Dir(dir) => dir.name == name,
+            RomNode::File(file) => file.name == name,
+        })
+    }
+}
+
+/// The parts of a ROM, which [`RomBuilder::build`] packs into a ROM with a regenerated FNT and
+/// FAT and fixed up offsets and checksums in the header. Parts that weren't changed keep their
+/// offset, so the ROM a builder was extracted from is rebuilt byte for byte.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RomBuilder {
+    /// The header, the offsets, sizes and checksums in it are regenerated.
+    pub header: [u8; CartridgeHeader::LEN],
+    pub arm9: Section,
+    /// Bytes right after the ARM9 binary which aren't part of its size.
+    pub arm9_footer: Vec<u8>,
+    pub arm9_overlay_table: Section,
+    /// The files of the entries of the overlay table, in the same order.
+    pub arm9_overlays: Vec<Section>,
+    pub arm7: Section,
+    pub arm7_overlay_table: Section,
+    pub arm7_overlays: Vec<Section>,
+    pub fnt_offset: Option<u32>,
+    pub fat_offset: Option<u32>,
+    pub banner: Section,
+    pub root: RomDir,
+    /// Bytes up to the ROM size outside of the other parts, like the reserved area after the
+    /// header. They're kept where nothing else is placed.
+    pub gaps: Vec<Section>,
+    /// Data after the used part of the ROM, like the signature of download play games.
+    pub tail: Vec<u8>,
+    /// The ROM is padded with 0xFF up to this length.
+    pub padded_len: usize,
+}
+
+fn error(msg: impl std::fmt::Display) -> Error {
+    Error::Cartridge(format!("ROM builder: {msg}"))
+}
+
+fn read_u16(data: &[u8], offs: usize) -> u16 {
+    u16::from_le_bytes(data[offs..offs + 2].try_into().unwrap())
+}
+
+fn read_u32(data: &[u8], offs: usize) -> u32 {
+    u32::from_le_bytes(data[offs..offs + 4].try_into().unwrap())
+}
+
+fn write_u16(data: &mut [u8], offs: usize, val: u16) {
+    data[offs..offs + 2].copy_from_slice(&val.to_le_bytes());
+}
+
+fn write_u32(data: &mut [u8], offs: usize, val: u32) {
+    data[offs..offs + 4].copy_from_slice(&val.to_le_bytes());
+}
+
+/// IDs of the directories and files of the file system.
+struct Numbering<'b> {
+    /// Ordered by ID once complete.
+    dirs: Vec<DirRecord<'b>>,
+    files: Vec<(u16, &'b RomFile)>,
+}
+
+struct DirRecord<'b> {
+    id: u16,
+    parent: u16,
+    first_file_id: u16,
+    dir: &'b RomDir,
+    /// IDs of the subdirectories in the order of the entries.
+    sub_ids: Vec<u16>,
+}
+
+impl<'b> Numbering<'b> {
+    /// The IDs the directories and files have, if they're all there and don't collide.
+    fn preserved(root: &'b RomDir, overlay_ids: &[u16]) -> Option<Self> {
+        fn collect<'b>(dir: &'b RomDir, parent: u16, numbering: &mut Numbering<'b>) -> Option<u16> {
+            let (id, first_file_id) = (dir.id?, dir.first_file_id?);
+            let index = numbering.dirs.len();
+            numbering.dirs.push(DirRecord {
+                id,
+                parent,
+                first_file_id,
+                dir,
+                sub_ids: Vec::new(),
+            });
+            let mut file_id = first_file_id;
+            for node in &dir.entries {
+                match node {
+                    RomNode::File(file) => {
+                        numbering.files.push((file_id, file));
+                        file_id = file_id.checked_add(1)?;
+                    }
+                    RomNode::Dir(sub) => {
+                        let sub_id = collect(sub, id, numbering)?;
+                        numbering.dirs[index].sub_ids.push(sub_id);
+                    }
+                }
+            }
+            Some(id)
+        }
+
+        let mut numbering = Self {
+            dirs: Vec::new(),
+            files: Vec::new(),
+        };
+        collect(root, ROOT_DIR_ID, &mut numbering)?;
+        numbering.dirs.sort_by_key(|record| record.id);
+        let dirs_dense = numbering
+            .dirs
+            .iter()
+            .enumerate()
+            .all(|(i, record)| record.id as usize == ROOT_DIR_ID as usize + i);
+        let mut file_ids = numbering
+            .files
+            .iter()
+            .map(|&(id, _)| id)
+            .chain(overlay_ids.iter().copied())
+            .collect::<Vec<_>>();
+        file_ids.sort_unstable();
+        let files_unique = file_ids.windows(2).all(|ids| ids[0] != ids[1]);
+        let files_below_dirs = file_ids.last().is_none_or(|&id| id < ROOT_DIR_ID);
+        (root.id == Some(ROOT_DIR_ID) && dirs_dense && files_unique && files_below_dirs)
+            .then_some(numbering)
+    }
+
+    /// Directories numbered depth first, the files of a directory numbered before those of its
+    /// subdirectories, after the overlays.
+    fn renumbered(root: &'b RomDir, overlay_ids: &[u16]) -> Result<Self> {
+        fn collect<'b>(
+            dir: &'b RomDir,
+            parent: u16,
+            next_dir_id: &mut u32,
+            next_file_id: &mut u32,
+            numbering: &mut Numbering<'b>,
+        ) -> Result<u16> {
+            let id = u16::try_from(*next_dir_id).map_err(|_| error("too many directories"))?;
+            *next_dir_id += 1;
+            let index = numbering.dirs.len();
+            numbering.dirs.push(DirRecord {
+                id,
+                parent,
+                first_file_id: *next_file_id as u16,
+                dir,
+                sub_ids: Vec::new(),
+            });
+            for node in &dir.entries {
+                if let RomNode::File(file) = node {
+                    if *next_file_id >= ROOT_DIR_ID as u32 {
+                        return Err(error("too many files"));
+                    }
+                    numbering.files.push((*next_file_id as u16, file));
+                    *next_file_id += 1;
+                }
+            }
+            for node in &dir.entries {
+                if let RomNode::Dir(sub) = node {
+                    let sub_id = collect(sub, id, next_dir_id, next_file_id, numbering)?;
+                    numbering.dirs[index].sub_ids.push(sub_id);
+                }
+            }
+            Ok(id)
+        }
+
+        let mut numbering = Self {
+            dirs: Vec::new(),
+            files: Vec::new(),
+        };
+        let mut next_dir_id = ROOT_DIR_ID as u32;
+        let mut next_file_id = overlay_ids.iter().max().map_or(0, |&id| id as u32 + 1);
+        collect(
+            root,
+            ROOT_DIR_ID,
+            &mut next_dir_id,
+            &mut next_file_id,
+            &mut numbering,
+        )?;
+        Ok(numbering)
+    }
+
+    fn fnt(&self) -> Result<Vec<u8>> {
+        let main_len = self.dirs.len() * 8;
+        let mut main = Vec::with_capacity(main_len);
+        let mut sub_tables = Vec::new();
+        for record in &self.dirs {
+            // the root directory has the number of directories in place of its parent.
+            let parent = if record.id == ROOT_DIR_ID {
+                self.dirs.len() as u16
+            } else {
+                record.parent
+            };
+            main.extend_from_slice(&((main_len + sub_tables.len()) as u32).to_le_bytes());
+            main.extend_from_slice(&record.first_file_id.to_le_bytes());
+            main.extend_from_slice(&parent.to_le_bytes());
+
+            let mut sub_ids = record.sub_ids.iter();
+            for node in &record.dir.entries {
+                let (name, dir_bit) = match node {
+                    RomNode::Dir(dir) => (&dir.name, 0x80),
+                    RomNode::File(file) => (&file.name, 0),
+                };
+                if name.is_empty() || name.len() > 0x7F || name.contains('/') {
+                    return Err(error(format!("invalid name '{name}'")));
+                }
+                sub_tables.push(dir_bit | name.len() as u8);
+                sub_tables.extend_from_slice(name.as_bytes());
+                if dir_bit != 0 {
+                    sub_tables.extend_from_slice(&sub_ids.next().unwrap().to_le_bytes());
+                }
+            }
+            sub_tables.push(0);
+        }
+        main.extend_from_slice(&sub_tables);
+        Ok(main)
+    }
+}
+
+/// Places the sections in the ROM being built.
+struct Layout {
+    rom: Vec<u8>,
+    placed: Vec<Range<usize>>,
+}
+
+impl Layout {
+    /// Place `data` at `offset` if it doesn't overlap anything else, except for identical data
+    /// when `shared` is set, which some ROMs have for duplicate files.
+    fn place_at(&mut self, data: &[u8], offset: Option<u32>, shared: bool) -> Option<usize> {
+        let start = offset? as usize;
+        let range = start..start + data.len();
+        if data.is_empty() {
+            return Some(start);
+        }
+        if start < CartridgeHeader::LEN {
+            return None;
+        }
+        let overlaps = self
+            .placed
+            .iter()
+            .any(|placed| placed.start < range.end && range.start < placed.end);
+        if overlaps && !(shared && self.rom.get(range.clone()) == Some(data)) {
+            return None;
+        }
+        self.write(range, data);
+        Some(start)
+    }
+
+    /// Place `data` after everything else.
+    fn append(&mut self, data: &[u8]) -> usize {
+        if data.is_empty() {
+            return 0;
+        }
+        let start = self.rom.len().next_multiple_of(SECTION_ALIGN);
+        self.write(start..start + data.len(), data);
+        start
+    }
+
+    /// Write `data` at `offset` except where something was placed.
+    fn fill(&mut self, data: &[u8], offset: usize) {
+        let end = offset + data.len();
+        let mut placed = self.placed.clone();
+        placed.sort_by_key(|range| range.start);
+        let mut pos = offset;
+        for range in placed.into_iter().chain(std::iter::once(end..end)) {
+            if range.start > pos {
+                let free = pos..range.start.min(end);
+                self.rom[free.clone()]
+                    .copy_from_slice(&data[free.start - offset..free.end - offset]);
+            }
+            pos = pos.max(range.end);
+            if pos >= end {
+                break;
+            }
+        }
+    }
+
+    fn write(&mut self, range: Range<usize>, data: &[u8]) {
+        if self.rom.len() < range.end {
+            self.rom.resize(range.end, 0xFF);
+        }
+        self.rom[range.clone()].copy_from_slice(data);
+        self.placed.push(range);
+    }
+}
+
+impl RomBuilder {
+    /// Take `rom` apart, the secure area is kept as it is.
+    pub fn extract(rom: &[u8]) -> Result<Self> {
+        let cartridge = Cartridge::new(rom)?;
+        let header = cartridge.header();
+        let fs = cartridge.nitro_fs()?;
+        let range = |offs: u32, len: u32| offs as usize..offs as usize + len as usize;
+
+        let arm9 = Section::extract(rom, range(header.arm9_rom_offset(), header.arm9_size()))?;
+        let arm9_end = header.arm9_rom_offset() as usize + header.arm9_size() as usize;
+        let arm9_footer = match rom.get(arm9_end..arm9_end + ARM9_FOOTER_LEN) {
+            Some(footer) if read_u32(footer, 0) == ARM9_FOOTER_MAGIC => footer.to_vec(),
+            _ => Vec::new(),
+        };
+
+        let overlays = |table: &Section| {
+            table
+                .data
+                .chunks_exact(OVERLAY_ENTRY_LEN)
+                .map(|entry| {
+                    let id = read_u32(entry, OVERLAY_FILE_ID) as usize;
+                    let range = fs
+                        .fat()
+                        .get(id)
+                        .ok_or_else(|| error(format!("overlay file '{id}' isn't in the FAT")))?;
+                    Section::extract(rom, range.clone())
+                })
+                .collect::<Result<Vec<_>>>()
+        };
+        let arm9_overlay_table = Section::extract(
+            rom,
+            range(header.arm9_overlay_offset(), header.arm9_overlay_size()),
+        )?;
+        let arm7_overlay_table = Section::extract(
+            rom,
+            range(header.arm7_overlay_offset(), header.arm7_overlay_size()),
+        )?;
+
+        let banner = match header.banner_offset() as usize {
+            0 => Section {
+                data: Vec::new(),
+                offset: Some(0),
+            },
+            offs => {
+                let version = rom
+                    .get(offs..offs + 2)
+                    .map_or(1, |version| read_u16(version, 0));
+                Section::extract(rom, offs..offs + Banner::len_of(version))?
+            }
+        };
+
+        let fnt = &rom[range(header.fnt_offset(), header.fnt_size())];
+        let first_file_id = |id: u16| read_u16(fnt, (id - ROOT_DIR_ID) as usize * 8 + 4);
+        let entries = fs.entries().collect::<Vec<_>>();
+        let mut pos = 0;
+        let mut root = Self::extract_dir(rom, &entries, &mut pos, "", &first_file_id)?;
+        root.id = Some(ROOT_DIR_ID);
+        root.first_file_id = Some(first_file_id(ROOT_DIR_ID));
+
+        let rom_size = header.rom_size() as usize;
+        let arm9_range = arm9.offset.unwrap() as usize..arm9_end + arm9_footer.len();
+        let mut covered = fs.fat().to_vec();
+        covered.extend([
+            arm9_range,
+            range(header.arm7_rom_offset(), header.arm7_size()),
+            range(header.arm9_overlay_offset(), header.arm9_overlay_size()),
+            range(header.arm7_overlay_offset(), header.arm7_overlay_size()),
+            range(header.fnt_offset(), header.fnt_size()),
+            range(header.fat_offset(), header.fat_size()),
+            banner.offset.unwrap() as usize..banner.offset.unwrap() as usize + banner.data.len(),
+        ]);
+        let gaps = Self::extract_gaps(rom, rom_size.min(rom.len()), covered);
+
+        let tail = rom.get(rom_size..).unwrap_or(&[]);
+        let tail_len = tail.len() - tail.iter().rev().take_while(|&&byte| byte == 0xFF).count();
+
+        Ok(Self {
+            header: header.as_ref().try_into().unwrap(),
+            arm9_overlays: overlays(&arm9_overlay_table)?,
+            arm7_overlays: overlays(&arm7_overlay_table)?,
+            arm9,
+            arm9_footer,
+            arm9_overlay_table,
+            arm7: Section::extract(rom, range(header.arm7_rom_offset(), header.arm7_size()))?,
+            arm7_overlay_table,
+            fnt_offset: Some(header.fnt_offset()),
+            fat_offset: Some(header.fat_offset()),
+            banner,
+            root,
+            gaps,
+            tail: tail[..tail_len].to_vec(),
+            padded_len: rom.len(),
+        })
+    }
+
+    /// The bytes after the header and before `end` outside of `covered`, without the runs
+    /// building fills them with anyway: zeros in the reserved area and 0xFF after it.
+    fn extract_gaps(rom: &[u8], end: usize, mut covered: Vec<Range<usize>>) -> Vec<Section> {
+        covered.sort_by_key(|range| range.start);
+        let mut free = Vec::new();
+        let mut pos = CartridgeHeader::LEN;
+        for range in covered.into_iter().chain(std::iter::once(end..end)) {
+            if pos >= end {
+                break;
+            }
+            if range.start > pos {
+                let start = pos;
+                let end = range.start.min(end);
+                if start < RESERVED_END && end > RESERVED_END {
+                    free.extend([start..RESERVED_END, RESERVED_END..end]);
+                } else {
+                    free.push(start..end);
+                }
+            }
+            pos = pos.max(range.end);
+        }
+
+        free.into_iter()
+            .filter_map(|range| {
+                let fill = if range.start < RESERVED_END { 0 } else { 0xFF };
+                let data = &rom[range.clone()];
+                let start = data.iter().position(|&byte| byte != fill)?;
+                let end = data.iter().rposition(|&byte| byte != fill)? + 1;
+                Some(Section {
+                    data: data[start..end].to_vec(),
+                    offset: Some((range.start + start) as u32),
+                })
+            })
+            .collect()
+    }
+
+    /// The entries of the directory at `path`, which follow it in `entries` from `pos` on.
+    fn extract_dir(
+        rom: &[u8],
+        entries: &[&NitroEntry],
+        pos: &mut usize,
+        path: &str,
+        first_file_id: &impl Fn(u16) -> u16,
+    ) -> Result<RomDir> {
+        let mut dir = RomDir::default();
+        while let Some(entry) = entries.get(*pos) {
+            let parent = entry.path.rsplit_once('/').map_or("", |(parent, _)| parent);
+            if parent != path {
+                break;
+            }
+            *pos += 1;
+            dir.entries.push(match &entry.kind {
+                NitroEntryKind::Dir => {
+                    let mut sub = Self::extract_dir(rom, entries, pos, &entry.path, first_file_id)?;
+                    sub.name = entry.name().into();
+                    sub.id = Some(entry.id);
+                    sub.first_file_id = Some(first_file_id(entry.id));
+                    RomNode::Dir(sub)
+                }
+                NitroEntryKind::File(range) => RomNode::File(RomFile {
+                    name: entry.name().into(),
+                    data: Section::extract(rom, range.clone())?,
+                }),
+            });
+        }
+        Ok(dir)
+    }
+
+    pub fn file(&self, path: &str) -> Option<&RomFile> {
+        let mut dir = &self.root;
+        let mut names = path.trim_matches('/').split('/').peekable();
+        while let Some(name) = names.next() {
+            let node = dir.entries.iter().find(|node| match node {
+                RomNode::Dir(dir) => dir.name == name,
+                RomNode::File(file) => file.name == name,
+            })?;
+            match node {
+                RomNode::Dir(sub) if names.peek().is_some() => dir = sub,
+                RomNode::File(file) if names.peek().is_none() => return Some(file),
+                _ => return None,
+            }
+        }
+        None
+    }
+
+    /// Replace the contents of the file at `path`, or add it and the directories leading to it.
+    pub fn insert_file(&mut self, path: &str, data: Vec<u8>) -> Result<()> {
+        let path = path.trim_matches('/');
+        let (dirs, name) = path.rsplit_once('/').unwrap_or(("", path));
+        let mut dir = &mut self.root;
+        for dir_name in dirs.split('/').filter(|name| !name.is_empty()) {
+            if dir.node_mut(dir_name).is_none() {
+                dir.entries.push(RomNode::Dir(RomDir::new(dir_name)));
+            }
+            dir = match dir.node_mut(dir_name) {
+                Some(RomNode::Dir(sub)) => sub,
+                _ => return Err(error(format!("'{dir_name}' in '{path}' is a file"))),
+            };
+        }
+        match dir.node_mut(name) {
+            Some(RomNode::File(file)) => file.data.set_data(data),
+            Some(RomNode::Dir(_)) => return Err(error(format!("'{path}' is a directory"))),
+            None => dir.entries.push(RomNode::File(RomFile {
+                name: name.into(),
+                data: Section::new(data),
+            })),
+        }
+        Ok(())
+    }
+
+    fn overlay_ids(table: &Section, overlays: &[Section]) -> Result<Vec<u16>> {
+        if table.data.len() / OVERLAY_ENTRY_LEN != overlays.len() {
+            return Err(error(format!(
+                "the overlay table has '{}' entries but there are '{}' overlays",
+                table.data.len() / OVERLAY_ENTRY_LEN,
+                overlays.len()
+            )));
+        }
+        Ok(table
+            .data
+            .chunks_exact(OVERLAY_ENTRY_LEN)
+            .map(|entry| read_u32(entry, OVERLAY_FILE_ID) as u16)
+            .collect())
+    }
+
+    /// Pack the parts into a ROM. `key1_table` is needed for the checksum of a decrypted secure
+    /// area, which is left as it is in the header without one.
+    pub fn build(&self, key1_table: Option<&[u8]>) -> Result<Vec<u8>> {
+        let arm9_overlay_ids = Self::overlay_ids(&self.arm9_overlay_table, &self.arm9_overlays)?;
+        let arm7_overlay_ids = Self::overlay_ids(&self.arm7_overlay_table, &self.arm7_overlays)?;
+        let overlay_ids = [&arm9_overlay_ids[..], &arm7_overlay_ids[..]].concat();
+        let numbering = match Numbering::preserved(&self.root, &overlay_ids) {
+            Some(numbering) => numbering,
+            None => Numbering::renumbered(&self.root, &overlay_ids)?,
+        };
+        let fnt = numbering.fnt()?;
+        let file_count = overlay_ids
+            .iter()
+            .chain(numbering.files.iter().map(|(id, _)| id))
+            .max()
+            .map_or(0, |&id| id as usize + 1);
+        let fat_placeholder = vec![0; file_count * 8];
+
+        let mut arm9 = self.arm9.data.clone();
+        arm9.extend_from_slice(&self.arm9_footer);
+        // the data, original offset and whether it may share its bytes with identical data.
+        let mut sections: Vec<(&[u8], Option<u32>, bool)> = Vec::new();
+        let mut file_ids = Vec::new();
+        let mut push = |sections: &mut Vec<_>, data, offset, shared| {
+            sections.push((data, offset, shared));
+            sections.len() - 1
+        };
+        let arm9_index = push(&mut sections, &arm9, self.arm9.offset, true);
+        let arm9_table = &self.arm9_overlay_table;
+        let arm9_table_index = push(&mut sections, &arm9_table.data, arm9_table.offset, true);
+        for (overlay, &id) in self.arm9_overlays.iter().zip(&arm9_overlay_ids) {
+            file_ids.push((id, push(&mut sections, &overlay.data, overlay.offset, true)));
+        }
+        let arm7_index = push(&mut sections, &self.arm7.data, self.arm7.offset, true);
+        let arm7_table = &self.arm7_overlay_table;
+        let arm7_table_index = push(&mut sections, &arm7_table.data, arm7_table.offset, true);
+        for (overlay, &id) in self.arm7_overlays.iter().zip(&arm7_overlay_ids) {
+            file_ids.push((id, push(&mut sections, &overlay.data, overlay.offset, true)));
+        }
+        // the FAT is filled in once everything is placed.
+        let fnt_index = push(&mut sections, &fnt, self.fnt_offset, false);
+        let fat_index = push(&mut sections, &fat_placeholder, self.fat_offset, false);
+        let banner_index = push(&mut sections, &self.banner.data, self.banner.offset, true);
+        let mut files = numbering.files.clone();
+        files.sort_by_key(|&(id, _)| id);
+        for (id, file) in files {
+            file_ids.push((
+                id,
+                push(&mut sections, &file.data.data, file.data.offset, true),
+            ));
+        }
+
+        let mut layout = Layout {
+            rom: self.header.to_vec(),
+            placed: Vec::new(),
+        };
+        layout.rom.resize(RESERVED_END, 0);
+        // keep what fits at its offset first, then put the rest at the end.
+        let mut offsets = sections
+            .iter()
+            .map(|&(data, offset, shared)| layout.place_at(data, offset, shared))
+            .collect::<Vec<_>>();
+        // the gaps are within the ROM size, so nothing is appended over them.
+        let gaps_end = self
+            .gaps
+            .iter()
+            .filter_map(|gap| Some(gap.offset? as usize + gap.data.len()))
+            .max()
+            .unwrap_or(0);
+        if layout.rom.len() < gaps_end {
+            layout.rom.resize(gaps_end, 0xFF);
+        }
+        for (offset, &(data, ..)) in offsets.iter_mut().zip(&sections) {
+            if offset.is_none() {
+                *offset = Some(layout.append(data));
+            }
+        }
+        let offsets = offsets.into_iter().map(Option::unwrap).collect::<Vec<_>>();
+        for gap in &self.gaps {
+            if let Some(offset) = gap.offset {
+                layout.fill(&gap.data, offset as usize);
+            }
+        }
+        let layout_end = layout.rom.len();
+        let mut rom = layout.rom;
+
+        let fat_offset = offsets[fat_index];
+        for (id, section) in file_ids {
+            let start = offsets[section];
+            let end = start + sections[section].0.len();
+            let entry = fat_offset + id as usize * 8;
+            write_u32(&mut rom, entry, start as u32);
+            write_u32(&mut rom, entry + 4, end as u32);
+        }
+
+        let header_rom_size = read_u32(&self.header, 0x80) as usize;
+        let rom_size = layout_end.max(header_rom_size);
+        let fields = [
+            (0x20, offsets[arm9_index]),
+            (0x2C, self.arm9.data.len()),
+            (0x30, offsets[arm7_index]),
+            (0x3C, self.arm7.data.len()),
+            (0x40, offsets[fnt_index]),
+            (0x44, fnt.len()),
+            (0x48, fat_offset),
+            (0x4C, fat_placeholder.len()),
+            (0x50, offsets[arm9_table_index]),
+            (0x54, self.arm9_overlay_table.data.len()),
+            (0x58, offsets[arm7_table_index]),
+            (0x5C, self.arm7_overlay_table.data.len()),
+            (0x68, offsets[banner_index]),
+            (0x80, rom_size),
+        ];
+        for (offs, val) in fields {
+            let val = u32::try_from(val).map_err(|_| error("the ROM is larger than 4GiB"))?;
+            write_u32(&mut rom, offs, val);
+        }
+
+        if !self.tail.is_empty() {
+            rom.resize(rom.len().max(rom_size), 0xFF);
+            rom.extend_from_slice(&self.tail);
+        }
+        rom.resize(rom.len().max(self.padded_len), 0xFF);
+
+        if let Some(crc) = Self::secure_area_crc(&rom, key1_table) {
+            write_u16(&mut rom, 0x6C, crc);
+        }
+        let header_crc = crc16(0xFFFF, &rom[..0x15E]);
+        write_u16(&mut rom, 0x15E, header_crc);
+        Ok(rom)
+    }
+
+    /// CRC16 of the secure area as the card sends it, which is encrypted.
+    fn secure_area_crc(rom: &[u8], key1_table: Option<&[u8]>) -> Option<u16> {
+        let start = read_u32(rom, 0x20) as usize;
+        match SecureArea::of(rom) {
+            SecureArea::None => None,
+            SecureArea::Encrypted => Some(crc16(0xFFFF, &rom[start..super::SECURE_AREA_END])),
+            SecureArea::Decrypted => {
+                let mut encrypted = rom[..super::SECURE_AREA_END].to_vec();
+                super::encrypt_secure_area(&mut encrypted, key1_table?).ok()?;
+                Some(crc16(0xFFFF, &encrypted[start..]))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A ROM with two files, one of them in a directory, bytes in the reserved area, a gap
+    /// between the files and the ROM size and a tail after it.
+    fn rom() -> Vec<u8> {
+        let mut rom = vec![0xFF; 0x5000];
+        rom[..RESERVED_END].fill(0);
+        rom[0x0C..0x10].copy_from_slice(b"ABCE");
+        rom[0x1000..0x1008].copy_from_slice(b"reserved");
+
+        let mut fnt = Vec::new();
+        // the main table: sub-table offset, first file ID and parent, the directory count for
+        // the root.
+        for (offs, first_file_id, parent) in [(16u32, 0u16, 2u16), (27, 1, ROOT_DIR_ID)] {
+            fnt.extend_from_slice(&offs.to_le_bytes());
+            fnt.extend_from_slice(&first_file_id.to_le_bytes());
+            fnt.extend_from_slice(&parent.to_le_bytes());
+        }
+        fnt.extend_from_slice(b"\x05a.bin\x81d\x01\xF0\x00");
+        fnt.extend_from_slice(b"\x05b.bin\x00");
+        let files = [
+            (0x4800, b"first file data!".as_slice()),
+            (0x4A00, b"second!!"),
+        ];
+        let fields = [
+            (0x20, 0x4000),
+            (0x2C, 0x100),
+            (0x30, 0x4200),
+            (0x3C, 0x100),
+            (0x40, 0x4400),
+            (0x44, fnt.len()),
+            (0x48, 0x4600),
+            (0x4C, files.len() * 8),
+            (0x80, 0x4C10),
+        ];
+        for (offs, val) in fields {
+            write_u32(&mut rom, offs, val as u32);
+        }
+
+        rom[0x4000..0x4100].fill(0x99);
+        rom[0x4200..0x4300].fill(0x77);
+        rom[0x4400..0x4400 + fnt.len()].copy_from_slice(&fnt);
+        for (id, (offs, data)) in files.into_iter().enumerate() {
+            write_u32(&mut rom, 0x4600 + id * 8, offs as u32);
+            write_u32(&mut rom, 0x4600 + id * 8 + 4, (offs + data.len()) as u32);
+            rom[offs..offs + data.len()].copy_from_slice(data);
+        }
+        rom[0x4C00..0x4C04].copy_from_slice(b"gap!");
+        rom[0x4E00..0x4E04].copy_from_slice(b"tail");
+
+        let crc = crc16(0xFFFF, &rom[..0x15E]);
+        write_u16(&mut rom, 0x15E, crc);
+        rom
+    }
+
+    #[test]
+    fn extracted_rom_is_rebuilt_byte_for_byte() {
+        let rom = rom();
+        let builder = RomBuilder::extract(&rom).unwrap();
+        assert_eq!(builder.file("d/b.bin").unwrap().data.data, b"second!!");
+        assert_eq!(builder.build(None).unwrap(), rom);
+    }
+
+    #[test]
+    fn grown_file_is_placed_after_the_gaps() {
+        let rom = rom();
+        let mut builder = RomBuilder::extract(&rom).unwrap();
+        builder.insert_file("a.bin", vec![0x55; 0x300]).unwrap();
+        let built = builder.build(None).unwrap();
+
+        assert_eq!(&built[0x1000..0x1008], b"reserved");
+        assert_eq!(&built[0x4C00..0x4C04], b"gap!");
+        let fs = Cartridge::new(&built).unwrap().nitro_fs().unwrap();
+        assert_eq!(fs.read_file("a.bin").unwrap(), [0x55; 0x300]);
+        assert_eq!(fs.read_file("d/b.bin").unwrap(), b"second!!");
+    }
+}
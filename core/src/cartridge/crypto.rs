@@ -0,0 +1,385 @@
+//! KEY1 (Blowfish keyed by the game code) used for the secure area and KEY1 mode commands, and
+//! the KEY2 stream cipher of the main data mode.
+
+use super::Cartridge;
+use crate::error::{Error, Result};
+
+/// Length of the KEY1 table, at 0x30 in the ARM7 BIOS.
+pub const KEY1_TABLE_LEN: usize = 0x1048;
+const KEY1_TABLE_BIOS_OFFSET: usize = 0x30;
+
+/// The secure area spans 0x4000..0x8000, only its first 2KiB are KEY1 encrypted.
+pub const SECURE_AREA_START: usize = 0x4000;
+pub const SECURE_AREA_END: usize = 0x8000;
+const SECURE_AREA_ENCRYPTED_LEN: usize = 0x800;
+
+/// Marker at the start of a decrypted secure area.
+const ENCRY_OBJ: [u8; 8] = *b"encryObj";
+/// The BIOS replaces the marker with two undefined instructions (0xE7FFDEFF) once it checked it.
+const DESTROYED: [u8; 8] = [0xFF, 0xDE, 0xFF, 0xE7, 0xFF, 0xDE, 0xFF, 0xE7];
+
+/// The KEY1 table of `data`, which is either an ARM7 BIOS or the table on its own.
+pub fn key1_table(data: &[u8]) -> Result<&[u8]> {
+    match data.len() {
+        KEY1_TABLE_LEN => Ok(data),
+        len if len >= KEY1_TABLE_BIOS_OFFSET + KEY1_TABLE_LEN => {
+            Ok(&data[KEY1_TABLE_BIOS_OFFSET..KEY1_TABLE_BIOS_OFFSET + KEY1_TABLE_LEN])
+        }
+        len => Err(Error::Cartridge(format!(
+            "expected an ARM7 BIOS or a KEY1 table of '{KEY1_TABLE_LEN}' bytes but got '{len}'"
+        ))),
+    }
+}
+
+/// Blowfish with the key schedule of the KEY1 table applied to a game code.
+#[derive(Clone)]
+pub struct Key1 {
+    /// The P-array (0x00..0x12) followed by the 4 S-boxes.
+    keybuf: Box<[u32; KEY1_TABLE_LEN / 4]>,
+}
+
+impl Key1 {
+    /// Initialize from `table` with the keycode of `game_code`. The commands use level 2, the
+    /// secure area level 3, both with a modulo of 8.
+    pub fn new(table: &[u8], game_code: u32, level: u32, modulo: usize) -> Self {
+        debug_assert_eq!(table.len(), KEY1_TABLE_LEN);
+        let mut keybuf = Box::new([0; KEY1_TABLE_LEN / 4]);
+        for (word, bytes) in keybuf.iter_mut().zip(table.chunks_exact(4)) {
+            *word = u32::from_le_bytes(bytes.try_into().unwrap());
+        }
+        let mut key1 = Self { keybuf };
+
+        let mut keycode = [game_code, game_code / 2, game_code.wrapping_mul(2)];
+        if level >= 1 {
+            key1.apply_keycode(&mut keycode, modulo);
+        }
+        if level >= 2 {
+            key1.apply_keycode(&mut keycode, modulo);
+        }
+        keycode[1] = keycode[1].wrapping_mul(2);
+        keycode[2] /= 2;
+        if level >= 3 {
+            key1.apply_keycode(&mut keycode, modulo);
+        }
+        key1
+    }
+
+    fn apply_keycode(&mut self, keycode: &mut [u32; 3], modulo: usize) {
+        [keycode[1], keycode[2]] = self.encrypt([keycode[1], keycode[2]]);
+        [keycode[0], keycode[1]] = self.encrypt([keycode[0], keycode[1]]);
+        for i in 0..0x12 {
+            self.keybuf[i] ^= keycode[(i * 4 % modulo) / 4].swap_bytes();
+        }
+        let mut scratch = [0; 2];
+        for i in (0..KEY1_TABLE_LEN / 4).step_by(2) {
+            scratch = self.encrypt(scratch);
+            self.keybuf[i] = scratch[1];
+            self.keybuf[i + 1] = scratch[0];
+        }
+    }
+
+    #[inline]
+    fn round(&self, z: u32) -> u32 {
+        let sbox =
+            |index: usize, byte: u32| self.keybuf[0x12 + index * 0x100 + (byte & 0xFF) as usize];
+        (sbox(0, z >> 24).wrapping_add(sbox(1, z >> 16)) ^ sbox(2, z >> 8)).wrapping_add(sbox(3, z))
+    }
+
+    pub fn encrypt(&self, [mut y, mut x]: [u32; 2]) -> [u32; 2] {
+        for i in 0..0x10 {
+            let z = self.keybuf[i] ^ x;
+            x = y ^ self.round(z);
+            y = z;
+        }
+        [x ^ self.keybuf[0x10], y ^ self.keybuf[0x11]]
+    }
+
+    pub fn decrypt(&self, [mut y, mut x]: [u32; 2]) -> [u32; 2] {
+        for i in (0x02..0x12).rev() {
+            let z = self.keybuf[i] ^ x;
+            x = y ^ self.round(z);
+            y = z;
+        }
+        [x ^ self.keybuf[0x01], y ^ self.keybuf[0x00]]
+    }
+
+    /// Apply `f` to every 8 bytes of `data` as two little endian words.
+    fn apply(data: &mut [u8], mut f: impl FnMut([u32; 2]) -> [u32; 2]) {
+        for block in data.chunks_exact_mut(8) {
+            let lo = u32::from_le_bytes(block[..4].try_into().unwrap());
+            let hi = u32::from_le_bytes(block[4..].try_into().unwrap());
+            let [lo, hi] = f([lo, hi]);
+            block[..4].copy_from_slice(&lo.to_le_bytes());
+            block[4..].copy_from_slice(&hi.to_le_bytes());
+        }
+    }
+
+    pub fn encrypt_bytes(&self, data: &mut [u8]) {
+        Self::apply(data, |block| self.encrypt(block));
+    }
+
+    pub fn decrypt_bytes(&self, data: &mut [u8]) {
+        Self::apply(data, |block| self.decrypt(block));
+    }
+
+    /// Commands are sent most significant byte first.
+    pub fn encrypt_command(&self, cmd: [u8; 8]) -> [u8; 8] {
+        let mut bytes = cmd;
+        bytes.reverse();
+        self.encrypt_bytes(&mut bytes);
+        bytes.reverse();
+        bytes
+    }
+
+    pub fn decrypt_command(&self, cmd: [u8; 8]) -> [u8; 8] {
+        let mut bytes = cmd;
+        bytes.reverse();
+        self.decrypt_bytes(&mut bytes);
+        bytes.reverse();
+        bytes
+    }
+}
+
+/// The KEY2 stream cipher, two 39 bit registers seeded through ROMCTRL.
+#[derive(Debug, Clone, Copy)]
+pub struct Key2 {
+    x: u64,
+    y: u64,
+}
+
+impl Key2 {
+    const MASK: u64 = (1 << 39) - 1;
+    /// Low byte of seed 0 selected by byte 0x13 of the header.
+    const SEED0_BYTES: [u8; 8] = [0xE8, 0x4D, 0x5A, 0xB1, 0x17, 0x8F, 0x99, 0xD5];
+    const SEED1: u64 = 0x5C_879B_9B05;
+
+    pub fn new(seed0: u64, seed1: u64) -> Self {
+        let reverse = |seed: u64| (seed & Self::MASK).reverse_bits() >> (64 - 39);
+        Self {
+            x: reverse(seed0),
+            y: reverse(seed1),
+        }
+    }
+
+    /// The seeds the firmware sets for a card with `seed_select` at 0x13 of its header.
+    pub fn default_seeds(seed_select: u8) -> [u64; 2] {
+        let seed0 = 0x58_C56D_E000 | Self::SEED0_BYTES[seed_select as usize & 0b111] as u64;
+        [seed0, Self::SEED1]
+    }
+
+    /// Encrypt or decrypt the next byte of the stream.
+    pub fn apply(&mut self, byte: u8) -> u8 {
+        let (x, y) = (self.x, self.y);
+        self.x = ((((x >> 5) ^ (x >> 17) ^ (x >> 18) ^ (x >> 31)) & 0xFF) + (x << 8)) & Self::MASK;
+        self.y = ((((y >> 5) ^ (y >> 23) ^ (y >> 18) ^ (y >> 31)) & 0xFF) + (y << 8)) & Self::MASK;
+        byte ^ self.x as u8 ^ self.y as u8
+    }
+}
+
+/// State of the secure area of a ROM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SecureArea {
+    /// The ARM9 binary doesn't start in the secure area, like in homebrew.
+    None,
+    Encrypted,
+    Decrypted,
+}
+
+impl SecureArea {
+    pub fn of(rom: &[u8]) -> Self {
+        let Ok(cartridge) = Cartridge::new(rom) else {
+            return Self::None;
+        };
+        let arm9_rom = cartridge.header().arm9_rom_offset() as usize;
+        if rom.len() < SECURE_AREA_END || !(SECURE_AREA_START..SECURE_AREA_END).contains(&arm9_rom)
+        {
+            return Self::None;
+        }
+        let marker = &rom[SECURE_AREA_START..SECURE_AREA_START + 8];
+        if marker == ENCRY_OBJ || marker == DESTROYED {
+            Self::Decrypted
+        } else {
+            Self::Encrypted
+        }
+    }
+}
+
+fn game_code(rom: &[u8]) -> Result<u32> {
+    Ok(Cartridge::new(rom)?.header().game_code())
+}
+
+/// Decrypt the secure area of `rom` in place with the KEY1 `table`, it keeps the "encryObj"
+/// marker. Fails without touching `rom` if it isn't encrypted or the marker doesn't match after
+/// decryption.
+pub fn decrypt_secure_area(rom: &mut [u8], table: &[u8]) -> Result<()> {
+    if SecureArea::of(rom) != SecureArea::Encrypted {
+        return Err(Error::Cartridge("the secure area isn't encrypted".into()));
+    }
+    let game_code = game_code(rom)?;
+    let mut area = [0; SECURE_AREA_ENCRYPTED_LEN];
+    area.copy_from_slice(&rom[SECURE_AREA_START..SECURE_AREA_START + SECURE_AREA_ENCRYPTED_LEN]);
+
+    // the first 8 bytes are encrypted twice.
+    Key1::new(table, game_code, 2, 8).decrypt_bytes(&mut area[..8]);
+    Key1::new(table, game_code, 3, 8).decrypt_bytes(&mut area);
+    if area[..8] != ENCRY_OBJ {
+        return Err(Error::Cartridge(
+            "the decrypted secure area doesn't start with \"encryObj\"".into(),
+        ));
+    }
+    rom[SECURE_AREA_START..SECURE_AREA_START + SECURE_AREA_ENCRYPTED_LEN].copy_from_slice(&area);
+    Ok(())
+}
+
+/// Encrypt the decrypted secure area of `rom` in place with the KEY1 `table`, restoring the
+/// "encryObj" marker first.
+pub fn encrypt_secure_area(rom: &mut [u8], table: &[u8]) -> Result<()> {
+    if SecureArea::of(rom) != SecureArea::Decrypted {
+        return Err(Error::Cartridge("the secure area isn't decrypted".into()));
+    }
+    let game_code = game_code(rom)?;
+    let area = &mut rom[SECURE_AREA_START..SECURE_AREA_START + SECURE_AREA_ENCRYPTED_LEN];
+    area[..8].copy_from_slice(&ENCRY_OBJ);
+    Key1::new(table, game_code, 3, 8).encrypt_bytes(area);
+    Key1::new(table, game_code, 2, 8).encrypt_bytes(&mut area[..8]);
+    Ok(())
+}
+
+/// Replace the "encryObj" marker at the start of `arm9`, the ARM9 binary of `rom` as loaded
+/// into memory, like the BIOS does after checking it. The card keeps the marker.
+pub fn destroy_secure_area_marker(rom: &[u8], arm9: &mut [u8]) {
+    if SecureArea::of(rom) != SecureArea::Decrypted || arm9.len() < 8 {
+        return;
+    }
+    // the secure area is only loaded from its start along with the marker.
+    let arm9_rom = Cartridge::new(rom).map(|cartridge| cartridge.header().arm9_rom_offset());
+    if arm9_rom.is_ok_and(|offs| offs as usize == SECURE_AREA_START) {
+        arm9[..8].copy_from_slice(&DESTROYED);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GAME_CODE: u32 = u32::from_le_bytes(*b"ABCE");
+
+    /// A KEY1 table with a P-array of single bits and S-boxes of arbitrary words.
+    fn table() -> Vec<u8> {
+        (0..KEY1_TABLE_LEN / 4)
+            .flat_map(|i| {
+                let word = match i {
+                    0..=0x11 => 1 << i,
+                    _ => (i as u32)
+                        .wrapping_mul(0x9E37_79B9)
+                        .rotate_left(i as u32 % 32),
+                };
+                word.to_le_bytes()
+            })
+            .collect()
+    }
+
+    fn rom() -> Vec<u8> {
+        let mut rom = vec![0; SECURE_AREA_END];
+        rom[0x0C..0x10].copy_from_slice(&GAME_CODE.to_le_bytes());
+        rom[0x20..0x24].copy_from_slice(&(SECURE_AREA_START as u32).to_le_bytes());
+        for (i, byte) in rom[SECURE_AREA_START..].iter_mut().enumerate() {
+            *byte = (i * 7) as u8;
+        }
+        rom[SECURE_AREA_START..SECURE_AREA_START + 8].copy_from_slice(&ENCRY_OBJ);
+        rom
+    }
+
+    #[test]
+    fn key1_without_keycode_is_blowfish_with_the_table() {
+        // with zeroed S-boxes every round only xors a P entry into a half and swaps them, which
+        // leaves the even entries xored into one half and the odd ones into the other.
+        let mut table = table();
+        table[0x12 * 4..].fill(0);
+        let key1 = Key1::new(&table, GAME_CODE, 0, 8);
+        let block = [0x1234_5678, 0x9ABC_DEF0];
+        let encrypted = key1.encrypt(block);
+        assert_eq!(encrypted, [0x9ABC_DEF0 ^ 0x1_5555, 0x1234_5678 ^ 0x2_AAAA]);
+        assert_eq!(key1.decrypt(encrypted), block);
+    }
+
+    #[test]
+    fn key1_decrypt_inverts_encrypt() {
+        let table = table();
+        for level in 1..=3 {
+            let key1 = Key1::new(&table, GAME_CODE, level, 8);
+            let block = [0xDEAD_BEEF, 0x0123_4567];
+            assert_ne!(key1.encrypt(block), block);
+            assert_eq!(key1.decrypt(key1.encrypt(block)), block);
+
+            let cmd = *b"\x3c\x00\x01\x02\x03\x04\x05\x06";
+            assert_eq!(key1.decrypt_command(key1.encrypt_command(cmd)), cmd);
+        }
+    }
+
+    #[test]
+    fn key2_default_seeds() {
+        assert_eq!(Key2::default_seeds(0), [0x58_C56D_E0E8, 0x5C_879B_9B05]);
+        assert_eq!(Key2::default_seeds(7), [0x58_C56D_E0D5, 0x5C_879B_9B05]);
+        // only the low 3 bits select the seed.
+        assert_eq!(Key2::default_seeds(8), Key2::default_seeds(0));
+    }
+
+    #[test]
+    fn key2_stream() {
+        // the seeds are bit reversed, so bit 38 seeds bit 0. Both registers shift in the same
+        // bytes until the bit reaches the taps at 17 of the first one and 23 of the second.
+        let stream = |seeds: [u64; 2]| {
+            let mut key2 = Key2::new(seeds[0], seeds[1]);
+            [0; 4].map(|byte| key2.apply(byte))
+        };
+        assert_eq!(stream([0, 0]), [0; 4]);
+        assert_eq!(stream([1 << 38, 0]), [0x00, 0x08, 0x00, 0x80]);
+        assert_eq!(stream([0, 1 << 38]), [0x00, 0x08, 0x00, 0x02]);
+
+        let [seed0, seed1] = Key2::default_seeds(3);
+        let data = *b"KEY2 stream";
+        let mut encrypted = data;
+        let mut key2 = Key2::new(seed0, seed1);
+        encrypted
+            .iter_mut()
+            .for_each(|byte| *byte = key2.apply(*byte));
+        assert_ne!(encrypted, data);
+        let mut key2 = Key2::new(seed0, seed1);
+        encrypted
+            .iter_mut()
+            .for_each(|byte| *byte = key2.apply(*byte));
+        assert_eq!(encrypted, data);
+    }
+
+    #[test]
+    fn secure_area_round_trip() {
+        let table = table();
+        let decrypted = rom();
+        assert_eq!(SecureArea::of(&decrypted), SecureArea::Decrypted);
+
+        let mut rom = decrypted.clone();
+        encrypt_secure_area(&mut rom, &table).unwrap();
+        assert_eq!(SecureArea::of(&rom), SecureArea::Encrypted);
+        assert_ne!(rom, decrypted);
+        // only the first 2KiB are encrypted.
+        assert_eq!(
+            rom[SECURE_AREA_START + SECURE_AREA_ENCRYPTED_LEN..],
+            decrypted[SECURE_AREA_START + SECURE_AREA_ENCRYPTED_LEN..]
+        );
+
+        decrypt_secure_area(&mut rom, &table).unwrap();
+        assert_eq!(rom, decrypted);
+        assert!(decrypt_secure_area(&mut rom, &table).is_err());
+    }
+
+    #[test]
+    fn marker_is_only_destroyed_in_the_loaded_binary() {
+        let rom = rom();
+        let mut arm9 = rom[SECURE_AREA_START..].to_vec();
+        destroy_secure_area_marker(&rom, &mut arm9);
+        assert_eq!(arm9[..8], DESTROYED);
+        assert_eq!(arm9[8..], rom[SECURE_AREA_START + 8..]);
+        assert_eq!(SecureArea::of(&rom), SecureArea::Decrypted);
+    }
+}
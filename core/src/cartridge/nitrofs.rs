@@ -0,0 +1,368 @@
+//! NitroFS, the file system of the cartridge: the FNT with the names of the files and
+//! directories, and the FAT with the location of every file in the ROM.
+
+use std::ops::Range;
+
+use super::Cartridge;
+use crate::error::{Error, Result};
+
+/// IDs of directories start here, the root directory has the first one. Files use the IDs below.
+pub const ROOT_DIR_ID: u16 = 0xF000;
+
+/// Length of an entry of the main FNT table and of the FAT.
+const TABLE_ENTRY_LEN: usize = 8;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NitroEntryKind {
+    Dir,
+    /// Bytes of the file in the ROM.
+    File(Range<usize>),
+}
+
+/// A directory or file of the file system.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NitroEntry {
+    /// Names separated by '/' without a leading one, e.g. "data/sound/bgm.sdat".
+    pub path: String,
+    pub id: u16,
+    pub kind: NitroEntryKind,
+}
+
+impl NitroEntry {
+    pub fn name(&self) -> &str {
+        self.path.rsplit('/').next().unwrap_or(&self.path)
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.kind == NitroEntryKind::Dir
+    }
+
+    /// Bytes of the file in the ROM, none for directories.
+    pub fn range(&self) -> Option<Range<usize>> {
+        match &self.kind {
+            NitroEntryKind::Dir => None,
+            NitroEntryKind::File(range) => Some(range.clone()),
+        }
+    }
+}
+
+/// The parsed FNT and FAT of a ROM.
+pub struct NitroFs<'a> {
+    rom: &'a [u8],
+    fat: Vec<Range<usize>>,
+    /// Every directory followed by its contents, in the order of the FNT.
+    entries: Vec<NitroEntry>,
+}
+
+fn error(msg: impl std::fmt::Display) -> Error {
+    Error::Cartridge(format!("NitroFS: {msg}"))
+}
+
+fn read_u16(data: &[u8], offs: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        data.get(offs..offs + 2)?.try_into().unwrap(),
+    ))
+}
+
+fn read_u32(data: &[u8], offs: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        data.get(offs..offs + 4)?.try_into().unwrap(),
+    ))
+}
+
+impl<'a> NitroFs<'a> {
+    pub fn new(cartridge: &Cartridge<'a>) -> Result<Self> {
+        let rom = cartridge.data();
+        let header = cartridge.header();
+        let table = |offs: u32, size: u32, name: &str| {
+            let (offs, size) = (offs as usize, size as usize);
+            rom.get(offs..offs + size)
+                .ok_or_else(|| error(format!("the {name} at '{offs:x}' is outside of the ROM")))
+        };
+        let fnt = table(header.fnt_offset(), header.fnt_size(), "FNT")?;
+        let fat = table(header.fat_offset(), header.fat_size(), "FAT")?;
+
+        let fat = fat
+            .chunks_exact(TABLE_ENTRY_LEN)
+            .enumerate()
+            .map(|(id, entry)| {
+                let start = read_u32(entry, 0).unwrap() as usize;
+                let end = read_u32(entry, 4).unwrap() as usize;
+                if start > end || end > rom.len() {
+                    Err(error(format!(
+                        "file '{id}' spans '{start:x}..{end:x}' which is outside of the ROM"
+                    )))
+                } else {
+                    Ok(start..end)
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        // the parent ID of the root directory holds the number of directories.
+        let dir_count = read_u16(fnt, 6).ok_or_else(|| error("the FNT is empty"))? as usize;
+        if dir_count == 0
+            || dir_count > (u16::MAX - ROOT_DIR_ID) as usize + 1
+            || dir_count * TABLE_ENTRY_LEN > fnt.len()
+        {
+            return Err(error(format!(
+                "invalid number of directories '{dir_count}'"
+            )));
+        }
+
+        let mut fs = Self {
+            rom,
+            fat,
+            entries: Vec::new(),
+        };
+        let mut visited = vec![false; dir_count];
+        fs.read_dir(fnt, ROOT_DIR_ID, "", &mut visited)?;
+        Ok(fs)
+    }
+
+    fn read_dir(&mut self, fnt: &[u8], id: u16, path: &str, visited: &mut [bool]) -> Result<()> {
+        let index = id.wrapping_sub(ROOT_DIR_ID) as usize;
+        match visited.get_mut(index) {
+            // a directory that was visited before would make a cycle.
+            Some(visited) if !*visited => *visited = true,
+            _ => return Err(error(format!("invalid directory ID '{id:x}'"))),
+        }
+        let truncated = || error(format!("the entries of directory '{id:x}' are truncated"));
+
+        let main = index * TABLE_ENTRY_LEN;
+        let mut offs = read_u32(fnt, main).unwrap() as usize;
+        let mut file_id = read_u16(fnt, main + 4).unwrap();
+        loop {
+            // the length of the name, with bit 7 set for directories. 0 ends the table.
+            let kind = *fnt.get(offs).ok_or_else(truncated)?;
+            offs += 1;
+            if kind == 0 {
+                return Ok(());
+            }
+            let len = (kind & 0x7F) as usize;
+            let name = fnt.get(offs..offs + len).ok_or_else(truncated)?;
+            offs += len;
+            let name = String::from_utf8_lossy(name);
+            let entry_path = if path.is_empty() {
+                name.into_owned()
+            } else {
+                format!("{path}/{name}")
+            };
+
+            if kind & 0x80 != 0 {
+                let dir_id = read_u16(fnt, offs).ok_or_else(truncated)?;
+                offs += 2;
+                self.entries.push(NitroEntry {
+                    path: entry_path.clone(),
+                    id: dir_id,
+                    kind: NitroEntryKind::Dir,
+                });
+                self.read_dir(fnt, dir_id, &entry_path, visited)?;
+            } else {
+                let range = self.fat.get(file_id as usize).cloned().ok_or_else(|| {
+                    error(format!("file '{entry_path}' has no FAT entry '{file_id}'"))
+                })?;
+                self.entries.push(NitroEntry {
+                    path: entry_path,
+                    id: file_id,
+                    kind: NitroEntryKind::File(range),
+                });
+                file_id = file_id.wrapping_add(1);
+            }
+        }
+    }
+
+    /// Every directory and file, a directory is followed by its contents.
+    pub fn entries(&self) -> impl Iterator<Item = &NitroEntry> + '_ {
+        self.entries.iter()
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = &NitroEntry> + '_ {
+        self.entries.iter().filter(|entry| !entry.is_dir())
+    }
+
+    /// The directory or file at `path`, a leading '/' is optional.
+    pub fn entry(&self, path: &str) -> Option<&NitroEntry> {
+        let path = path.trim_matches('/');
+        self.entries.iter().find(|entry| entry.path == path)
+    }
+
+    /// Bytes of every file by its ID, which includes the overlays that have no name.
+    pub fn fat(&self) -> &[Range<usize>] {
+        &self.fat
+    }
+
+    pub fn read_file(&self, path: &str) -> Result<&'a [u8]> {
+        match self.entry(path).map(|entry| &entry.kind) {
+            Some(NitroEntryKind::File(range)) => Ok(&self.rom[range.clone()]),
+            Some(NitroEntryKind::Dir) => Err(error(format!("'{path}' is a directory"))),
+            None => Err(error(format!("'{path}' doesn't exist"))),
+        }
+    }
+
+    pub fn read_file_id(&self, id: u16) -> Option<&'a [u8]> {
+        let range = self.fat.get(id as usize)?;
+        Some(&self.rom[range.clone()])
+    }
+
+    /// The file that holds the byte at `offset` of the ROM, to tell which file a game reads.
+    pub fn file_at(&self, offset: usize) -> Option<&NitroEntry> {
+        self.files()
+            .find(|entry| entry.range().is_some_and(|range| range.contains(&offset)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FNT_OFFSET: usize = 0x200;
+    const FAT_OFFSET: usize = 0x300;
+
+    /// The FNT of directories given by their first file ID and their sub-table, the root first.
+    fn fnt(dirs: &[(u16, &[u8])]) -> Vec<u8> {
+        let mut fnt = Vec::new();
+        let mut offs = dirs.len() * TABLE_ENTRY_LEN;
+        for (i, &(first_file_id, sub_table)) in dirs.iter().enumerate() {
+            // the parent ID of the root holds the number of directories.
+            let parent = if i == 0 {
+                dirs.len() as u16
+            } else {
+                ROOT_DIR_ID
+            };
+            fnt.extend_from_slice(&(offs as u32).to_le_bytes());
+            fnt.extend_from_slice(&first_file_id.to_le_bytes());
+            fnt.extend_from_slice(&parent.to_le_bytes());
+            offs += sub_table.len();
+        }
+        for (_, sub_table) in dirs {
+            fnt.extend_from_slice(sub_table);
+        }
+        fnt
+    }
+
+    /// A ROM of 0x400 bytes with `fnt` and the FAT of `files` by their start and end, the files
+    /// hold their ID.
+    fn rom(fnt: &[u8], files: &[(usize, usize)]) -> Vec<u8> {
+        let mut rom = vec![0; 0x400];
+        for (offs, val) in [
+            (0x40, FNT_OFFSET),
+            (0x44, fnt.len()),
+            (0x48, FAT_OFFSET),
+            (0x4C, files.len() * TABLE_ENTRY_LEN),
+        ] {
+            rom[offs..offs + 4].copy_from_slice(&(val as u32).to_le_bytes());
+        }
+        rom[FNT_OFFSET..FNT_OFFSET + fnt.len()].copy_from_slice(fnt);
+        for (id, &(start, end)) in files.iter().enumerate() {
+            let entry = FAT_OFFSET + id * TABLE_ENTRY_LEN;
+            rom[entry..entry + 4].copy_from_slice(&(start as u32).to_le_bytes());
+            rom[entry + 4..entry + 8].copy_from_slice(&(end as u32).to_le_bytes());
+            if let Some(data) = rom.get_mut(start..end) {
+                data.fill(id as u8);
+            }
+        }
+        rom
+    }
+
+    /// "a.bin", "d/b.bin" and "d/e/c.bin", after an overlay without a name.
+    fn tree() -> Vec<u8> {
+        let fnt = fnt(&[
+            (1, b"\x05a.bin\x81d\x01\xF0\x00"),
+            (2, b"\x81e\x02\xF0\x05b.bin\x00"),
+            (3, b"\x05c.bin\x00"),
+        ]);
+        rom(
+            &fnt,
+            &[
+                (0x380, 0x388),
+                (0x388, 0x390),
+                (0x390, 0x398),
+                (0x398, 0x3A0),
+            ],
+        )
+    }
+
+    fn parse(rom: &[u8]) -> Result<NitroFs<'_>> {
+        Cartridge::new(rom).unwrap().nitro_fs()
+    }
+
+    #[test]
+    fn entries_have_paths_ids_and_ranges() {
+        let rom = tree();
+        let fs = parse(&rom).unwrap();
+        let entries: Vec<_> = fs
+            .entries()
+            .map(|entry| (entry.path.as_str(), entry.id, entry.range()))
+            .collect();
+        assert_eq!(
+            entries,
+            [
+                ("a.bin", 1, Some(0x388..0x390)),
+                ("d", 0xF001, None),
+                ("d/e", 0xF002, None),
+                ("d/e/c.bin", 3, Some(0x398..0x3A0)),
+                ("d/b.bin", 2, Some(0x390..0x398)),
+            ]
+        );
+        assert_eq!(fs.files().count(), 3);
+        assert_eq!(fs.entry("/d/e/").unwrap().name(), "e");
+        assert!(fs.entry("d/e").unwrap().is_dir());
+        assert_eq!(fs.fat().len(), 4);
+        assert_eq!(fs.read_file_id(0), Some(&[0; 8][..]));
+        assert_eq!(fs.read_file_id(4), None);
+    }
+
+    #[test]
+    fn files_are_read_by_path_and_found_by_offset() {
+        let rom = tree();
+        let fs = parse(&rom).unwrap();
+        assert_eq!(fs.read_file("d/b.bin").unwrap(), [2; 8]);
+        assert_eq!(fs.read_file("/d/e/c.bin").unwrap(), [3; 8]);
+        assert!(fs.read_file("d").is_err());
+        assert!(fs.read_file("b.bin").is_err());
+
+        assert_eq!(fs.file_at(0x388).unwrap().path, "a.bin");
+        assert_eq!(fs.file_at(0x397).unwrap().path, "d/b.bin");
+        // the overlay has no name.
+        assert_eq!(fs.file_at(0x380), None);
+        assert_eq!(fs.file_at(0x3A0), None);
+    }
+
+    #[test]
+    fn cyclic_and_unknown_directories_are_rejected() {
+        let files = [(0x380, 0x388)];
+        // "d" lists the root as a subdirectory.
+        let cycle = fnt(&[(0, b"\x81d\x01\xF0\x00"), (0, b"\x84root\x00\xF0\x00")]);
+        assert!(parse(&rom(&cycle, &files)).is_err());
+        // "d" is listed twice.
+        let twice = fnt(&[(0, b"\x81d\x01\xF0\x81e\x01\xF0\x00"), (0, b"\x00")]);
+        assert!(parse(&rom(&twice, &files)).is_err());
+        // there's no third directory.
+        let unknown = fnt(&[(0, b"\x81d\x02\xF0\x00"), (0, b"\x00")]);
+        assert!(parse(&rom(&unknown, &files)).is_err());
+        // a file ID below the directory IDs.
+        let file_id = fnt(&[(0, b"\x81d\x00\x10\x00"), (0, b"\x00")]);
+        assert!(parse(&rom(&file_id, &files)).is_err());
+
+        // more directories than fit in the FNT, and none.
+        let mut count = fnt(&[(0, b"\x00")]);
+        count[6] = 2;
+        assert!(parse(&rom(&count, &files)).is_err());
+        count[6] = 0;
+        assert!(parse(&rom(&count, &files)).is_err());
+    }
+
+    #[test]
+    fn fat_entries_outside_of_the_rom_are_rejected() {
+        let fnt = fnt(&[(0, b"\x05a.bin\x00")]);
+        assert!(parse(&rom(&fnt, &[(0x380, 0x388)])).is_ok());
+        assert!(parse(&rom(&fnt, &[(0x3F8, 0x408)])).is_err());
+        assert!(parse(&rom(&fnt, &[(0x388, 0x380)])).is_err());
+        // a file without a FAT entry.
+        assert!(parse(&rom(&fnt, &[])).is_err());
+
+        let mut rom = rom(&fnt, &[(0x380, 0x388)]);
+        rom[0x48..0x4C].copy_from_slice(&0x3FCu32.to_le_bytes());
+        assert!(parse(&rom).is_err());
+    }
+}
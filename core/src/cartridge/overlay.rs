@@ -0,0 +1,214 @@
+//! Replacement files served in place of the files of a ROM, so modified files can be tried out
+//! without repacking the ROM.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use super::{Cartridge, NitroEntryKind};
+use crate::error::{Error, Result};
+
+/// Replaced files are placed after the end of the ROM, each aligned like in a packed ROM.
+const FILE_ALIGN: usize = 0x200;
+
+/// Replacement files by their path in the file system of the ROM.
+#[derive(Debug, Clone, Default)]
+pub struct FileOverlay {
+    files: BTreeMap<String, Vec<u8>>,
+}
+
+impl FileOverlay {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The files in `dir` and its subdirectories, with `dir` standing in for the root directory
+    /// of the ROM.
+    pub fn from_dir(dir: impl AsRef<Path>) -> Result<Self> {
+        let mut overlay = Self::new();
+        overlay.read_dir(dir.as_ref(), "")?;
+        Ok(overlay)
+    }
+
+    fn read_dir(&mut self, dir: &Path, path: &str) -> Result<()> {
+        let io_error =
+            |err: std::io::Error| Error::Cartridge(format!("'{}': {err}", dir.display()));
+        for entry in std::fs::read_dir(dir).map_err(io_error)? {
+            let entry = entry.map_err(io_error)?;
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            let entry_path = if path.is_empty() {
+                name.into_owned()
+            } else {
+                format!("{path}/{name}")
+            };
+            if entry.file_type().map_err(io_error)?.is_dir() {
+                self.read_dir(&entry.path(), &entry_path)?;
+            } else {
+                let data = std::fs::read(entry.path()).map_err(io_error)?;
+                self.insert(&entry_path, data);
+            }
+        }
+        Ok(())
+    }
+
+    /// Replace the file at `path`, a leading '/' is optional.
+    pub fn insert(&mut self, path: &str, data: Vec<u8>) {
+        self.files.insert(path.trim_matches('/').into(), data);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    pub fn paths(&self) -> impl Iterator<Item = &str> + '_ {
+        self.files.keys().map(String::as_str)
+    }
+
+    /// Lay out the replacement files after the end of `rom` and rebuild its FAT to point at them.
+    /// Files that aren't in the file system of the ROM are left out, see
+    /// [`VirtualRom::skipped`].
+    pub fn map(&self, rom: &[u8]) -> Result<VirtualRom> {
+        let cartridge = Cartridge::new(rom)?;
+        let header = cartridge.header();
+        let fs = cartridge.nitro_fs()?;
+
+        let fat_offset = header.fat_offset() as usize;
+        let mut fat = rom[fat_offset..fat_offset + header.fat_size() as usize].to_vec();
+        let mut files = Vec::with_capacity(self.files.len());
+        let mut skipped = Vec::new();
+        let mut end = rom.len();
+        for (path, data) in &self.files {
+            let id = match fs.entry(path) {
+                Some(entry) if !entry.is_dir() => entry.id as usize,
+                _ => {
+                    skipped.push(path.clone());
+                    continue;
+                }
+            };
+            let start = end.next_multiple_of(FILE_ALIGN);
+            end = start + data.len();
+            let entry = &mut fat[id * 8..id * 8 + 8];
+            entry[..4].copy_from_slice(&(start as u32).to_le_bytes());
+            entry[4..].copy_from_slice(&(end as u32).to_le_bytes());
+            files.push((start, data.clone()));
+        }
+
+        Ok(VirtualRom {
+            fat_offset,
+            fat: fat.into(),
+            files,
+            len: end,
+            skipped,
+        })
+    }
+}
+
+/// A ROM with replaced files as seen through the gamecard, the rebuilt FAT and the files past
+/// the end of the ROM are served on top of the original ROM which stays untouched.
+#[derive(Debug, Clone)]
+pub struct VirtualRom {
+    fat_offset: usize,
+    fat: Box<[u8]>,
+    /// Start offset and contents of the replacement files, in order of their offsets.
+    files: Vec<(usize, Vec<u8>)>,
+    len: usize,
+    skipped: Vec<String>,
+}
+
+impl VirtualRom {
+    /// Length of the ROM with the replaced files appended.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Paths of the replacement files that aren't files of the ROM and were left out.
+    pub fn skipped(&self) -> &[String] {
+        &self.skipped
+    }
+
+    /// The byte at `adr` of the ROM with the files replaced.
+    pub fn read(&self, rom: &[u8], adr: usize) -> Option<u8> {
+        if let Some(offs) = adr.checked_sub(self.fat_offset) {
+            if let Some(&byte) = self.fat.get(offs) {
+                return Some(byte);
+            }
+        }
+        if adr < rom.len() {
+            return rom.get(adr).copied();
+        }
+        let index = self.files.partition_point(|&(start, _)| start <= adr);
+        let (start, data) = self.files.get(index.checked_sub(1)?)?;
+        data.get(adr - start).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A ROM of 0x400 bytes with "a.bin" at 0x380 and "b.bin" at 0x390 in its root directory.
+    fn rom() -> Vec<u8> {
+        let mut rom = vec![0; 0x400];
+        let fnt = b"\x08\0\0\0\0\0\x01\0\x05a.bin\x05b.bin\0";
+        for (offs, val) in [(0x40, 0x200), (0x44, fnt.len()), (0x48, 0x300), (0x4C, 16)] {
+            rom[offs..offs + 4].copy_from_slice(&(val as u32).to_le_bytes());
+        }
+        rom[0x200..0x200 + fnt.len()].copy_from_slice(fnt);
+        for (id, (start, end)) in [(0x380u32, 0x390u32), (0x390, 0x398)]
+            .into_iter()
+            .enumerate()
+        {
+            rom[0x300 + id * 8..0x304 + id * 8].copy_from_slice(&start.to_le_bytes());
+            rom[0x304 + id * 8..0x308 + id * 8].copy_from_slice(&end.to_le_bytes());
+        }
+        rom[0x380..0x390].fill(0xAA);
+        rom[0x390..0x398].fill(0xBB);
+        rom
+    }
+
+    #[test]
+    fn virtual_rom_serves_the_rebuilt_fat_and_grown_files() {
+        let rom = rom();
+        let mut overlay = FileOverlay::new();
+        overlay.insert("/b.bin", vec![0xCC; 0x300]);
+        overlay.insert("c.bin", vec![0xDD; 4]);
+        let virtual_rom = overlay.map(&rom).unwrap();
+        assert_eq!(virtual_rom.skipped(), ["c.bin"]);
+        assert_eq!(virtual_rom.len(), 0x700);
+
+        let read = |adr| virtual_rom.read(&rom, adr);
+        assert_eq!(read(0x308), Some(0x00));
+        assert_eq!(read(0x309), Some(0x04));
+        assert_eq!(read(0x30D), Some(0x07));
+        assert_eq!(read(0x390), Some(0xBB));
+        assert_eq!(read(0x400), Some(0xCC));
+        assert_eq!(read(0x6FF), Some(0xCC));
+        assert_eq!(read(0x700), None);
+
+        let built: Vec<u8> = (0..virtual_rom.len())
+            .map(|adr| read(adr).unwrap())
+            .collect();
+        let fs = Cartridge::new(&built).unwrap().nitro_fs().unwrap();
+        assert_eq!(fs.read_file("a.bin").unwrap(), [0xAA; 0x10]);
+        assert_eq!(fs.read_file("b.bin").unwrap(), [0xCC; 0x300]);
+    }
+
+    #[test]
+    fn files_past_the_end_of_the_rom_are_aligned() {
+        let rom = rom();
+        let mut overlay = FileOverlay::new();
+        overlay.insert("a.bin", vec![0x11; 3]);
+        overlay.insert("b.bin", vec![0x22; 3]);
+        let virtual_rom = overlay.map(&rom).unwrap();
+        assert!(virtual_rom.skipped().is_empty());
+        assert_eq!(virtual_rom.len(), 0x603);
+        assert_eq!(virtual_rom.read(&rom, 0x402), Some(0x11));
+        assert_eq!(virtual_rom.read(&rom, 0x403), None);
+        assert_eq!(virtual_rom.read(&rom, 0x5FF), None);
+        assert_eq!(virtual_rom.read(&rom, 0x600), Some(0x22));
+    }
+}
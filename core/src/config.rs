@@ -0,0 +1,119 @@
+/// Size of the main memory installed in the console.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MainMemorySize {
+    /// 4MiB found in retail consoles.
+    #[default]
+    Retail,
+    /// 8MiB found in debug consoles.
+    Debug,
+}
+
+impl MainMemorySize {
+    /// Size in bytes.
+    pub const fn bytes(self) -> usize {
+        match self {
+            MainMemorySize::Retail => mb!(4),
+            MainMemorySize::Debug => mb!(8),
+        }
+    }
+}
+
+/// Value RAM, VRAM and WRAM hold at power on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MemoryFill {
+    #[default]
+    Zero,
+    /// Every byte set to 0xFF.
+    Ones,
+    /// A little endian word repeated over the whole memory.
+    Pattern(u32),
+    /// Pseudo-random bytes. If no seed is given one is picked on power on
+    /// and written back into the config of the core.
+    Random { seed: Option<u64> },
+}
+
+impl MemoryFill {
+    /// Fill `mem`, the seed of a random fill has to be resolved beforehand.
+    pub(crate) fn fill(&self, mem: &mut [u8]) {
+        match *self {
+            MemoryFill::Zero => mem.fill(0),
+            MemoryFill::Ones => mem.fill(u8::MAX),
+            MemoryFill::Pattern(word) => {
+                for (i, byte) in mem.iter_mut().enumerate() {
+                    *byte = word.to_le_bytes()[i & 0b11];
+                }
+            }
+            MemoryFill::Random { seed } => {
+                let mut state = seed.expect("random memory fill seed was not resolved");
+                for chunk in mem.chunks_mut(8) {
+                    let rand = splitmix64(&mut state).to_le_bytes();
+                    chunk.copy_from_slice(&rand[..chunk.len()]);
+                }
+            }
+        }
+    }
+
+    /// Pick a seed from the host clock if the fill is random and has none.
+    pub(crate) fn resolve_seed(&mut self) {
+        if let MemoryFill::Random { seed: seed @ None } = self {
+            let nanos = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|time| time.as_nanos() as u64)
+                .unwrap_or_default();
+            *seed = Some(nanos);
+        }
+    }
+}
+
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E3779B97F4A7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+/// Time the real-time clock counts from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RtcSource {
+    /// The UTC time of the host.
+    #[default]
+    HostClock,
+    /// A fixed time in seconds since the Unix epoch at power on, advancing with emulated time
+    /// so runs are reproducible.
+    Fixed { start: i64 },
+}
+
+/// Source of the microphone samples.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum MicSource {
+    #[default]
+    Silence,
+    /// Mono samples played in a loop, e.g. from [`crate::audio::decode_wav`].
+    Loop { samples: Vec<i16>, sample_rate: u32 },
+    /// Mono samples pushed through [`crate::Core::push_mic_samples`], silence while there are
+    /// none.
+    Pushed { sample_rate: u32 },
+}
+
+use crate::cartridge::FileOverlay;
+use crate::firmware::FirmwareSettings;
+
+/// Configuration of the emulated console, supplied when creating a [`crate::Core`].
+#[derive(Debug, Clone, Default)]
+pub struct CoreConfig {
+    pub main_memory_size: MainMemorySize,
+    pub memory_fill: MemoryFill,
+    /// Firmware image served over the SPI bus, synthesised from `firmware_settings` if `None`.
+    pub firmware: Option<Vec<u8>>,
+    /// User settings of the synthesised firmware, unused when a firmware image is supplied.
+    pub firmware_settings: FirmwareSettings,
+    pub rtc: RtcSource,
+    /// Rate of the audio returned by [`crate::Core::drain_audio`], the native rate if `None`.
+    pub audio_sample_rate: Option<u32>,
+    pub mic: MicSource,
+    /// ARM7 BIOS image, or only its KEY1 table, used for the encryption of the gamecard.
+    pub arm7_bios: Option<Vec<u8>>,
+    /// Files served by the gamecard in place of the files of the ROM.
+    pub file_overlay: Option<FileOverlay>,
+}
@@ -1,23 +1,77 @@
 use crate::bus::{self, masks, PtrTable};
+use crate::cartridge::{self, SecureArea, VirtualRom};
 use crate::cpu::arm9;
-use crate::mmap::MAIN_MEMORY_START;
+use crate::firmware;
+use crate::gamecard::Gamecard;
+use crate::ipc::Ipc;
+use crate::keypad::Keypad;
+use crate::mic::Mic;
+use crate::mmap::{
+    ARM7_WRAM_REGION_END, ARM7_WRAM_START, MAIN_MEMORY_REGION_END, MAIN_MEMORY_START,
+    SHARED_WRAM_START, VRAM_LCDC_START,
+};
+use crate::power::{self, Power};
+use crate::rtc::Rtc;
+use crate::scheduler::{Event, Scheduler, ARM9_CLOCK};
+use crate::spi::{Flash, Spi};
+use crate::spu::{self, Spu};
 use crate::unsafemem::UnsafeMem;
-use crate::{Arm9, Cartridge, Core, Engine, Result};
+use crate::wifi::{self, Wifi};
+use crate::{Arm7, Arm9, Cartridge, CartridgeHeader, Core, CoreConfig, Engine, MemoryFill, Result};
 
 impl<E: Engine> Core<E> {
+    /// ID of the ST M45PE20 used for the firmware.
+    const FIRMWARE_JEDEC_ID: [u8; 3] = [0x20, 0x40, 0x12];
+
     pub fn new(#[cfg(feature = "log")] logger: slog::Logger) -> Self {
+        Self::with_config(
+            CoreConfig::default(),
+            #[cfg(feature = "log")]
+            logger,
+        )
+    }
+
+    pub fn with_config(
+        mut config: CoreConfig,
+        #[cfg(feature = "log")] logger: slog::Logger,
+    ) -> Self {
         let arm9 = Arm9::<E>::new(
             #[cfg(feature = "log")]
             logger.new(slog::o!("arm9" => "arm9")),
         );
+        let arm7 = Arm7::<E>::new(
+            #[cfg(feature = "log")]
+            logger.new(slog::o!("arm7" => "arm7")),
+        );
+        config.memory_fill.resolve_seed();
+        let firmware = match config.firmware.take() {
+            Some(firmware) => firmware.into_boxed_slice(),
+            None => firmware::build(&config.firmware_settings).into_boxed_slice(),
+        };
+        let main_memory_len = config.main_memory_size.bytes();
         let mut core = Self {
             global_data: Default::default(),
             arm9,
-            main_memory: UnsafeMem::from_box(
-                vec![0; mb!(4)]
+            arm7,
+            scheduler: Scheduler::new(),
+            ipc: Ipc::new(),
+            keypad: Keypad::new(),
+            power: Power::new(),
+            spi: Spi::new(Flash::new(firmware, Self::FIRMWARE_JEDEC_ID)),
+            mic: Mic::new(config.mic.clone()),
+            rtc: Rtc::new(config.rtc),
+            spu: Spu::new(config.audio_sample_rate),
+            wifi: Wifi::new(),
+            gamecard: Gamecard::new(),
+            config,
+            main_memory: UnsafeMem::from_box(vec![0; main_memory_len].into_boxed_slice()),
+            shared_wram: UnsafeMem::new([0; kb!(32)]),
+            arm7_wram: UnsafeMem::new([0; kb!(64)]),
+            vram: UnsafeMem::from_box(
+                vec![0; kb!(656)]
                     .into_boxed_slice()
                     .try_into()
-                    .expect("failed to initialize main memory"),
+                    .expect("failed to initialize vram"),
             ),
             #[cfg(feature = "log")]
             logger,
@@ -26,33 +80,107 @@ impl<E: Engine> Core<E> {
         core
     }
 
+    pub fn config(&self) -> &CoreConfig {
+        &self.config
+    }
+
+    /// The cartridge in the slot, none before a ROM is loaded.
+    pub fn cartridge(&self) -> Option<Cartridge<'_>> {
+        Cartridge::new(self.gamecard.rom()).ok()
+    }
+
+    /// Emulated time since power on.
+    pub fn elapsed(&self) -> std::time::Duration {
+        let now = self.scheduler.now();
+        std::time::Duration::from_nanos((now as u128 * 1_000_000_000 / ARM9_CLOCK as u128) as u64)
+    }
+
+    /// The system was powered off through the power management chip.
+    pub fn powered_off(&self) -> bool {
+        self.spi.power.power_off()
+    }
+
     fn init(&mut self) {
         self.arm9.init();
+        self.arm7.init();
+        self.scheduler
+            .schedule(spu::SAMPLE_PERIOD, Event::SpuSample);
+        self.scheduler.schedule(wifi::TICK_PERIOD, Event::WifiTick);
+
+        // fill memory with its power on values.
+        let memory_fill = self.config.memory_fill;
+        if let MemoryFill::Random { seed: Some(seed) } = memory_fill {
+            info!(self.logger, "random memory fill seed: {seed}");
+        }
+        unsafe {
+            memory_fill.fill(&mut *self.main_memory.get());
+            memory_fill.fill(&mut *self.shared_wram.get());
+            memory_fill.fill(&mut *self.arm7_wram.get());
+            memory_fill.fill(&mut *self.vram.get());
+        }
 
         let main_memory_slice = unsafe { &mut *self.main_memory.get() };
-        let main_memory_ptr = unsafe { (*main_memory_slice).as_mut_ptr() };
+        let main_memory_len = main_memory_slice.len();
+        let main_memory_ptr = main_memory_slice.as_mut_ptr();
 
-        // map pointer tables in the arm9 core.
+        // map pointer tables in the arm9 and arm7 cores.
 
-        // map main memory (mirrored 16MiB).
+        // map main memory (mirrored over 16MiB).
+        let mirrors = (MAIN_MEMORY_REGION_END - MAIN_MEMORY_START) as usize / main_memory_len;
         let mut adr = MAIN_MEMORY_START;
-        for _ in 0..4 {
+        for _ in 0..mirrors {
             let mut ptr = main_memory_ptr;
             let pg_size = PtrTable::PG_SIZE;
-            for _ in 0..(mb!(4) / pg_size) {
-                self.arm9.bus_ptrs.map(
-                    PtrTable::adr_to_page(adr),
-                    masks::R | masks::W_16_32 | masks::W_8,
-                    ptr,
-                );
+            for _ in 0..(main_memory_len / pg_size) {
+                let attrs = masks::R | masks::W_16_32 | masks::W_8;
+                self.arm9
+                    .bus_ptrs
+                    .map(PtrTable::adr_to_page(adr), attrs, ptr);
+                self.arm7
+                    .bus_ptrs
+                    .map(PtrTable::adr_to_page(adr), attrs, ptr);
                 debug!(self.logger, "{adr:08x} => {ptr:?}");
                 adr += pg_size as u32;
                 ptr = unsafe { ptr.add(pg_size) }
             }
         }
+
+        // map wram as the BIOS leaves it on boot: all of the shared wram belongs to the arm7 and
+        // is followed by the arm7 wram, both mirrored. the arm9 has none of it.
+        let shared_wram_ptr = unsafe { (*self.shared_wram.get()).as_mut_ptr() };
+        let arm7_wram_ptr = unsafe { (*self.arm7_wram.get()).as_mut_ptr() };
+        let mut adr = SHARED_WRAM_START;
+        while adr < ARM7_WRAM_REGION_END {
+            let ptr = if adr < ARM7_WRAM_START {
+                unsafe { shared_wram_ptr.add(adr as usize & (kb!(32) - 1)) }
+            } else {
+                unsafe { arm7_wram_ptr.add(adr as usize & (kb!(64) - 1)) }
+            };
+            self.arm7.bus_ptrs.map(
+                PtrTable::adr_to_page(adr),
+                masks::R | masks::W_16_32 | masks::W_8,
+                ptr,
+            );
+            adr += PtrTable::PG_SIZE as u32;
+        }
+
+        // map vram, every bank is in LCDC mode where they're laid out back to back.
+        let vram_ptr = unsafe { (*self.vram.get()).as_mut_ptr() };
+        let vram_len = kb!(656);
+        let mut offs = 0;
+        while offs < vram_len {
+            let adr = VRAM_LCDC_START + offs as u32;
+            self.arm9.bus_ptrs.map(
+                PtrTable::adr_to_page(adr),
+                masks::R | masks::W_16_32,
+                unsafe { vram_ptr.add(offs) },
+            );
+            offs += PtrTable::PG_SIZE;
+        }
     }
 
-    pub fn load_rom(&mut self, rom: Box<[u8]>) -> Result<()> {
+    pub fn load_rom(&mut self, mut rom: Box<[u8]>) -> Result<()> {
+        self.prepare_secure_area(&mut rom);
         let cartridge = Cartridge::new(&rom)?;
         let header = cartridge.header();
 
@@ -66,16 +194,18 @@ impl<E: Engine> Core<E> {
         );
 
         // validate the cartridge has expected values.
-        cartridge.validate()?;
+        cartridge.validate(self.config.main_memory_size)?;
 
         self.load_rom_internal(&cartridge);
+        self.insert_gamecard(rom);
 
         Ok(())
     }
 
     /// # Safety
     /// may cause out of bounds access if the ROM is malformed.
-    pub unsafe fn load_unvalidated_rom(&mut self, rom: Box<[u8]>) -> Result<()> {
+    pub unsafe fn load_unvalidated_rom(&mut self, mut rom: Box<[u8]>) -> Result<()> {
+        self.prepare_secure_area(&mut rom);
         let cartridge = Cartridge::new(&rom)?;
         let header = cartridge.header();
 
@@ -89,10 +219,57 @@ impl<E: Engine> Core<E> {
         );
 
         self.load_rom_internal(&cartridge);
+        self.insert_gamecard(rom);
 
         Ok(())
     }
 
+    /// The KEY1 table of the ARM7 BIOS of the config.
+    fn key1_table(&self) -> Option<&[u8]> {
+        let bios = self.config.arm7_bios.as_deref()?;
+        match cartridge::key1_table(bios) {
+            Ok(table) => Some(table),
+            Err(err) => {
+                warn!(self.logger, "{err}");
+                None
+            }
+        }
+    }
+
+    /// Decrypt the secure area of encrypted dumps, the ARM9 binary is loaded from it.
+    fn prepare_secure_area(&self, rom: &mut [u8]) {
+        if SecureArea::of(rom) == SecureArea::Encrypted {
+            match self.key1_table() {
+                Some(table) => {
+                    if let Err(err) = cartridge::decrypt_secure_area(rom, table) {
+                        warn!(self.logger, "{err}");
+                    }
+                }
+                None => warn!(
+                    self.logger,
+                    "the secure area is encrypted but there is no ARM7 BIOS to decrypt it"
+                ),
+            }
+        }
+    }
+
+    fn insert_gamecard(&mut self, rom: Box<[u8]>) {
+        let table = self.key1_table().map(<[u8]>::to_vec);
+        let overlay = self.config.file_overlay.as_ref().and_then(|overlay| {
+            overlay
+                .map(&rom)
+                .map_err(|err| warn!(self.logger, "not replacing any files: {err}"))
+                .ok()
+        });
+        for path in overlay.iter().flat_map(VirtualRom::skipped) {
+            warn!(
+                self.logger,
+                "'{path}' isn't a file of the ROM, not replacing it"
+            );
+        }
+        self.gamecard.insert(rom, table.as_deref(), overlay);
+    }
+
     fn load_rom_internal(&mut self, cartridge: &Cartridge) {
         let header = cartridge.header();
         let main_memory = unsafe { &mut *self.main_memory.get() };
@@ -109,24 +286,48 @@ impl<E: Engine> Core<E> {
         let arm7_entry = header.arm7_entry_address();
         let arm7_ram = header.arm7_ram_address();
 
-        // map cartridge header.
-        main_memory[0x3ffe00..0x400000].copy_from_slice(header.as_ref());
+        // map cartridge header at the end of main memory.
+        let header_offs = main_memory.len() - CartridgeHeader::LEN;
+        main_memory[header_offs..].copy_from_slice(header.as_ref());
+
+        // the firmware leaves the chip ID of the card at 0x027FF800 and 0x027FFC00.
+        let chip_id = Gamecard::chip_id_for(cartridge.data().len());
+        for offs in [0x800, 0x804, 0x400, 0x404] {
+            let offs = main_memory.len() - offs;
+            main_memory[offs..offs + 4].copy_from_slice(&chip_id.to_le_bytes());
+        }
+
+        // the firmware leaves a copy of the user settings at 0x027FFC80.
+        if let Some(settings) = firmware::user_settings_of(self.spi.firmware.data()) {
+            let settings_offs = main_memory.len() - 0x380;
+            main_memory[settings_offs..settings_offs + settings.len()].copy_from_slice(settings);
+        }
 
         // map the arm7 rom.
         let arm7_offset_beg = arm7_ram;
         let arm7_offset_end = arm7_offset_beg + arm7_size;
-        let _arm7_rom = cartridge.arm7_rom();
-        for (_i, _adr) in (arm7_offset_beg..arm7_offset_end).enumerate() {}
+        let arm7_rom = cartridge.arm7_rom();
+        for (i, adr) in (arm7_offset_beg..arm7_offset_end).enumerate() {
+            bus::arm7::write8(self, adr, arm7_rom[i]);
+        }
 
         // map the arm9 rom.
         let arm9_offset_beg = arm9_ram;
         let arm9_offset_end = arm9_offset_beg + arm9_size;
-        let arm9_rom = cartridge.arm9_rom();
+        let mut arm9_rom = cartridge.arm9_rom().to_vec();
+        cartridge::destroy_secure_area_marker(cartridge.data(), &mut arm9_rom);
         for (i, adr) in (arm9_offset_beg..arm9_offset_end).enumerate() {
             bus::arm9::write8(self, adr, arm9_rom[i]);
         }
 
-        // set the correct pc values.
-        self.arm9.pc_set(arm9_entry);
+        // the firmware sets POSTFLG once it has booted.
+        power::postflg_set::<E, true>(self, 0b1);
+        power::postflg_set::<E, false>(self, 0b1);
+
+        // start at the entry points with the stacks the BIOS sets up, the arm9 ones are in DTCM.
+        let dtcm_base = self.arm9.dtcm_base;
+        self.arm9
+            .boot(arm9_entry, dtcm_base + 0x2F7C, dtcm_base + 0x3F80);
+        self.arm7.boot(arm7_entry, 0x0380FD80, 0x0380FF80);
     }
 }
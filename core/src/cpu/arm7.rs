@@ -0,0 +1,155 @@
+use super::psr::Psr;
+
+use crate::bus::{self, masks, PtrTable};
+use crate::dma::Dma;
+use crate::irq::{Interrupt, Irq};
+use crate::mmap::MAIN_MEMORY_START;
+use crate::timers::Timers;
+use crate::{mmap, Core, Engine};
+
+use slog::Logger;
+
+/// Power state set through HALTCNT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Halt {
+    Running,
+    /// Woken up by any enabled interrupt.
+    Halted,
+    /// Woken up by the enabled interrupts of [`Halt::SLEEP_WAKEUP`].
+    Sleeping,
+}
+
+impl Halt {
+    const SLEEP_WAKEUP: u32 = Interrupt::Rtc.mask()
+        | Interrupt::Keypad.mask()
+        | Interrupt::GbaSlot.mask()
+        | Interrupt::ScreensUnfolding.mask();
+}
+
+pub struct Arm7<E: Engine> {
+    pub gpr: [u32; 16],
+    pub cpsr: Psr,
+    /// r13 and r14 of IRQ mode while not in it, and the ones of the interrupted mode while in it.
+    irq_bank: [u32; 2],
+    spsr_irq: Psr,
+    pub(crate) irq: Irq,
+    pub(crate) halt: Halt,
+    pub(crate) timers: Timers,
+    pub(crate) dma: Dma,
+    pub(crate) bus_ptrs: Box<PtrTable>,
+    pub(crate) data: E::ARM7Data,
+    pub(crate) logger: Logger,
+}
+
+impl<E: Engine> Arm7<E> {
+    pub(crate) const IRQ_VECTOR: u32 = 0x00000018;
+
+    pub fn new(#[cfg(feature = "log")] logger: Logger) -> Self {
+        Self {
+            bus_ptrs: PtrTable::new_boxed(),
+            gpr: [0; 16],
+            irq_bank: [0; 2],
+            spsr_irq: Psr::new(),
+            irq: Irq::new_arm7(),
+            halt: Halt::Running,
+            timers: Timers::new(),
+            dma: Dma::new(),
+            data: Default::default(),
+            cpsr: Psr::new(),
+            #[cfg(feature = "log")]
+            logger,
+        }
+    }
+
+    pub fn init(&mut self) {
+        self.gpr = Default::default();
+        self.cpsr = Default::default();
+        self.irq_bank = Default::default();
+        self.spsr_irq = Default::default();
+        self.irq = Irq::new_arm7();
+        self.halt = Halt::Running;
+        self.timers = Timers::new();
+        self.dma = Dma::new();
+    }
+
+    pub fn halted(&self) -> bool {
+        self.halt != Halt::Running
+    }
+
+    /// Wake the CPU up if an interrupt allows it, and deliver a pending interrupt if CPSR.I
+    /// allows it.
+    pub fn check_irq(&mut self) {
+        let wakeup = match self.halt {
+            Halt::Running => false,
+            Halt::Halted => self.irq.requested(),
+            Halt::Sleeping => self.irq.ie() & self.irq.if_() & Halt::SLEEP_WAKEUP != 0,
+        };
+        if wakeup {
+            self.halt = Halt::Running;
+        }
+        if self.halted() {
+            return;
+        }
+        if self.irq.pending() && !self.cpsr.i() {
+            self.enter_irq();
+        }
+    }
+
+    fn enter_irq(&mut self) {
+        // the next instruction plus 4, the handler returns with `subs pc, lr, #4`.
+        let return_adr = self.gpr[15].wrapping_add(4);
+        self.spsr_irq = self.cpsr;
+        self.gpr[13..15].swap_with_slice(&mut self.irq_bank);
+        self.cpsr.mode_set(Psr::MODE_IRQ);
+        self.cpsr.i_set(true);
+        self.cpsr.t_set(false);
+        self.lr_set(return_adr);
+        self.pc_set(Self::IRQ_VECTOR);
+    }
+
+    /// Return from an exception by restoring CPSR from the SPSR, which switches back to the
+    /// registers of the interrupted mode. Modes without a SPSR are left untouched.
+    pub(crate) fn restore_cpsr(&mut self) {
+        if self.cpsr.mode() != Psr::MODE_IRQ {
+            return;
+        }
+        self.cpsr = self.spsr_irq;
+        if self.cpsr.mode() != Psr::MODE_IRQ {
+            self.gpr[13..15].swap_with_slice(&mut self.irq_bank);
+        }
+    }
+
+    /// Start in system mode with the stacks the BIOS sets up before booting a game.
+    pub(crate) fn boot(&mut self, entry: u32, sp: u32, sp_irq: u32) {
+        self.cpsr.mode_set(Psr::MODE_SYSTEM);
+        self.gpr[13] = sp;
+        self.irq_bank[0] = sp_irq;
+        self.pc_set(entry);
+    }
+
+    pub fn gpr(&self, index: usize) -> u32 {
+        debug_assert!(index < self.gpr.len());
+        match index & 0xF {
+            i @ 0..=14 => unsafe { *self.gpr.get_unchecked(i & 0xF) },
+            15 => unsafe { self.gpr.get_unchecked(15) }.wrapping_add(4),
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn gpr_set(&mut self, index: usize, val: u32) {
+        debug_assert!(index < self.gpr.len());
+        unsafe { *self.gpr.get_unchecked_mut(index & 0xF) = val };
+    }
+
+    pub fn lr_set(&mut self, val: u32) {
+        self.gpr_set(14, val)
+    }
+
+    pub fn pc(&self) -> u32 {
+        self.gpr(15)
+    }
+
+    pub fn pc_set(&mut self, val: u32) {
+        self.gpr_set(15, val)
+    }
+}
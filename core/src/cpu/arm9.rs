@@ -1,7 +1,11 @@
 use super::psr::Psr;
 
 use crate::bus::{self, masks, PtrTable};
-use crate::mmap::{MAIN_MEMORY_END, MAIN_MEMORY_START};
+use crate::dma::Dma;
+use crate::irq::Irq;
+use crate::math::Math;
+use crate::mmap::MAIN_MEMORY_START;
+use crate::timers::Timers;
 use crate::{mmap, Core, Engine};
 
 use slog::Logger;
@@ -9,16 +13,36 @@ use slog::Logger;
 pub struct Arm9<E: Engine> {
     pub gpr: [u32; 16],
     pub cpsr: Psr,
+    /// r13 and r14 of IRQ mode while not in it, and the ones of the interrupted mode while in it.
+    irq_bank: [u32; 2],
+    spsr_irq: Psr,
+    pub(crate) irq: Irq,
+    /// Waiting for an interrupt after the CP15 wait for interrupt operation.
+    pub(crate) halted: bool,
+    pub(crate) timers: Timers,
+    pub(crate) dma: Dma,
+    pub(crate) math: Math,
+    pub(crate) dtcm_base: u32,
     pub(crate) bus_ptrs: Box<PtrTable>,
     pub(crate) data: E::ARM9Data,
     pub(crate) logger: Logger,
 }
 
 impl<E: Engine> Arm9<E> {
+    pub(crate) const IRQ_VECTOR: u32 = 0xFFFF0018;
+
     pub fn new(#[cfg(feature = "log")] logger: Logger) -> Self {
         Self {
-            bus_ptrs: Box::new(PtrTable::default()),
+            bus_ptrs: PtrTable::new_boxed(),
             gpr: [0; 16],
+            irq_bank: [0; 2],
+            spsr_irq: Psr::new(),
+            irq: Irq::new_arm9(),
+            halted: false,
+            timers: Timers::new(),
+            dma: Dma::new(),
+            math: Math::new(),
+            dtcm_base: 0x027E0000,
             data: Default::default(),
             cpsr: Psr::new(),
             #[cfg(feature = "log")]
@@ -29,6 +53,63 @@ impl<E: Engine> Arm9<E> {
     pub fn init(&mut self) {
         self.gpr = Default::default();
         self.cpsr = Default::default();
+        self.irq_bank = Default::default();
+        self.spsr_irq = Default::default();
+        self.irq = Irq::new_arm9();
+        self.halted = false;
+        self.timers = Timers::new();
+        self.dma = Dma::new();
+        self.math = Math::new();
+    }
+
+    pub fn halted(&self) -> bool {
+        self.halted
+    }
+
+    /// Wake the CPU up if an enabled interrupt is requested, and deliver a pending interrupt if
+    /// CPSR.I allows it.
+    pub fn check_irq(&mut self) {
+        if self.halted {
+            if !self.irq.requested() {
+                return;
+            }
+            self.halted = false;
+        }
+        if self.irq.pending() && !self.cpsr.i() {
+            self.enter_irq();
+        }
+    }
+
+    fn enter_irq(&mut self) {
+        // the next instruction plus 4, the handler returns with `subs pc, lr, #4`.
+        let return_adr = self.gpr[15].wrapping_add(4);
+        self.spsr_irq = self.cpsr;
+        self.gpr[13..15].swap_with_slice(&mut self.irq_bank);
+        self.cpsr.mode_set(Psr::MODE_IRQ);
+        self.cpsr.i_set(true);
+        self.cpsr.t_set(false);
+        self.lr_set(return_adr);
+        self.pc_set(Self::IRQ_VECTOR);
+    }
+
+    /// Return from an exception by restoring CPSR from the SPSR, which switches back to the
+    /// registers of the interrupted mode. Modes without a SPSR are left untouched.
+    pub(crate) fn restore_cpsr(&mut self) {
+        if self.cpsr.mode() != Psr::MODE_IRQ {
+            return;
+        }
+        self.cpsr = self.spsr_irq;
+        if self.cpsr.mode() != Psr::MODE_IRQ {
+            self.gpr[13..15].swap_with_slice(&mut self.irq_bank);
+        }
+    }
+
+    /// Start in system mode with the stacks the BIOS sets up before booting a game.
+    pub(crate) fn boot(&mut self, entry: u32, sp: u32, sp_irq: u32) {
+        self.cpsr.mode_set(Psr::MODE_SYSTEM);
+        self.gpr[13] = sp;
+        self.irq_bank[0] = sp_irq;
+        self.pc_set(entry);
     }
 
     pub fn gpr(&self, index: usize) -> u32 {
@@ -1,4 +1,5 @@
 /// Program status register.
+#[derive(Clone, Copy)]
 #[repr(transparent)]
 pub struct Psr(u32);
 
@@ -9,6 +10,9 @@ impl Default for Psr {
 }
 
 impl Psr {
+    pub const MODE_IRQ: u32 = 0x12;
+    pub const MODE_SYSTEM: u32 = 0x1F;
+
     pub fn new() -> Self {
         Self(0)
     }
@@ -61,6 +65,42 @@ impl Psr {
         toggle_bit!(self.0, 31, v)
     }
 
+    /// Get IRQ disable.
+    #[inline(always)]
+    pub fn i(&self) -> bool {
+        get_bit!(self.0, 7)
+    }
+
+    /// Set IRQ disable.
+    #[inline(always)]
+    pub fn i_set(&mut self, v: bool) {
+        toggle_bit!(self.0, 7, v)
+    }
+
+    /// Get thumb state.
+    #[inline(always)]
+    pub fn t(&self) -> bool {
+        get_bit!(self.0, 5)
+    }
+
+    /// Set thumb state.
+    #[inline(always)]
+    pub fn t_set(&mut self, v: bool) {
+        toggle_bit!(self.0, 5, v)
+    }
+
+    /// Get processor mode.
+    #[inline(always)]
+    pub fn mode(&self) -> u32 {
+        self.0 & 0x1F
+    }
+
+    /// Set processor mode.
+    #[inline(always)]
+    pub fn mode_set(&mut self, mode: u32) {
+        self.0 = (self.0 & !0x1F) | (mode & 0x1F)
+    }
+
     #[inline(always)]
     pub fn raw(&self) -> u32 {
         self.0
@@ -0,0 +1,27 @@
+/// The CRC16 used by the BIOS, firmware and cartridge headers (reflected polynomial 0xA001).
+pub fn crc16(init: u16, data: &[u8]) -> u16 {
+    let mut crc = init;
+    for &byte in data {
+        crc ^= byte as u16;
+        for _ in 0..8 {
+            crc = if crc & 0b1 != 0 {
+                (crc >> 1) ^ 0xA001
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_values() {
+        // CRC-16/MODBUS and CRC-16/ARC, which differ only in the initial value.
+        assert_eq!(crc16(0xFFFF, b"123456789"), 0x4B37);
+        assert_eq!(crc16(0, b"123456789"), 0xBB3D);
+    }
+}
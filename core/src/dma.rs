@@ -0,0 +1,273 @@
+use crate::bus::{arm7, arm9};
+use crate::irq::{self, Interrupt};
+use crate::{Core, Engine};
+
+/// Events a DMA channel can wait for before transferring.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StartMode {
+    Immediate,
+    VBlank,
+    HBlank,
+    DisplayStart,
+    MainMemoryDisplay,
+    DsCartridge,
+    GbaSlot,
+    GeometryFifo,
+    /// ARM7 channel 0 and 2 only.
+    Wifi,
+}
+
+impl StartMode {
+    fn decode<const ARM9: bool>(index: usize, cnt: u32) -> Self {
+        if ARM9 {
+            match (cnt >> 27) & 0b111 {
+                0 => Self::Immediate,
+                1 => Self::VBlank,
+                2 => Self::HBlank,
+                3 => Self::DisplayStart,
+                4 => Self::MainMemoryDisplay,
+                5 => Self::DsCartridge,
+                6 => Self::GbaSlot,
+                7 => Self::GeometryFifo,
+                _ => unreachable!(),
+            }
+        } else {
+            match (cnt >> 28) & 0b11 {
+                0 => Self::Immediate,
+                1 => Self::VBlank,
+                2 => Self::DsCartridge,
+                3 if index & 0b1 == 0 => Self::Wifi,
+                3 => Self::GbaSlot,
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    /// Units transferred every time the event happens, the whole count if `None`.
+    fn units_per_trigger(self) -> Option<u32> {
+        match self {
+            Self::GeometryFifo => Some(112),
+            Self::MainMemoryDisplay => Some(4),
+            // a word every time the gamecard has one ready.
+            Self::DsCartridge => Some(1),
+            _ => None,
+        }
+    }
+}
+
+/// Registers and internal state of one channel (DMAxSAD/DAD/CNT).
+#[derive(Default, Clone, Copy)]
+struct Channel {
+    sad: u32,
+    dad: u32,
+    cnt: u32,
+    /// Internal source, destination and remaining units latched on enable.
+    src: u32,
+    dst: u32,
+    count: u32,
+}
+
+impl Channel {
+    #[inline]
+    fn enabled(&self) -> bool {
+        get_bit!(self.cnt, 31)
+    }
+
+    #[inline]
+    fn dst_ctrl(&self) -> u32 {
+        (self.cnt >> 21) & 0b11
+    }
+
+    #[inline]
+    fn src_ctrl(&self) -> u32 {
+        (self.cnt >> 23) & 0b11
+    }
+
+    #[inline]
+    fn repeat(&self) -> bool {
+        get_bit!(self.cnt, 25)
+    }
+
+    #[inline]
+    fn word(&self) -> bool {
+        get_bit!(self.cnt, 26)
+    }
+
+    #[inline]
+    fn irq(&self) -> bool {
+        get_bit!(self.cnt, 30)
+    }
+}
+
+#[derive(Default)]
+pub struct Dma {
+    channels: [Channel; 4],
+    /// DMAxFILL, ARM9 only.
+    fill: [u32; 4],
+}
+
+impl Dma {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+fn dma<E: Engine, const ARM9: bool>(core: &mut Core<E>) -> &mut Dma {
+    if ARM9 {
+        &mut core.arm9.dma
+    } else {
+        &mut core.arm7.dma
+    }
+}
+
+fn src_mask<const ARM9: bool>(index: usize) -> u32 {
+    if ARM9 || index != 0 {
+        0x0FFFFFFF
+    } else {
+        0x07FFFFFF
+    }
+}
+
+fn dst_mask<const ARM9: bool>(index: usize) -> u32 {
+    if ARM9 || index == 3 {
+        0x0FFFFFFF
+    } else {
+        0x07FFFFFF
+    }
+}
+
+/// Units to transfer, a count of zero is the maximum.
+fn word_count<const ARM9: bool>(index: usize, cnt: u32) -> u32 {
+    let mask = if ARM9 {
+        0x1FFFFF
+    } else if index == 3 {
+        0xFFFF
+    } else {
+        0x3FFF
+    };
+    match cnt & mask {
+        0 => mask + 1,
+        count => count,
+    }
+}
+
+pub fn sad<E: Engine, const ARM9: bool>(core: &mut Core<E>, index: usize) -> u32 {
+    dma::<E, ARM9>(core).channels[index].sad
+}
+
+pub fn sad_set<E: Engine, const ARM9: bool>(core: &mut Core<E>, index: usize, val: u32) {
+    dma::<E, ARM9>(core).channels[index].sad = val & src_mask::<ARM9>(index);
+}
+
+pub fn dad<E: Engine, const ARM9: bool>(core: &mut Core<E>, index: usize) -> u32 {
+    dma::<E, ARM9>(core).channels[index].dad
+}
+
+pub fn dad_set<E: Engine, const ARM9: bool>(core: &mut Core<E>, index: usize, val: u32) {
+    dma::<E, ARM9>(core).channels[index].dad = val & dst_mask::<ARM9>(index);
+}
+
+pub fn cnt<E: Engine, const ARM9: bool>(core: &mut Core<E>, index: usize) -> u32 {
+    dma::<E, ARM9>(core).channels[index].cnt
+}
+
+pub fn cnt_set<E: Engine, const ARM9: bool>(core: &mut Core<E>, index: usize, val: u32) {
+    let channel = &mut dma::<E, ARM9>(core).channels[index];
+    let was_enabled = channel.enabled();
+    channel.cnt = val;
+    if channel.enabled() && !was_enabled {
+        channel.src = channel.sad;
+        channel.dst = channel.dad;
+        channel.count = word_count::<ARM9>(index, val);
+        if StartMode::decode::<ARM9>(index, val) == StartMode::Immediate {
+            transfer::<E, ARM9>(core, index);
+        }
+    }
+}
+
+pub fn fill(core: &mut Core<impl Engine>, index: usize) -> u32 {
+    core.arm9.dma.fill[index]
+}
+
+pub fn fill_set(core: &mut Core<impl Engine>, index: usize, val: u32) {
+    core.arm9.dma.fill[index] = val;
+}
+
+/// Start the enabled channels waiting for `mode`, called by the peripherals when the event
+/// happens.
+pub fn trigger<E: Engine, const ARM9: bool>(core: &mut Core<E>, mode: StartMode) {
+    for index in 0..4 {
+        let channel = dma::<E, ARM9>(core).channels[index];
+        if channel.enabled() && StartMode::decode::<ARM9>(index, channel.cnt) == mode {
+            transfer::<E, ARM9>(core, index);
+        }
+    }
+}
+
+fn transfer<E: Engine, const ARM9: bool>(core: &mut Core<E>, index: usize) {
+    let channel = dma::<E, ARM9>(core).channels[index];
+    let mode = StartMode::decode::<ARM9>(index, channel.cnt);
+    let units = match mode.units_per_trigger() {
+        Some(units) => units.min(channel.count),
+        None => channel.count,
+    };
+
+    fn step(adr: u32, ctrl: u32, size: u32) -> u32 {
+        match ctrl {
+            0 | 3 => adr.wrapping_add(size),
+            1 => adr.wrapping_sub(size),
+            _ => adr,
+        }
+    }
+
+    let (mut src, mut dst) = (channel.src, channel.dst);
+    if channel.word() {
+        for _ in 0..units {
+            let val = if ARM9 {
+                arm9::read32(core, src & !0b11)
+            } else {
+                arm7::read32(core, src & !0b11)
+            };
+            if ARM9 {
+                arm9::write32(core, dst & !0b11, val)
+            } else {
+                arm7::write32(core, dst & !0b11, val)
+            }
+            src = step(src, channel.src_ctrl(), 4);
+            dst = step(dst, channel.dst_ctrl(), 4);
+        }
+    } else {
+        for _ in 0..units {
+            let val = if ARM9 {
+                arm9::read16(core, src & !0b1)
+            } else {
+                arm7::read16(core, src & !0b1)
+            };
+            if ARM9 {
+                arm9::write16(core, dst & !0b1, val)
+            } else {
+                arm7::write16(core, dst & !0b1, val)
+            }
+            src = step(src, channel.src_ctrl(), 2);
+            dst = step(dst, channel.dst_ctrl(), 2);
+        }
+    }
+
+    let channel = &mut dma::<E, ARM9>(core).channels[index];
+    channel.src = src;
+    channel.dst = dst;
+    channel.count -= units;
+    if channel.count == 0 {
+        if channel.repeat() && mode != StartMode::Immediate {
+            channel.count = word_count::<ARM9>(index, channel.cnt);
+            if channel.dst_ctrl() == 3 {
+                channel.dst = channel.dad;
+            }
+        } else {
+            unset_bit!(channel.cnt, 31);
+        }
+        if channel.irq() {
+            irq::request::<E, ARM9>(core, Interrupt::dma(index));
+        }
+    }
+}
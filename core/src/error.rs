@@ -3,12 +3,14 @@ use std::fmt::Write;
 #[derive(Debug)]
 pub enum Error {
     Cartridge(String),
+    Audio(String),
 }
 
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Error::Cartridge(err) => f.write_fmt(format_args!("cartridge error: {err}")),
+            Error::Audio(err) => f.write_fmt(format_args!("audio error: {err}")),
         }
     }
 }
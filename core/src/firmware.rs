@@ -0,0 +1,262 @@
+//! Synthesised firmware images, for booting without a dump of the console firmware.
+
+use crate::crc::crc16;
+
+/// Size of the synthesised image, the 256KiB of the original DS.
+pub const FIRMWARE_LEN: usize = kb!(256);
+
+/// Offset of the first of the two user settings blocks, the second follows it.
+pub const USER_SETTINGS_OFFSET: usize = FIRMWARE_LEN - 0x200;
+/// Bytes of a user settings block covered by its CRC, also the part the firmware copies into
+/// main memory.
+pub const USER_SETTINGS_LEN: usize = 0x70;
+
+const WIFI_CONFIG_OFFSET: usize = 0x2A;
+const WIFI_CONFIG_LEN: usize = 0x138;
+const ACCESS_POINTS_OFFSET: usize = FIRMWARE_LEN - 0x600;
+
+/// Language of the firmware menus, stored in the user settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Language {
+    Japanese = 0,
+    #[default]
+    English = 1,
+    French = 2,
+    German = 3,
+    Italian = 4,
+    Spanish = 5,
+    Chinese = 6,
+}
+
+/// Two reference points mapping raw touchscreen ADC values to screen pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TouchCalibration {
+    pub adc1: (u16, u16),
+    pub screen1: (u8, u8),
+    pub adc2: (u16, u16),
+    pub screen2: (u8, u8),
+}
+
+impl Default for TouchCalibration {
+    /// Matches the touchscreen controller, which reports 12 bit values of pixel << 4.
+    fn default() -> Self {
+        Self {
+            adc1: (0x20 << 4, 0x20 << 4),
+            screen1: (0x20, 0x20),
+            adc2: (0xE0 << 4, 0xA0 << 4),
+            screen2: (0xE0, 0xA0),
+        }
+    }
+}
+
+/// User settings written into a synthesised firmware image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FirmwareSettings {
+    /// Truncated to 10 UTF-16 units.
+    pub nickname: String,
+    /// Truncated to 26 UTF-16 units.
+    pub message: String,
+    /// Month (1-12) and day (1-31).
+    pub birthday: (u8, u8),
+    pub language: Language,
+    /// Favourite colour, 0-15.
+    pub colour: u8,
+    pub touch_calibration: TouchCalibration,
+    pub mac_address: [u8; 6],
+}
+
+impl Default for FirmwareSettings {
+    fn default() -> Self {
+        Self {
+            nickname: "vargds".into(),
+            message: String::new(),
+            birthday: (1, 1),
+            language: Language::default(),
+            colour: 0,
+            touch_calibration: TouchCalibration::default(),
+            mac_address: [0x00, 0x09, 0xBF, 0x12, 0x34, 0x56],
+        }
+    }
+}
+
+/// Build a firmware image holding `settings`. It has no boot code, the core boots cartridges
+/// directly.
+pub fn build(settings: &FirmwareSettings) -> Vec<u8> {
+    let mut image = vec![0xFF; FIRMWARE_LEN];
+    write_header(&mut image);
+    write_wifi_config(&mut image, settings);
+    write_access_points(&mut image);
+    let block = user_settings(settings);
+    // the firmware picks the copy with the higher update counter.
+    for (i, offs) in [USER_SETTINGS_OFFSET, USER_SETTINGS_OFFSET + 0x100]
+        .into_iter()
+        .enumerate()
+    {
+        let mut block = block;
+        write16(&mut block, 0x70, i as u16);
+        let crc = crc16(0xFFFF, &block[..USER_SETTINGS_LEN]);
+        write16(&mut block, 0x72, crc);
+        image[offs..offs + 0x100].copy_from_slice(&block);
+    }
+    image
+}
+
+/// The part of the newest valid user settings block of `image` the firmware copies into main
+/// memory, if there is one.
+pub fn user_settings_of(image: &[u8]) -> Option<&[u8]> {
+    let offs = read16(image, 0x20)? as usize * 8;
+    let blocks = [offs, offs + 0x100].map(|offs| {
+        let block = image.get(offs..offs + 0x100)?;
+        let crc = crc16(0xFFFF, &block[..USER_SETTINGS_LEN]);
+        (read16(block, 0x72)? == crc).then_some(block)
+    });
+    let newest = match blocks {
+        [Some(a), Some(b)] => {
+            let count = |block: &[u8]| read16(block, 0x70).unwrap_or_default() & 0x7F;
+            if (count(b).wrapping_sub(count(a)) & 0x7F) == 1 {
+                b
+            } else {
+                a
+            }
+        }
+        [Some(block), None] | [None, Some(block)] => block,
+        [None, None] => return None,
+    };
+    Some(&newest[..USER_SETTINGS_LEN])
+}
+
+fn write_header(image: &mut [u8]) {
+    // boot code addresses and CRCs, there is no boot code.
+    image[..0x08].fill(0);
+    image[0x08..0x0C].copy_from_slice(b"MACP");
+    image[0x0C..0x1D].fill(0);
+    // console type, original DS.
+    image[0x1D] = 0xFF;
+    write16(image, 0x20, (USER_SETTINGS_OFFSET / 8) as u16);
+    write16(image, 0x22, 0);
+    write16(image, 0x24, 0);
+    write16(image, 0x26, 0);
+}
+
+fn write_wifi_config(image: &mut [u8], settings: &FirmwareSettings) {
+    let config = &mut image[WIFI_CONFIG_OFFSET..WIFI_CONFIG_OFFSET + 2 + WIFI_CONFIG_LEN];
+    write16(config, 0x02, WIFI_CONFIG_LEN as u16);
+    config[0x04] = 0;
+    // version.
+    config[0x05] = 0;
+    config[0x0C..0x12].copy_from_slice(&settings.mac_address);
+    // channels 1-13 enabled.
+    write16(config, 0x12, 0x3FFE);
+    // RF chip type 2 with 3 byte entries, no entries as the RF registers are stubbed.
+    config[0x16] = 2;
+    config[0x17] = 24;
+    config[0x18] = 0;
+    config[0x19] = 1;
+    config[0x1A..0x3A].fill(0);
+    let crc = crc16(0, &config[0x02..]);
+    write16(config, 0x00, crc);
+}
+
+/// Three unconfigured access points.
+fn write_access_points(image: &mut [u8]) {
+    for i in 0..3 {
+        let offs = ACCESS_POINTS_OFFSET + i * 0x100;
+        let ap = &mut image[offs..offs + 0x100];
+        ap.fill(0);
+        // status, not configured.
+        ap[0xE7] = 0xFF;
+        let crc = crc16(0, &ap[..0xFE]);
+        write16(ap, 0xFE, crc);
+    }
+}
+
+/// A user settings block without update counter and CRC.
+fn user_settings(settings: &FirmwareSettings) -> [u8; 0x100] {
+    let mut block = [0; 0x100];
+    // version.
+    write16(&mut block, 0x00, 5);
+    block[0x02] = settings.colour & 0xF;
+    block[0x03] = settings.birthday.0;
+    block[0x04] = settings.birthday.1;
+    let nickname_len = write_utf16(&mut block[0x06..0x1A], &settings.nickname);
+    write16(&mut block, 0x1A, nickname_len as u16);
+    let message_len = write_utf16(&mut block[0x1C..0x50], &settings.message);
+    write16(&mut block, 0x50, message_len as u16);
+
+    let cal = settings.touch_calibration;
+    write16(&mut block, 0x58, cal.adc1.0);
+    write16(&mut block, 0x5A, cal.adc1.1);
+    block[0x5C] = cal.screen1.0;
+    block[0x5D] = cal.screen1.1;
+    write16(&mut block, 0x5E, cal.adc2.0);
+    write16(&mut block, 0x60, cal.adc2.1);
+    block[0x62] = cal.screen2.0;
+    block[0x63] = cal.screen2.1;
+
+    // language and the flags marking the settings as done, upper screen for GBA mode.
+    write16(&mut block, 0x64, settings.language as u16 | 0xFC00);
+    block[0x66] = 0;
+    block[0x67] = 0x01;
+    block[0x6C..0x70].fill(0xFF);
+    block[0x74..].fill(0xFF);
+    block
+}
+
+/// Write `s` as UTF-16 into `dst`, returning the units written.
+fn write_utf16(dst: &mut [u8], s: &str) -> usize {
+    let mut len = 0;
+    for (unit, chunk) in s.encode_utf16().zip(dst.chunks_exact_mut(2)) {
+        chunk.copy_from_slice(&unit.to_le_bytes());
+        len += 1;
+    }
+    len
+}
+
+fn write16(buf: &mut [u8], offs: usize, val: u16) {
+    buf[offs..offs + 2].copy_from_slice(&val.to_le_bytes());
+}
+
+fn read16(buf: &[u8], offs: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        buf.get(offs..offs + 2)?.try_into().ok()?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocks_have_valid_crcs() {
+        let image = build(&FirmwareSettings::default());
+        let config = &image[WIFI_CONFIG_OFFSET..WIFI_CONFIG_OFFSET + 2 + WIFI_CONFIG_LEN];
+        assert_eq!(read16(config, 0x00), Some(crc16(0, &config[0x02..])));
+        for i in 0..3 {
+            let ap = &image[ACCESS_POINTS_OFFSET + i * 0x100..][..0x100];
+            assert_eq!(read16(ap, 0xFE), Some(crc16(0, &ap[..0xFE])));
+        }
+        for offs in [USER_SETTINGS_OFFSET, USER_SETTINGS_OFFSET + 0x100] {
+            let block = &image[offs..offs + 0x100];
+            let crc = crc16(0xFFFF, &block[..USER_SETTINGS_LEN]);
+            assert_eq!(read16(block, 0x72), Some(crc));
+        }
+    }
+
+    #[test]
+    fn newest_valid_user_settings_are_used() {
+        let mut image = build(&FirmwareSettings::default());
+        let newest = USER_SETTINGS_OFFSET + 0x100;
+        assert_eq!(
+            user_settings_of(&image),
+            Some(&image[newest..newest + USER_SETTINGS_LEN])
+        );
+
+        image[newest + 0x72] ^= 0xFF;
+        assert_eq!(
+            user_settings_of(&image),
+            Some(&image[USER_SETTINGS_OFFSET..USER_SETTINGS_OFFSET + USER_SETTINGS_LEN])
+        );
+        image[USER_SETTINGS_OFFSET + 0x72] ^= 0xFF;
+        assert_eq!(user_settings_of(&image), None);
+    }
+}
@@ -0,0 +1,703 @@
+//! The gamecard interface (AUXSPICNT/ROMCTRL/card commands at 0x040001A0 and the data port at
+//! 0x04100010), shared by the CPUs through EXMEMCNT.
+
+mod save;
+mod savedb;
+use save::SaveChip;
+pub use save::{SaveFile, SaveType};
+
+use crate::cartridge::{self, Key1, Key2, VirtualRom, SECURE_AREA_END, SECURE_AREA_START};
+use crate::dma::{self, StartMode};
+use crate::irq::{self, Interrupt};
+use crate::scheduler::{Event, Timestamp, BUS_CYCLE};
+use crate::spi::SpiDevice;
+use crate::{Core, Engine};
+
+/// Protocol mode of the card, changed by the mode commands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Unencrypted commands after a reset.
+    Raw,
+    /// Commands encrypted with KEY1, used to read the secure area.
+    Key1,
+    /// The main data mode, commands and data are encrypted with KEY2 by the hardware.
+    Key2,
+}
+
+pub struct Gamecard {
+    rom: Box<[u8]>,
+    chip_id: u32,
+    mode: Mode,
+    /// EXMEMCNT of the ARM9, bit 11 gives the slot to the ARM7.
+    exmemcnt: u16,
+    /// Bits 0-6 of EXMEMCNT of the ARM7, the rest is read from the ARM9 one.
+    exmemcnt7: u8,
+    auxspicnt: u16,
+    auxspidata: u8,
+    aux_busy_until: Timestamp,
+    /// The backup chip on the AUXSPI bus.
+    save: SaveChip,
+    romctrl: u32,
+    command: [u8; 8],
+    /// KEY2 seeds (0x040001B0-0x040001BB), 39 bits each.
+    seeds: [u64; 2],
+    /// KEY1 of the commands in KEY1 mode, if a KEY1 table was supplied.
+    key1: Option<Key1>,
+    /// The secure area encrypted as the card sends it in KEY1 mode.
+    secure_area: Option<Box<[u8]>>,
+    /// Replacement files served on top of the ROM.
+    overlay: Option<VirtualRom>,
+    /// KEY2 of the transfers, applied by the hardware and the card alike once the card enabled
+    /// it.
+    key2: Key2,
+    key2_enabled: bool,
+    /// Response to the current command and the bytes of it already read.
+    response: Vec<u8>,
+    pos: usize,
+}
+
+impl Gamecard {
+    pub fn new() -> Self {
+        Self {
+            rom: Box::new([]),
+            chip_id: 0,
+            mode: Mode::Raw,
+            exmemcnt: 0x2000,
+            exmemcnt7: 0,
+            auxspicnt: 0,
+            auxspidata: 0,
+            aux_busy_until: 0,
+            save: SaveChip::None,
+            romctrl: 0,
+            command: [0; 8],
+            seeds: [0; 2],
+            key1: None,
+            secure_area: None,
+            overlay: None,
+            key2: Key2::new(0, 0),
+            key2_enabled: false,
+            response: Vec::new(),
+            pos: 0,
+        }
+    }
+
+    /// Chip ID of a card with a ROM of `rom_len` bytes, made by Macronix with the size in
+    /// MiB - 1.
+    pub fn chip_id_for(rom_len: usize) -> u32 {
+        let size_mib = rom_len.next_power_of_two() >> 20;
+        0xC2 | (size_mib.saturating_sub(1) as u32) << 8
+    }
+
+    /// Insert the card with `rom`, which has a decrypted secure area, in the main data mode the
+    /// firmware leaves it in. Without a KEY1 `table` the card can't be booted from KEY1 mode.
+    pub fn insert(&mut self, rom: Box<[u8]>, table: Option<&[u8]>, overlay: Option<VirtualRom>) {
+        self.chip_id = Self::chip_id_for(rom.len());
+        if let Ok(cartridge) = cartridge::Cartridge::new(&rom) {
+            let header = cartridge.header();
+            self.seeds = Key2::default_seeds(header.key2_seed_select());
+            if let Some(table) = table {
+                self.key1 = Some(Key1::new(table, header.game_code(), 2, 8));
+                let mut encrypted = rom.to_vec();
+                if cartridge::encrypt_secure_area(&mut encrypted, table).is_ok() {
+                    self.secure_area = Some(encrypted[SECURE_AREA_START..SECURE_AREA_END].into());
+                }
+            }
+        }
+        self.key2 = Key2::new(self.seeds[0], self.seeds[1]);
+        self.key2_enabled = true;
+        self.rom = rom;
+        self.overlay = overlay;
+        self.mode = Mode::Key2;
+    }
+
+    /// Put the card back in the state it powers on in, with unencrypted commands, KEY2 disabled
+    /// and RESB in ROMCTRL cleared, as the firmware finds it when it boots the card.
+    pub fn reset(&mut self) {
+        self.mode = Mode::Raw;
+        self.key2_enabled = false;
+        self.romctrl = 0;
+        self.response.clear();
+        self.pos = 0;
+    }
+
+    pub fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    #[inline]
+    pub fn chip_id(&self) -> u32 {
+        self.chip_id
+    }
+
+    #[inline]
+    fn arm9_owns_slot(&self) -> bool {
+        !get_bit!(self.exmemcnt, 11)
+    }
+
+    /// The AUXSPI bus is enabled and connected to the backup chip.
+    #[inline]
+    fn aux_spi_enabled(&self) -> bool {
+        get_bit!(self.auxspicnt, 15) && get_bit!(self.auxspicnt, 13)
+    }
+
+    #[inline]
+    fn irq_enabled(&self) -> bool {
+        get_bit!(self.auxspicnt, 14)
+    }
+
+    #[inline]
+    fn busy(&self) -> bool {
+        get_bit!(self.romctrl, 31)
+    }
+
+    /// Bus cycles per transferred byte, at 6.7MHz or 4.2MHz.
+    #[inline]
+    fn byte_cycles(&self) -> Timestamp {
+        if get_bit!(self.romctrl, 27) {
+            8
+        } else {
+            5
+        }
+    }
+
+    /// Bytes transferred for a command, set by ROMCTRL.
+    fn block_len(&self) -> usize {
+        match (self.romctrl >> 24) & 0b111 {
+            0 => 0,
+            7 => 4,
+            n => 0x100 << n,
+        }
+    }
+
+    /// Read `len` bytes at `adr` of the ROM, which is mirrored to a power of two and reads 0xFF
+    /// past its end.
+    fn read_rom(&self, adr: u32, len: usize) -> Vec<u8> {
+        let rom_len = match &self.overlay {
+            Some(overlay) => overlay.len(),
+            None => self.rom.len(),
+        };
+        let mask = rom_len.next_power_of_two().max(1) - 1;
+        (0..len)
+            .map(|i| {
+                // reads wrap around in 4KiB pages.
+                let adr = (adr as usize & !0xFFF) | ((adr as usize + i) & 0xFFF);
+                let adr = adr & mask;
+                match &self.overlay {
+                    Some(overlay) => overlay.read(&self.rom, adr),
+                    None => self.rom.get(adr).copied(),
+                }
+                .unwrap_or(0xFF)
+            })
+            .collect()
+    }
+
+    fn chip_id_response(&self, len: usize) -> Vec<u8> {
+        self.chip_id
+            .to_le_bytes()
+            .into_iter()
+            .cycle()
+            .take(len)
+            .collect()
+    }
+
+    /// The response of `len` bytes to the current command, `None` for unknown commands.
+    fn respond(&mut self, len: usize) -> Option<Vec<u8>> {
+        let cmd = self.command;
+        Some(match self.mode {
+            Mode::Raw => match cmd[0] {
+                0x9F => vec![0xFF; len],
+                // the header, repeated every 0x200 bytes.
+                0x00 => (0..len)
+                    .map(|i| self.rom.get(i & 0x1FF).copied().unwrap_or(0xFF))
+                    .collect(),
+                0x90 => self.chip_id_response(len),
+                0x3C => {
+                    self.mode = Mode::Key1;
+                    vec![0xFF; len]
+                }
+                _ => return None,
+            },
+            Mode::Key1 => match self
+                .key1
+                .as_ref()
+                .map_or(cmd, |key1| key1.decrypt_command(cmd))
+            {
+                cmd if cmd[0] >> 4 == 0x1 => self.chip_id_response(len),
+                // "2bbbbiiijjjkkkkkh", the secure area block is bbbb.
+                cmd if cmd[0] >> 4 == 0x2 => {
+                    let adr = ((u64::from_be_bytes(cmd) >> 44 & 0xFFFF) << 12) as usize;
+                    match &self.secure_area {
+                        Some(area) if (SECURE_AREA_START..SECURE_AREA_END).contains(&adr) => {
+                            let offs = adr - SECURE_AREA_START;
+                            (0..len).map(|i| area[(offs + i) & 0x3FFF]).collect()
+                        }
+                        _ => self.read_rom(adr as u32, len),
+                    }
+                }
+                cmd if cmd[0] >> 4 == 0x4 => {
+                    self.key2_enabled = true;
+                    vec![0xFF; len]
+                }
+                cmd if cmd[0] >> 4 == 0xA => {
+                    self.mode = Mode::Key2;
+                    vec![0xFF; len]
+                }
+                _ => return None,
+            },
+            Mode::Key2 => match cmd[0] {
+                0xB7 => {
+                    let adr = u32::from_be_bytes([cmd[1], cmd[2], cmd[3], cmd[4]]);
+                    // the area before 0x8000 can't be read in this mode.
+                    let adr = if adr < 0x8000 {
+                        0x8000 + (adr & 0x1FF)
+                    } else {
+                        adr
+                    };
+                    self.read_rom(adr, len)
+                }
+                0xB8 => self.chip_id_response(len),
+                _ => return None,
+            },
+        })
+    }
+}
+
+impl Default for Gamecard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn exmemcnt<E: Engine, const ARM9: bool>(core: &mut Core<E>) -> u16 {
+    let gamecard = &core.gamecard;
+    if ARM9 {
+        gamecard.exmemcnt
+    } else {
+        (gamecard.exmemcnt & 0xFF80) | gamecard.exmemcnt7 as u16
+    }
+}
+
+pub fn exmemcnt_set<E: Engine, const ARM9: bool>(core: &mut Core<E>, val: u16) {
+    let gamecard = &mut core.gamecard;
+    if ARM9 {
+        // bit 13 is always set.
+        gamecard.exmemcnt = (val & 0xE8FF) | 0x2000;
+    } else {
+        gamecard.exmemcnt7 = val as u8 & 0x7F;
+    }
+}
+
+/// The slot registers read as 0 for the CPU that doesn't own it.
+#[inline]
+fn owns_slot<E: Engine, const ARM9: bool>(core: &Core<E>) -> bool {
+    core.gamecard.arm9_owns_slot() == ARM9
+}
+
+pub fn auxspicnt<E: Engine, const ARM9: bool>(core: &mut Core<E>) -> u16 {
+    if !owns_slot::<E, ARM9>(core) {
+        return 0;
+    }
+    let gamecard = &core.gamecard;
+    let mut cnt = gamecard.auxspicnt;
+    toggle_bit!(cnt, 7, core.scheduler.now() < gamecard.aux_busy_until);
+    cnt
+}
+
+pub fn auxspicnt_set<E: Engine, const ARM9: bool>(core: &mut Core<E>, val: u16) {
+    if !owns_slot::<E, ARM9>(core) {
+        return;
+    }
+    let gamecard = &mut core.gamecard;
+    gamecard.auxspicnt = val & 0xE043;
+    if !gamecard.aux_spi_enabled() {
+        gamecard.save.deselect();
+    }
+}
+
+pub fn auxspidata<E: Engine, const ARM9: bool>(core: &mut Core<E>) -> u8 {
+    if owns_slot::<E, ARM9>(core) {
+        core.gamecard.auxspidata
+    } else {
+        0
+    }
+}
+
+/// Writing AUXSPIDATA transfers a byte with the backup chip.
+pub fn auxspidata_set<E: Engine, const ARM9: bool>(core: &mut Core<E>, val: u8) {
+    let now = core.scheduler.now();
+    if !owns_slot::<E, ARM9>(core) {
+        return;
+    }
+    let gamecard = &mut core.gamecard;
+    if !gamecard.aux_spi_enabled() {
+        return;
+    }
+    gamecard.auxspidata = gamecard.save.transfer(val);
+    // chip select is released after the transfer unless it's held.
+    if !get_bit!(gamecard.auxspicnt, 6) {
+        let detecting = matches!(gamecard.save, SaveChip::Detecting(_));
+        gamecard.save.deselect();
+        let save_type = gamecard.save.save_type();
+        if detecting && save_type != SaveType::None {
+            info!(core.logger, "detected the save type {save_type}");
+        }
+    }
+
+    // 8 bits at 4MHz >> baudrate.
+    let gamecard = &mut core.gamecard;
+    let bit_cycles = (8 << (gamecard.auxspicnt & 0b11)) * BUS_CYCLE;
+    gamecard.aux_busy_until = now + 8 * bit_cycles;
+}
+
+pub fn romctrl<E: Engine, const ARM9: bool>(core: &mut Core<E>) -> u32 {
+    if owns_slot::<E, ARM9>(core) {
+        core.gamecard.romctrl
+    } else {
+        0
+    }
+}
+
+pub fn romctrl_set<E: Engine, const ARM9: bool>(core: &mut Core<E>, val: u32) {
+    if !owns_slot::<E, ARM9>(core) {
+        return;
+    }
+    let gamecard = &mut core.gamecard;
+    let was_busy = gamecard.busy();
+    // bit 23 is read only, RESB (bit 29) can't be cleared again and bit 15 isn't kept.
+    let keep = gamecard.romctrl & (b!(23) | b!(29));
+    gamecard.romctrl = (val & !(b!(15) | b!(23))) | keep;
+    if get_bit!(val, 15) {
+        gamecard.key2 = Key2::new(gamecard.seeds[0], gamecard.seeds[1]);
+    }
+    if get_bit!(gamecard.auxspicnt, 15) && gamecard.busy() && !was_busy {
+        start(core);
+    }
+}
+
+/// Write the bytes of the command in `mask`, `word` is 0 for 0x040001A8 and 1 for 0x040001AC.
+pub fn command_set<E: Engine, const ARM9: bool>(
+    core: &mut Core<E>,
+    word: usize,
+    val: u32,
+    mask: u32,
+) {
+    if !owns_slot::<E, ARM9>(core) {
+        return;
+    }
+    let command = &mut core.gamecard.command[word * 4..word * 4 + 4];
+    let old = u32::from_le_bytes(command.try_into().unwrap());
+    command.copy_from_slice(&((old & !mask) | (val & mask)).to_le_bytes());
+}
+
+/// Write the bits in `mask` of the KEY2 seed registers at `adr`, 0x040001B0..=0x040001B8.
+pub fn seed_set<E: Engine, const ARM9: bool>(core: &mut Core<E>, adr: u32, val: u32, mask: u32) {
+    if !owns_slot::<E, ARM9>(core) {
+        return;
+    }
+    let seeds = &mut core.gamecard.seeds;
+    let masked = |old: u64, val: u64, mask: u64| (old & !mask) | (val & mask);
+    match adr {
+        0x040001B0 => seeds[0] = masked(seeds[0], val as u64, mask as u64),
+        0x040001B4 => seeds[1] = masked(seeds[1], val as u64, mask as u64),
+        _ => {
+            let high = 0x7F << 32;
+            seeds[0] = masked(seeds[0], (val as u64) << 32, (mask as u64) << 32 & high);
+            seeds[1] = masked(
+                seeds[1],
+                (val as u64 >> 16) << 32,
+                (mask as u64 >> 16) << 32 & high,
+            );
+        }
+    }
+}
+
+/// Start the transfer of the current command, its response comes in a word at a time.
+fn start<E: Engine>(core: &mut Core<E>) {
+    let now = core.scheduler.now();
+    let len = core.gamecard.block_len();
+    let response = match core.gamecard.respond(len) {
+        Some(response) => response,
+        None => {
+            let command = core.gamecard.command;
+            warn!(core.logger, "unhandled gamecard command {command:02X?}");
+            vec![0xFF; len]
+        }
+    };
+    let gamecard = &mut core.gamecard;
+    gamecard.response = response;
+    gamecard.pos = 0;
+    // the command is encrypted by the hardware and decrypted by the card.
+    if get_bit!(gamecard.romctrl, 22) {
+        for byte in gamecard.command {
+            gamecard.key2.apply(byte);
+        }
+    }
+    unset_bit!(gamecard.romctrl, 23);
+
+    // the 8 command bytes and the KEY1 gap come before the first word.
+    let gap1 = (gamecard.romctrl & 0x1FFF) as Timestamp;
+    let bytes = if len == 0 { 8 + gap1 } else { 8 + gap1 + 4 };
+    let at = now + bytes * gamecard.byte_cycles() * BUS_CYCLE;
+    core.scheduler.schedule(at, Event::GamecardWord);
+}
+
+/// Called by the scheduler when the next word of the response arrived.
+pub fn word_ready<E: Engine>(core: &mut Core<E>) {
+    let gamecard = &mut core.gamecard;
+    // the card was reset during the transfer.
+    if !gamecard.busy() {
+        return;
+    }
+    if gamecard.pos >= gamecard.response.len() {
+        finish(core);
+        return;
+    }
+    set_bit!(gamecard.romctrl, 23);
+    if gamecard.arm9_owns_slot() {
+        dma::trigger::<E, true>(core, StartMode::DsCartridge);
+    } else {
+        dma::trigger::<E, false>(core, StartMode::DsCartridge);
+    }
+}
+
+fn finish<E: Engine>(core: &mut Core<E>) {
+    let gamecard = &mut core.gamecard;
+    unset_bit!(gamecard.romctrl, 31);
+    unset_bit!(gamecard.romctrl, 23);
+    if gamecard.irq_enabled() {
+        if gamecard.arm9_owns_slot() {
+            irq::request::<E, true>(core, Interrupt::CartTransferComplete);
+        } else {
+            irq::request::<E, false>(core, Interrupt::CartTransferComplete);
+        }
+    }
+}
+
+/// Read the data port at 0x04100010, taking the word that is ready.
+pub fn data<E: Engine, const ARM9: bool>(core: &mut Core<E>) -> u32 {
+    let now = core.scheduler.now();
+    if !owns_slot::<E, ARM9>(core) {
+        return 0;
+    }
+    let gamecard = &mut core.gamecard;
+    if !get_bit!(gamecard.romctrl, 23) {
+        return 0;
+    }
+    unset_bit!(gamecard.romctrl, 23);
+    let pos = gamecard.pos;
+    let mut bytes: [u8; 4] = gamecard.response[pos..pos + 4].try_into().unwrap();
+    gamecard.pos += 4;
+    // the card encrypts the data with KEY2 and the hardware decrypts it if enabled.
+    if gamecard.key2_enabled {
+        for byte in &mut bytes {
+            let encrypted = gamecard.key2.apply(*byte);
+            if !get_bit!(gamecard.romctrl, 13) {
+                *byte = encrypted;
+            }
+        }
+    }
+    let word = u32::from_le_bytes(bytes);
+
+    if gamecard.pos >= gamecard.response.len() {
+        finish(core);
+    } else {
+        // the KEY1 gap 2 is inserted every 0x200 bytes.
+        let gap2 = if gamecard.pos & 0x1FF == 0 {
+            ((gamecard.romctrl >> 16) & 0x3F) as Timestamp
+        } else {
+            0
+        };
+        let at = now + (4 + gap2) * gamecard.byte_cycles() * BUS_CYCLE;
+        core.scheduler.schedule(at, Event::GamecardWord);
+    }
+    word
+}
+
+/// Data port reads of the debugger don't take the word.
+pub fn data_peek<E: Engine, const ARM9: bool>(core: &mut Core<E>) -> u32 {
+    let gamecard = &core.gamecard;
+    if !owns_slot::<E, ARM9>(core) || !get_bit!(gamecard.romctrl, 23) {
+        return 0;
+    }
+    let pos = gamecard.pos;
+    u32::from_le_bytes(gamecard.response[pos..pos + 4].try_into().unwrap())
+}
+
+impl<E: Engine> Core<E> {
+    /// Reset the card as if it was reinserted, for software that boots it on its own like the
+    /// firmware. Loading a ROM leaves it in the main data mode instead.
+    pub fn reset_gamecard(&mut self) {
+        self.gamecard.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::arm9 as bus;
+    use crate::{scheduler, Interpreter};
+
+    const AUXSPICNT: u32 = 0x040001A0;
+    const ROMCTRL: u32 = 0x040001A4;
+    const COMMAND: u32 = 0x040001A8;
+    const DATA: u32 = 0x04100010;
+
+    fn rom() -> Vec<u8> {
+        (0..0x10000usize).map(|i| (i ^ i >> 8) as u8).collect()
+    }
+
+    /// A core with a reset card, the slot enabled and the transfer completion interrupt on.
+    fn core() -> Core<Interpreter> {
+        let mut core = Core::new(slog::Logger::root(slog::Discard, slog::o!()));
+        core.gamecard.insert(rom().into(), None, None);
+        core.reset_gamecard();
+        bus::write32(&mut core, AUXSPICNT, 0xC000);
+        core
+    }
+
+    /// Handle the events up to `at`.
+    fn advance_to(core: &mut Core<Interpreter>, at: Timestamp) {
+        while let Some(next) = core.scheduler.next_event_at().filter(|&next| next <= at) {
+            core.scheduler.skip_to(next);
+            scheduler::handle_events(core);
+        }
+        core.scheduler.skip_to(at);
+    }
+
+    fn start(core: &mut Core<Interpreter>, cmd: [u8; 8], block_size: u32) {
+        bus::write32(
+            core,
+            COMMAND,
+            u32::from_le_bytes(cmd[..4].try_into().unwrap()),
+        );
+        bus::write32(
+            core,
+            COMMAND + 4,
+            u32::from_le_bytes(cmd[4..].try_into().unwrap()),
+        );
+        bus::write32(core, ROMCTRL, b!(31) | b!(29) | block_size << 24);
+    }
+
+    /// Send `cmd` and read the response by polling ROMCTRL bit 23.
+    fn command(core: &mut Core<Interpreter>, cmd: [u8; 8], block_size: u32) -> Vec<u8> {
+        start(core, cmd, block_size);
+        let mut response = Vec::new();
+        while get_bit!(bus::read32(core, ROMCTRL), 31) {
+            let next = core.scheduler.next_event_at().unwrap();
+            advance_to(core, next);
+            if get_bit!(bus::read32(core, ROMCTRL), 23) {
+                response.extend(bus::read32(core, DATA).to_le_bytes());
+            }
+        }
+        response
+    }
+
+    #[test]
+    fn block_sizes() {
+        let mut gamecard = Gamecard::new();
+        let sizes = (0..8).map(|size| {
+            gamecard.romctrl = size << 24;
+            gamecard.block_len()
+        });
+        assert!(sizes.eq([0, 0x200, 0x400, 0x800, 0x1000, 0x2000, 0x4000, 4]));
+    }
+
+    #[test]
+    fn raw_commands() {
+        let mut core = core();
+        let rom = rom();
+        assert_eq!(
+            command(&mut core, [0x9F, 0, 0, 0, 0, 0, 0, 0], 5),
+            [0xFF; 0x2000]
+        );
+        // the header repeats every 0x200 bytes.
+        let header = command(&mut core, [0; 8], 2);
+        assert_eq!(header[..0x200], rom[..0x200]);
+        assert_eq!(header[0x200..], rom[..0x200]);
+        assert_eq!(
+            command(&mut core, [0x90, 0, 0, 0, 0, 0, 0, 0], 7),
+            [0xC2, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn key1_and_key2_modes() {
+        let mut core = core();
+        let rom = rom();
+        // the KEY1 commands aren't encrypted without a KEY1 table.
+        command(&mut core, [0x3C, 0, 0, 0, 0, 0, 0, 0], 0);
+        assert_eq!(core.gamecard.mode, Mode::Key1);
+        assert_eq!(
+            command(&mut core, [0x10, 0, 0, 0, 0, 0, 0, 0], 7),
+            [0xC2, 0, 0, 0]
+        );
+        command(&mut core, [0xA0, 0, 0, 0, 0, 0, 0, 0], 0);
+        assert_eq!(core.gamecard.mode, Mode::Key2);
+
+        assert_eq!(
+            command(&mut core, [0xB8, 0, 0, 0, 0, 0, 0, 0], 7),
+            [0xC2, 0, 0, 0]
+        );
+        let data = command(&mut core, [0xB7, 0, 0, 0x90, 0x00, 0, 0, 0], 1);
+        assert_eq!(data, rom[0x9000..0x9200]);
+        // reads before 0x8000 are redirected.
+        let data = command(&mut core, [0xB7, 0, 0, 0x10, 0x00, 0, 0, 0], 7);
+        assert_eq!(data, rom[0x8000..0x8004]);
+    }
+
+    #[test]
+    fn transfer_timing_and_irq() {
+        let mut core = core();
+        let t0 = core.scheduler.now();
+        start(&mut core, [0x90, 0, 0, 0, 0, 0, 0, 0], 7);
+        assert_eq!(bus::read32(&mut core, ROMCTRL) & (b!(31) | b!(23)), b!(31));
+
+        // the command and the first word at 5 bus cycles per byte.
+        let ready = t0 + 12 * 5 * BUS_CYCLE;
+        advance_to(&mut core, ready - 1);
+        assert!(!get_bit!(bus::read32(&mut core, ROMCTRL), 23));
+        advance_to(&mut core, ready);
+        assert!(get_bit!(bus::read32(&mut core, ROMCTRL), 23));
+        assert_eq!(
+            core.arm9.irq.if_() & Interrupt::CartTransferComplete.mask(),
+            0
+        );
+
+        assert_eq!(bus::read32(&mut core, DATA), 0xC2);
+        assert_eq!(bus::read32(&mut core, ROMCTRL) & (b!(31) | b!(23)), 0);
+        assert_ne!(
+            core.arm9.irq.if_() & Interrupt::CartTransferComplete.mask(),
+            0
+        );
+    }
+
+    #[test]
+    fn dma_takes_the_words() {
+        let mut core = core();
+        let rom = rom();
+        let dst = 0x0200_0000;
+        // DMA3 from the data port, fixed source, started by the card, 0x80 words.
+        bus::write32(&mut core, 0x040000D4, DATA);
+        bus::write32(&mut core, 0x040000D8, dst);
+        bus::write32(
+            &mut core,
+            0x040000DC,
+            b!(31) | 5 << 27 | b!(26) | 2 << 23 | 0x80,
+        );
+        command(&mut core, [0x3C, 0, 0, 0, 0, 0, 0, 0], 0);
+        command(&mut core, [0xA0, 0, 0, 0, 0, 0, 0, 0], 0);
+        start(&mut core, [0xB7, 0, 0, 0x90, 0x00, 0, 0, 0], 1);
+        while get_bit!(bus::read32(&mut core, ROMCTRL), 31) {
+            let next = core.scheduler.next_event_at().unwrap();
+            advance_to(&mut core, next);
+        }
+        let data: Vec<u8> = (0..0x80)
+            .flat_map(|i| bus::read32(&mut core, dst + i * 4).to_le_bytes())
+            .collect();
+        assert_eq!(data, rom[0x9000..0x9200]);
+        assert_ne!(
+            core.arm9.irq.if_() & Interrupt::CartTransferComplete.mask(),
+            0
+        );
+    }
+}
@@ -0,0 +1,792 @@
+//! The backup chip of the gamecard, on the AUXSPI bus.
+
+use super::savedb;
+use crate::spi::{Flash, SpiDevice};
+use crate::{Core, Engine};
+
+/// Type and size in bytes of the backup chip of a gamecard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SaveType {
+    #[default]
+    None,
+    /// 512B, 8KiB, 64KiB or 128KiB.
+    Eeprom(usize),
+    /// 256KiB to 8MiB.
+    Flash(usize),
+    /// 32KiB.
+    Fram(usize),
+}
+
+impl SaveType {
+    pub fn len(self) -> usize {
+        match self {
+            Self::None => 0,
+            Self::Eeprom(len) | Self::Flash(len) | Self::Fram(len) => len,
+        }
+    }
+
+    /// There's no chip to save to.
+    pub fn is_empty(self) -> bool {
+        self.len() == 0
+    }
+
+    /// The type of a save of `len` bytes, chips of the same size are assumed to be of the more
+    /// common type.
+    pub fn from_len(len: usize) -> Option<Self> {
+        Some(match len {
+            0x200 | 0x2000 | 0x10000 | 0x20000 => Self::Eeprom(len),
+            0x8000 => Self::Fram(len),
+            _ if Self::Flash(len).is_valid() => Self::Flash(len),
+            _ => return None,
+        })
+    }
+
+    /// The sizes the chips come in.
+    pub fn is_valid(self) -> bool {
+        match self {
+            Self::None => true,
+            Self::Eeprom(len) => matches!(len, 0x200 | 0x2000 | 0x10000 | 0x20000),
+            Self::Flash(len) => len.is_power_of_two() && (kb!(256)..=mb!(8)).contains(&len),
+            Self::Fram(len) => len == kb!(32),
+        }
+    }
+}
+
+impl std::fmt::Display for SaveType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::None => f.write_str("none"),
+            Self::Eeprom(len) => f.write_fmt(format_args!("eeprom-{len}")),
+            Self::Flash(len) => f.write_fmt(format_args!("flash-{len}")),
+            Self::Fram(len) => f.write_fmt(format_args!("fram-{len}")),
+        }
+    }
+}
+
+impl std::str::FromStr for SaveType {
+    type Err = String;
+
+    /// Parse "none" or "<eeprom|flash|fram>-<len>", with the length in bytes or with a "k" or "m"
+    /// suffix.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "none" {
+            return Ok(Self::None);
+        }
+        let err = || format!("invalid save type '{s}'");
+        let (kind, len) = s.split_once('-').ok_or_else(err)?;
+        let len = len.to_ascii_lowercase();
+        let len = if let Some(len) = len.strip_suffix('k') {
+            len.parse::<usize>().map(|len| kb!(len))
+        } else if let Some(len) = len.strip_suffix('m') {
+            len.parse::<usize>().map(|len| mb!(len))
+        } else {
+            len.parse::<usize>()
+        }
+        .map_err(|_| err())?;
+        let save_type = match kind {
+            "eeprom" => Self::Eeprom(len),
+            "flash" => Self::Flash(len),
+            "fram" => Self::Fram(len),
+            _ => return Err(err()),
+        };
+        if save_type.is_valid() {
+            Ok(save_type)
+        } else {
+            Err(err())
+        }
+    }
+}
+
+/// Serial EEPROM and FRAM, which share their commands.
+pub struct Eeprom {
+    data: Box<[u8]>,
+    fram: bool,
+    /// Address bytes after the command, 1 for the 512B EEPROM which takes bit 8 in the command.
+    adr_len: usize,
+    /// Writes wrap around within a page, the FRAM has none.
+    page_len: usize,
+    status: u8,
+    cmd: Option<u8>,
+    adr: usize,
+    pos: usize,
+    dirty: bool,
+}
+
+impl Eeprom {
+    const STATUS_WEL: u8 = b!(1);
+    /// Block protect bits.
+    const STATUS_BP: u8 = 0b1100;
+
+    const CMD_WRSR: u8 = 0x01;
+    const CMD_WRITE: u8 = 0x02;
+    const CMD_READ: u8 = 0x03;
+    const CMD_WRDI: u8 = 0x04;
+    const CMD_RDSR: u8 = 0x05;
+    const CMD_WREN: u8 = 0x06;
+    /// The 512B EEPROM accesses its upper half with these.
+    const CMD_WRITE_HI: u8 = 0x0A;
+    const CMD_READ_HI: u8 = 0x0B;
+
+    fn new(data: Box<[u8]>, fram: bool) -> Self {
+        let len = data.len();
+        let adr_len = match len {
+            0x200 => 1,
+            0x20000 => 3,
+            _ => 2,
+        };
+        let page_len = match len {
+            _ if fram => len,
+            0x200 => 0x10,
+            0x2000 => 0x20,
+            0x10000 => 0x80,
+            _ => 0x100,
+        };
+        Self {
+            data,
+            fram,
+            adr_len,
+            page_len,
+            status: 0,
+            cmd: None,
+            adr: 0,
+            pos: 0,
+            dirty: false,
+        }
+    }
+
+    fn write_enabled(&self) -> bool {
+        self.status & Self::STATUS_WEL != 0
+    }
+
+    /// The block protect bits cover the upper quarter, half or all of the memory.
+    fn protected(&self, offs: usize) -> bool {
+        let len = self.data.len();
+        match (self.status & Self::STATUS_BP) >> 2 {
+            0 => false,
+            1 => offs >= len - len / 4,
+            2 => offs >= len / 2,
+            _ => true,
+        }
+    }
+
+    fn write(&mut self, val: u8) {
+        let offs = self.adr & (self.data.len() - 1);
+        if self.write_enabled() && !self.protected(offs) {
+            self.data[offs] = val;
+            self.dirty = true;
+        }
+        let page = self.adr & !(self.page_len - 1);
+        self.adr = page | (self.adr.wrapping_add(1) & (self.page_len - 1));
+    }
+}
+
+impl SpiDevice for Eeprom {
+    fn transfer(&mut self, val: u8) -> u8 {
+        let Some(cmd) = self.cmd else {
+            self.cmd = Some(val);
+            self.pos = 0;
+            self.adr = 0;
+            match val {
+                Self::CMD_WREN => self.status |= Self::STATUS_WEL,
+                Self::CMD_WRDI => self.status &= !Self::STATUS_WEL,
+                Self::CMD_READ_HI | Self::CMD_WRITE_HI if self.adr_len == 1 => self.adr = 0x100,
+                _ => {}
+            }
+            return 0xFF;
+        };
+        self.pos += 1;
+        let pos = self.pos;
+        match cmd {
+            Self::CMD_RDSR => {
+                // the unused bits of the 512B EEPROM read as set.
+                if self.adr_len == 1 {
+                    self.status | 0xF0
+                } else {
+                    self.status
+                }
+            }
+            Self::CMD_WRSR => {
+                if pos == 1 && self.write_enabled() {
+                    self.status = (self.status & !Self::STATUS_BP) | (val & Self::STATUS_BP);
+                }
+                0xFF
+            }
+            Self::CMD_READ | Self::CMD_WRITE | Self::CMD_READ_HI | Self::CMD_WRITE_HI => {
+                let hi = matches!(cmd, Self::CMD_READ_HI | Self::CMD_WRITE_HI);
+                if hi && self.adr_len != 1 {
+                    return 0xFF;
+                }
+                if pos <= self.adr_len {
+                    self.adr |= (val as usize) << ((self.adr_len - pos) * 8);
+                    0xFF
+                } else if matches!(cmd, Self::CMD_READ | Self::CMD_READ_HI) {
+                    let read = self.data[self.adr & (self.data.len() - 1)];
+                    self.adr = self.adr.wrapping_add(1);
+                    read
+                } else {
+                    self.write(val);
+                    0xFF
+                }
+            }
+            _ => 0xFF,
+        }
+    }
+
+    fn deselect(&mut self) {
+        // writes finish when chip select is released, which clears the latch.
+        if let Some(Self::CMD_WRITE | Self::CMD_WRITE_HI | Self::CMD_WRSR) = self.cmd {
+            self.status &= !Self::STATUS_WEL;
+        }
+        self.cmd = None;
+    }
+}
+
+/// Stands in for a backup chip of unknown type until the commands of the game give it away.
+/// Reads are served from the contents of the .sav file under the address width seen so far, the
+/// commands are replayed on the detected chip which starts out with those contents.
+pub struct Detector {
+    /// Contents of the chip once its type is known.
+    data: Vec<u8>,
+    commands: Vec<Vec<u8>>,
+    current: Vec<u8>,
+    write_enabled: bool,
+    /// Address bytes of the reads so far, if they gave it away.
+    adr_len: Option<usize>,
+}
+
+impl Detector {
+    fn new(data: Vec<u8>) -> Self {
+        Self {
+            data,
+            commands: Vec::new(),
+            current: Vec::new(),
+            write_enabled: false,
+            adr_len: None,
+        }
+    }
+
+    /// Address bytes to read with: the ones the reads gave away, else the ones of a chip the size
+    /// of the .sav file.
+    fn provisional_adr_len(&self) -> usize {
+        self.adr_len.unwrap_or(match self.data.len() {
+            1..=0x200 => 1,
+            0x10001.. => 3,
+            _ => 2,
+        })
+    }
+
+    fn transfer(&mut self, val: u8) -> u8 {
+        self.current.push(val);
+        let pos = self.current.len() - 1;
+        let adr_len = self.provisional_adr_len();
+        let (adr_len, hi) = match self.current[0] {
+            // RDSR, the chip is never busy.
+            0x05 if pos > 0 => return (self.write_enabled as u8) << 1,
+            0x03 => (adr_len, false),
+            // the upper half of the 512B EEPROM, else FAST_READ of the FLASH with a dummy byte.
+            0x0B if adr_len == 1 => (1, true),
+            0x0B => (4, false),
+            _ => return 0xFF,
+        };
+        if pos <= adr_len {
+            return 0xFF;
+        }
+        let adr = self.current[1..=adr_len.min(3)]
+            .iter()
+            .fold(0, |adr, &val| adr << 8 | val as usize);
+        let offs = (hi as usize) << 8 | adr;
+        self.data
+            .get(offs + pos - adr_len - 1)
+            .copied()
+            .unwrap_or(0xFF)
+    }
+
+    /// End the current command, returning the type of the chip if it's known now.
+    fn deselect(&mut self) -> Option<SaveType> {
+        let cmd = std::mem::take(&mut self.current);
+        let &first = cmd.first()?;
+        match first {
+            0x06 => self.write_enabled = true,
+            0x04 => self.write_enabled = false,
+            _ => {}
+        }
+        let detected = self.classify(&cmd);
+        self.commands.push(cmd);
+        detected
+    }
+
+    /// The address bytes of a command of `cmd.len()` bytes, if only one of `widths` leaves a
+    /// power of two bytes after the address, as reads and writes of whole blocks do.
+    fn adr_len(cmd: &[u8], widths: impl Iterator<Item = usize>) -> Option<usize> {
+        let len = cmd.len() - 1;
+        let mut widths = widths.filter(|&width| len > width && (len - width).is_power_of_two());
+        let width = widths.next()?;
+        widths.next().is_none().then_some(width)
+    }
+
+    fn classify(&mut self, cmd: &[u8]) -> Option<SaveType> {
+        match cmd[0] {
+            // commands only the FLASH has.
+            0x9F | 0xD8 | 0xDB | 0xB9 | 0xAB => Some(SaveType::Flash(kb!(512))),
+            // reads give away the address width, but only the 512B EEPROM has a single address
+            // byte.
+            0x03 => {
+                let width = Self::adr_len(cmd, 1..=3)?;
+                self.adr_len = Some(width);
+                (width == 1).then_some(SaveType::Eeprom(0x200))
+            }
+            0x0B => match Self::adr_len(cmd, [1, 4].into_iter())? {
+                1 => Some(SaveType::Eeprom(0x200)),
+                _ => Some(SaveType::Flash(kb!(512))),
+            },
+            // writes are usually a whole page, so the bytes after the address are a power of
+            // two, which gives away the address width, else the one of the reads does.
+            0x02 | 0x0A => {
+                let width = Self::adr_len(cmd, 1..=3)
+                    .or(self.adr_len)
+                    .filter(|&width| cmd.len() - 1 > width)?;
+                let data_len = cmd.len() - 1 - width;
+                match width {
+                    1 => Some(SaveType::Eeprom(0x200)),
+                    2 if data_len <= 0x20 => Some(SaveType::Eeprom(kb!(8))),
+                    2 => Some(SaveType::Eeprom(kb!(64))),
+                    // 0x0A with 3 address bytes is the page write of the FLASH.
+                    _ if cmd[0] == 0x0A => Some(SaveType::Flash(kb!(512))),
+                    _ => Some(SaveType::Eeprom(kb!(128))),
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The backup chip of a gamecard.
+pub enum SaveChip {
+    None,
+    Detecting(Detector),
+    Eeprom(Eeprom),
+    Flash(Flash),
+}
+
+impl SaveChip {
+    /// A chip of `save_type` holding `data`, which is cut or filled up with 0xFF to its size.
+    pub fn new(save_type: SaveType, data: &[u8]) -> Self {
+        let mut contents = vec![0xFF; save_type.len()].into_boxed_slice();
+        let len = data.len().min(contents.len());
+        contents[..len].copy_from_slice(&data[..len]);
+        match save_type {
+            SaveType::None => Self::None,
+            SaveType::Eeprom(_) => Self::Eeprom(Eeprom::new(contents, false)),
+            SaveType::Fram(_) => Self::Eeprom(Eeprom::new(contents, true)),
+            SaveType::Flash(len) => {
+                // ST with the size as log2 of 128KiB units.
+                let size = (len / kb!(128)).trailing_zeros() as u8;
+                Self::Flash(Flash::new(contents, [0x20, 0x40, 0x11 + size]))
+            }
+        }
+    }
+
+    pub fn save_type(&self) -> SaveType {
+        match self {
+            Self::None | Self::Detecting(_) => SaveType::None,
+            Self::Eeprom(eeprom) if eeprom.fram => SaveType::Fram(eeprom.data.len()),
+            Self::Eeprom(eeprom) => SaveType::Eeprom(eeprom.data.len()),
+            Self::Flash(flash) => SaveType::Flash(flash.data().len()),
+        }
+    }
+
+    pub fn data(&self) -> &[u8] {
+        match self {
+            Self::None | Self::Detecting(_) => &[],
+            Self::Eeprom(eeprom) => &eeprom.data,
+            Self::Flash(flash) => flash.data(),
+        }
+    }
+
+    /// The contents were changed since the last call.
+    pub fn take_dirty(&mut self) -> bool {
+        match self {
+            Self::None | Self::Detecting(_) => false,
+            Self::Eeprom(eeprom) => std::mem::take(&mut eeprom.dirty),
+            Self::Flash(flash) => flash.take_dirty(),
+        }
+    }
+}
+
+impl SpiDevice for SaveChip {
+    fn transfer(&mut self, val: u8) -> u8 {
+        match self {
+            // nothing drives the data line.
+            Self::None => 0xFF,
+            Self::Detecting(detector) => detector.transfer(val),
+            Self::Eeprom(eeprom) => eeprom.transfer(val),
+            Self::Flash(flash) => flash.transfer(val),
+        }
+    }
+
+    fn deselect(&mut self) {
+        match self {
+            Self::None => {}
+            Self::Detecting(detector) => {
+                if let Some(save_type) = detector.deselect() {
+                    let commands = std::mem::take(&mut detector.commands);
+                    *self = Self::new(save_type, &detector.data);
+                    for cmd in commands {
+                        for val in cmd {
+                            self.transfer(val);
+                        }
+                        self.deselect();
+                    }
+                }
+            }
+            Self::Eeprom(eeprom) => eeprom.deselect(),
+            Self::Flash(flash) => flash.deselect(),
+        }
+    }
+}
+
+impl<E: Engine> Core<E> {
+    /// Connect a backup chip of `save_type` holding `data` to the gamecard, e.g. from a .sav
+    /// file.
+    pub fn load_save(&mut self, save_type: SaveType, data: &[u8]) {
+        self.gamecard.save = SaveChip::new(save_type, data);
+    }
+
+    /// Connect the backup chip of a .sav file, with the type recorded in it, else the type listed
+    /// for the game, else the type of a chip of its size. Without any of those the type is
+    /// guessed from the first commands of the game, and the chip gets the contents of the file
+    /// then.
+    pub fn load_save_file(&mut self, file: &[u8]) {
+        let save = SaveFile::parse(file);
+        let game_code = self.gamecard.rom().get(0x0C..0x10);
+        let save_type = save
+            .save_type
+            .or_else(|| savedb::lookup(game_code?.try_into().unwrap()))
+            .or_else(|| SaveType::from_len(save.data.len()));
+        self.gamecard.save = match save_type {
+            Some(save_type) => SaveChip::new(save_type, &save.data),
+            None => SaveChip::Detecting(Detector::new(save.data)),
+        };
+    }
+
+    /// Type of the backup chip, [`SaveType::None`] while it's being detected.
+    pub fn save_type(&self) -> SaveType {
+        self.gamecard.save.save_type()
+    }
+
+    /// Contents of the backup chip, to be written to a .sav file.
+    pub fn save_data(&self) -> &[u8] {
+        self.gamecard.save.data()
+    }
+
+    /// Contents of the backup chip with its type recorded, to be written to a .sav file.
+    pub fn save_file(&self) -> Vec<u8> {
+        SaveFile {
+            save_type: Some(self.save_type()),
+            data: self.save_data().to_vec(),
+        }
+        .to_bytes()
+    }
+
+    /// The backup chip was written since the last call, so its contents should be flushed.
+    pub fn take_save_dirty(&mut self) -> bool {
+        self.gamecard.save.take_dirty()
+    }
+}
+
+/// A .sav file, the raw contents of the backup chip optionally followed by a footer recording its
+/// type: the type as in [`SaveType`]'s `Display` padded with zeros to 16 bytes, then
+/// [`SaveFile::MAGIC`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SaveFile {
+    pub save_type: Option<SaveType>,
+    pub data: Vec<u8>,
+}
+
+impl SaveFile {
+    pub const MAGIC: &'static [u8; 16] = b"|-vargds save-|\0";
+    const FOOTER_LEN: usize = 32;
+
+    pub fn parse(file: &[u8]) -> Self {
+        if let Some(data_len) = file.len().checked_sub(Self::FOOTER_LEN) {
+            let footer = &file[data_len..];
+            if &footer[16..] == Self::MAGIC {
+                let name = footer[..16].split(|&byte| byte == 0).next().unwrap_or(&[]);
+                let save_type = std::str::from_utf8(name)
+                    .ok()
+                    .and_then(|name| name.parse::<SaveType>().ok());
+                if let Some(save_type) = save_type {
+                    return Self {
+                        save_type: Some(save_type),
+                        data: file[..data_len].to_vec(),
+                    };
+                }
+            }
+        }
+        Self {
+            save_type: None,
+            data: file.to_vec(),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.data.clone();
+        if let Some(save_type) = self.save_type {
+            let mut name = [0; 16];
+            let save_type = save_type.to_string();
+            let len = save_type.len().min(name.len());
+            name[..len].copy_from_slice(&save_type.as_bytes()[..len]);
+            bytes.extend_from_slice(&name);
+            bytes.extend_from_slice(Self::MAGIC);
+        }
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(chip: &mut SaveChip, cmd: &[u8]) -> Vec<u8> {
+        let response = cmd.iter().map(|&val| chip.transfer(val)).collect();
+        chip.deselect();
+        response
+    }
+
+    /// Address bytes of `adr` for an EEPROM of `len` bytes.
+    fn address(len: usize, adr: usize) -> Vec<u8> {
+        let width = match len {
+            0x200 => 1,
+            0x20000 => 3,
+            _ => 2,
+        };
+        (0..width).rev().map(|i| (adr >> (i * 8)) as u8).collect()
+    }
+
+    fn write(chip: &mut SaveChip, cmd: u8, adr: &[u8], data: &[u8]) {
+        command(chip, &[Eeprom::CMD_WREN]);
+        command(chip, &[&[cmd], adr, data].concat());
+    }
+
+    fn read(chip: &mut SaveChip, cmd: u8, adr: &[u8], len: usize) -> Vec<u8> {
+        let mut response = command(chip, &[&[cmd], adr, &vec![0; len]].concat());
+        response.split_off(1 + adr.len())
+    }
+
+    fn status(chip: &mut SaveChip) -> u8 {
+        command(chip, &[Eeprom::CMD_RDSR, 0])[1]
+    }
+
+    #[test]
+    fn eeprom_write_enable_latch() {
+        let mut chip = SaveChip::new(SaveType::Eeprom(kb!(8)), &[]);
+        assert_eq!(status(&mut chip), 0);
+        // without WREN writes are ignored.
+        command(&mut chip, &[Eeprom::CMD_WRITE, 0, 0, 0x12]);
+        assert_eq!(chip.data()[0], 0xFF);
+        assert!(!chip.take_dirty());
+
+        command(&mut chip, &[Eeprom::CMD_WREN]);
+        assert_eq!(status(&mut chip), Eeprom::STATUS_WEL);
+        command(&mut chip, &[Eeprom::CMD_WRDI]);
+        assert_eq!(status(&mut chip), 0);
+
+        // the latch stays set until a write is deselected.
+        command(&mut chip, &[Eeprom::CMD_WREN]);
+        command(&mut chip, &[Eeprom::CMD_READ, 0, 0, 0]);
+        assert_eq!(status(&mut chip), Eeprom::STATUS_WEL);
+        command(&mut chip, &[Eeprom::CMD_WRITE, 0, 0, 0x12]);
+        assert_eq!(status(&mut chip), 0);
+        assert_eq!(chip.data()[0], 0x12);
+        assert!(chip.take_dirty());
+
+        command(&mut chip, &[Eeprom::CMD_WREN]);
+        command(&mut chip, &[Eeprom::CMD_WRSR, 0]);
+        assert_eq!(status(&mut chip), 0);
+    }
+
+    #[test]
+    fn eeprom_block_protect() {
+        let len = kb!(64);
+        let mut chip = SaveChip::new(SaveType::Eeprom(len), &[]);
+        // WRSR needs the latch too.
+        command(&mut chip, &[Eeprom::CMD_WRSR, 0b1100]);
+        assert_eq!(status(&mut chip), 0);
+
+        for (bp, first_protected) in [(0, len), (1, len / 4 * 3), (2, len / 2), (3, 0)] {
+            write(&mut chip, Eeprom::CMD_WRSR, &[], &[bp << 2]);
+            assert_eq!(status(&mut chip), bp << 2);
+            for adr in [
+                0,
+                len / 2 - 1,
+                len / 2,
+                len / 4 * 3 - 1,
+                len / 4 * 3,
+                len - 1,
+            ] {
+                let val = bp + 1;
+                write(&mut chip, Eeprom::CMD_WRITE, &address(len, adr), &[val]);
+                assert_eq!(
+                    chip.data()[adr] == val,
+                    adr < first_protected,
+                    "BP {bp} address {adr:#X}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn eeprom_writes_wrap_within_a_page() {
+        for (len, page_len) in [
+            (0x200, 0x10),
+            (kb!(8), 0x20),
+            (kb!(64), 0x80),
+            (kb!(128), 0x100),
+        ] {
+            let mut chip = SaveChip::new(SaveType::Eeprom(len), &[]);
+            let adr = 3 * page_len + page_len - 2;
+            write(
+                &mut chip,
+                Eeprom::CMD_WRITE,
+                &address(len, adr),
+                &[1, 2, 3, 4],
+            );
+            let page = &chip.data()[3 * page_len..4 * page_len];
+            assert_eq!(page[page_len - 2..], [1, 2], "{len:#X}");
+            assert_eq!(page[..2], [3, 4], "{len:#X}");
+            assert_eq!(chip.data()[4 * page_len], 0xFF, "{len:#X}");
+            // reads go on across pages.
+            assert_eq!(
+                read(&mut chip, Eeprom::CMD_READ, &address(len, adr), 3),
+                [1, 2, 0xFF]
+            );
+        }
+    }
+
+    #[test]
+    fn eeprom_512b_upper_half() {
+        let mut chip = SaveChip::new(SaveType::Eeprom(0x200), &[]);
+        // the unused bits of the status read as set.
+        assert_eq!(status(&mut chip), 0xF0);
+        command(&mut chip, &[Eeprom::CMD_WREN]);
+        assert_eq!(status(&mut chip), 0xF0 | Eeprom::STATUS_WEL);
+        command(&mut chip, &[Eeprom::CMD_WRDI]);
+
+        write(&mut chip, Eeprom::CMD_WRITE, &[0x10], &[0xAA]);
+        write(&mut chip, Eeprom::CMD_WRITE_HI, &[0x10], &[0xBB]);
+        write(&mut chip, Eeprom::CMD_WRITE_HI, &[0x00], &[0xCC]);
+        assert_eq!(chip.data()[0x010], 0xAA);
+        assert_eq!(chip.data()[0x110], 0xBB);
+        assert_eq!(read(&mut chip, Eeprom::CMD_READ, &[0x10], 1), [0xAA]);
+        assert_eq!(read(&mut chip, Eeprom::CMD_READ_HI, &[0x10], 1), [0xBB]);
+        // reads of the lower half go on into the upper half.
+        assert_eq!(read(&mut chip, Eeprom::CMD_READ, &[0xFF], 2), [0xFF, 0xCC]);
+    }
+
+    #[test]
+    fn flash_erase_and_jedec_id() {
+        let data: Vec<u8> = (0..kb!(512)).map(|i| (i >> 8) as u8 & 0xFE).collect();
+        let mut chip = SaveChip::new(SaveType::Flash(kb!(512)), &data);
+        // ST, 512KiB.
+        assert_eq!(
+            command(&mut chip, &[0x9F, 0, 0, 0])[1..],
+            [0x20, 0x40, 0x13]
+        );
+        let mut chip_8m = SaveChip::new(SaveType::Flash(mb!(8)), &[]);
+        assert_eq!(
+            command(&mut chip_8m, &[0x9F, 0, 0, 0])[1..],
+            [0x20, 0x40, 0x17]
+        );
+
+        // page erase
+        command(&mut chip, &[0x06]);
+        command(&mut chip, &[0xDB, 0x01, 0x23, 0x45]);
+        assert!(chip.data()[0x12300..0x12400].iter().all(|&val| val == 0xFF));
+        assert_eq!(chip.data()[0x122FF], 0x22);
+        assert_eq!(chip.data()[0x12400], 0x24);
+        assert_eq!(status(&mut chip), 0);
+        assert!(chip.take_dirty());
+
+        // sector erase
+        command(&mut chip, &[0x06]);
+        command(&mut chip, &[0xD8, 0x03, 0x00, 0x00]);
+        assert!(chip.data()[0x30000..0x40000].iter().all(|&val| val == 0xFF));
+        assert_eq!(chip.data()[0x2FFFF], 0xFE);
+        assert_eq!(chip.data()[0x40000], 0x00);
+        assert!(chip.take_dirty());
+
+        // erases need the latch.
+        command(&mut chip, &[0xD8, 0x00, 0x00, 0x00]);
+        assert_eq!(chip.data()[0], 0x00);
+        assert!(!chip.take_dirty());
+    }
+
+    #[test]
+    fn detected_chip_keeps_the_save() {
+        let data: Vec<u8> = (0..kb!(512)).map(|i| i as u8).collect();
+        let mut chip = SaveChip::Detecting(Detector::new(data.clone()));
+        assert_eq!(chip.save_type(), SaveType::None);
+        // RDID is only understood by FLASH chips.
+        command(&mut chip, &[0x9F, 0, 0, 0]);
+        assert_eq!(chip.save_type(), SaveType::Flash(kb!(512)));
+        assert_eq!(chip.data(), &data[..]);
+        assert_eq!(
+            command(&mut chip, &[0x03, 0x00, 0x01, 0x02, 0, 0])[4..],
+            [0x02, 0x03]
+        );
+    }
+
+    #[test]
+    fn detector_serves_reads_before_the_first_write() {
+        let data: Vec<u8> = (0..0x1000).map(|i| i as u8).collect();
+        let mut chip = SaveChip::Detecting(Detector::new(data.clone()));
+        // served with the 2 address bytes of a chip the size of the save, which the 4 bytes read
+        // after them confirm.
+        assert_eq!(
+            command(&mut chip, &[0x03, 0x00, 0x10, 0, 0, 0, 0])[3..],
+            [0x10, 0x11, 0x12, 0x13]
+        );
+        assert_eq!(chip.save_type(), SaveType::None);
+        // 2 bytes after 2 or 3 address bytes, the read settles it.
+        command(&mut chip, &[0x06]);
+        command(&mut chip, &[0x02, 0x00, 0x20, 0xAA, 0xBB]);
+        assert_eq!(chip.save_type(), SaveType::Eeprom(kb!(8)));
+        assert_eq!(chip.data()[..0x20], data[..0x20]);
+        assert_eq!(chip.data()[0x20..0x22], [0xAA, 0xBB]);
+        assert_eq!(chip.data()[0x22..0x1000], data[0x22..]);
+        assert!(chip.data()[0x1000..].iter().all(|&val| val == 0xFF));
+    }
+
+    #[test]
+    fn detector_takes_a_single_address_byte_for_the_512b_eeprom() {
+        let mut chip = SaveChip::Detecting(Detector::new(Vec::new()));
+        assert_eq!(command(&mut chip, &[0x0B, 0x10, 0]), [0xFF; 3]);
+        assert_eq!(chip.save_type(), SaveType::Eeprom(0x200));
+
+        let mut chip = SaveChip::Detecting(Detector::new(Vec::new()));
+        command(&mut chip, &[0x03, 0x10, 0]);
+        assert_eq!(chip.save_type(), SaveType::Eeprom(0x200));
+    }
+
+    #[test]
+    fn save_file_records_the_type_in_a_footer() {
+        let file = SaveFile {
+            save_type: Some(SaveType::Eeprom(kb!(8))),
+            data: vec![0xAB; kb!(8)],
+        };
+        let bytes = file.to_bytes();
+        assert_eq!(bytes.len(), kb!(8) + 32);
+        assert_eq!(&bytes[kb!(8)..kb!(8) + 16], b"eeprom-8192\0\0\0\0\0");
+        assert_eq!(&bytes[kb!(8) + 16..], SaveFile::MAGIC);
+        assert_eq!(SaveFile::parse(&bytes), file);
+    }
+
+    #[test]
+    fn save_file_without_a_footer_is_raw_data() {
+        let data = vec![0x5A; kb!(64)];
+        let file = SaveFile::parse(&data);
+        assert_eq!(file.save_type, None);
+        assert_eq!(file.data, data);
+        assert_eq!(file.to_bytes(), data);
+    }
+}
@@ -0,0 +1,72 @@
+//! Backup chips of known games, as the type isn't stored in the ROM. Games missing here get the
+//! type recorded in their .sav file, or the one guessed from its size or from their first commands
+//! to the chip.
+
+use super::SaveType;
+
+const NONE: SaveType = SaveType::None;
+const EEPROM_512: SaveType = SaveType::Eeprom(0x200);
+const EEPROM_8K: SaveType = SaveType::Eeprom(kb!(8));
+const FLASH_256K: SaveType = SaveType::Flash(kb!(256));
+const FLASH_512K: SaveType = SaveType::Flash(kb!(512));
+
+/// Games by the first three characters of their game code, the last one is the region which
+/// doesn't change the chip. Sorted by code.
+const GAMES: &[(&[u8; 3], SaveType)] = &[
+    // homebrew.
+    (b"###", NONE),
+    // New Super Mario Bros.
+    (b"A2D", EEPROM_8K),
+    // Pokemon Diamond.
+    (b"ADA", FLASH_512K),
+    // Animal Crossing: Wild World.
+    (b"ADM", FLASH_256K),
+    // Mario Kart DS.
+    (b"AMC", FLASH_256K),
+    // Pokemon Pearl.
+    (b"APA", FLASH_512K),
+    // Super Mario 64 DS.
+    (b"ASM", EEPROM_512),
+    // Pokemon Platinum.
+    (b"CPU", FLASH_512K),
+    // Pokemon SoulSilver.
+    (b"IPG", FLASH_512K),
+    // Pokemon HeartGold.
+    (b"IPK", FLASH_512K),
+    // Pokemon White.
+    (b"IRA", FLASH_512K),
+    // Pokemon Black.
+    (b"IRB", FLASH_512K),
+    // Pokemon White 2.
+    (b"IRD", FLASH_512K),
+    // Pokemon Black 2.
+    (b"IRE", FLASH_512K),
+];
+
+/// The backup chip of the game with `game_code` (0x0C in the header), if it's known.
+pub fn lookup(game_code: [u8; 4]) -> Option<SaveType> {
+    GAMES
+        .binary_search_by_key(&&game_code[..3], |&(id, _)| &id[..])
+        .ok()
+        .map(|i| GAMES[i].1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn games_are_sorted_and_valid() {
+        assert!(GAMES.windows(2).all(|games| games[0].0 < games[1].0));
+        assert!(GAMES.iter().all(|(_, save_type)| save_type.is_valid()));
+    }
+
+    #[test]
+    fn lookup_ignores_the_region() {
+        assert_eq!(lookup(*b"ADAE"), Some(FLASH_512K));
+        assert_eq!(lookup(*b"ADAJ"), Some(FLASH_512K));
+        assert_eq!(lookup(*b"ASMP"), Some(EEPROM_512));
+        assert_eq!(lookup(*b"####"), Some(NONE));
+        assert_eq!(lookup(*b"ZZZE"), None);
+    }
+}
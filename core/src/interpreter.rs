@@ -1,6 +1,6 @@
 pub mod arm9;
 
-use crate::{Core, Engine};
+use crate::{scheduler, Core, Engine};
 
 pub struct Interpreter;
 
@@ -10,8 +10,24 @@ impl Engine for Interpreter {
     type ARM7Data = ();
 }
 
+/// Run for a slice of emulated time. Only the ARM9 executes code: there's no ARM7 interpreter,
+/// its binary is loaded and interrupts are delivered to it but it never runs past them.
 pub fn run(core: &mut Core<Interpreter>) {
-    for _ in 0..100_000 {
-        arm9::step(core)
+    let end = core.scheduler.now() + 100_000 * arm9::INSTR_CYCLES;
+    while core.scheduler.now() < end {
+        // an arm7 halted through HALTCNT keeps interrupts pending until one wakes it up.
+        core.arm7.check_irq();
+        if core.arm9.halted() {
+            core.arm9.check_irq();
+        }
+        if core.arm9.halted() {
+            // nothing runs until an event requests an interrupt.
+            let next = core.scheduler.next_event_at().map_or(end, |at| at.min(end));
+            core.scheduler.skip_to(next);
+        } else {
+            arm9::step(core);
+            core.scheduler.advance(arm9::INSTR_CYCLES);
+        }
+        scheduler::handle_events(core);
     }
 }
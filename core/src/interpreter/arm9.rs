@@ -1,3 +1,4 @@
+mod bios;
 mod branch;
 mod data;
 mod mem;
@@ -7,6 +8,9 @@ use crate::bus::arm9 as bus;
 use crate::cpu::arm9;
 use crate::{Core, Interpreter};
 
+/// Cycles every instruction is assumed to take.
+pub const INSTR_CYCLES: u64 = 1;
+
 static COND_INSTR_LUT: [fn(&mut Core<Interpreter>, u32); 4096] = {
     use arm_decode::*;
 
@@ -18,8 +22,11 @@ static UNCOND_INSTR_LUT: [fn(&mut Core<Interpreter>, u32); 4096] =
 
 impl Core<Interpreter> {
     fn fetch(&mut self) -> u32 {
-        let fetch = bus::read32(self, self.arm9.pc());
-        self.arm9.pc_set(self.arm9.pc().wrapping_add(4));
+        // r15 holds the address of the next instruction between instructions, it reads as the
+        // address of the instruction plus 8 while executing it.
+        let adr = self.arm9.gpr[15];
+        let fetch = bus::read32(self, adr);
+        self.arm9.pc_set(adr.wrapping_add(4));
         fetch
     }
 
@@ -29,6 +36,14 @@ impl Core<Interpreter> {
 }
 
 pub fn step(core: &mut Core<Interpreter>) {
+    core.arm9.check_irq();
+    // there's no BIOS image, its interrupt handler is emulated instead.
+    match core.arm9.gpr[15] {
+        arm9::Arm9::<Interpreter>::IRQ_VECTOR => return bios::irq(core),
+        bios::IRQ_RETURN => return bios::irq_return(core),
+        bios::INTR_WAIT_LOOP => return bios::intr_wait_loop(core),
+        _ => {}
+    }
     let fetch = core.fetch();
     let is_cond = arm_decode::ARM9.is_cond_instr(fetch);
     let index = arm_decode::ARM9.extract_instr_bits(fetch) as usize;
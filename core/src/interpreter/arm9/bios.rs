@@ -0,0 +1,186 @@
+//! The IRQ handler of the ARM9 BIOS, which calls the handler the game stored at the end of DTCM,
+//! and the BIOS functions that wait for it.
+
+use crate::bus::arm9 as bus;
+use crate::cpu::arm9::Arm9;
+use crate::irq::{self, Interrupt};
+use crate::{Core, Interpreter};
+
+/// Where the handler of the game returns to, in the BIOS after the IRQ vector.
+pub const IRQ_RETURN: u32 = Arm9::<Interpreter>::IRQ_VECTOR + 0x8;
+
+/// Where IntrWait halts, in the BIOS so the IRQ handler returns there to check the flags again.
+pub const INTR_WAIT_LOOP: u32 = Arm9::<Interpreter>::IRQ_VECTOR + 0x10;
+
+/// Offset in DTCM of the address of the handler of the game.
+const HANDLER_OFFS: u32 = 0x3FFC;
+
+/// Registers the BIOS saves on the IRQ stack, the handler of the game may clobber them.
+const SAVED_REGS: [usize; 6] = [0, 1, 2, 3, 12, 14];
+
+/// `stmfd sp!, {r0-r3, r12, lr}`, then call the handler at DTCM+0x3FFC.
+pub fn irq(core: &mut Core<Interpreter>) {
+    let mut sp = core.arm9.gpr(13);
+    for &reg in SAVED_REGS.iter().rev() {
+        sp = sp.wrapping_sub(4);
+        let val = core.arm9.gpr(reg);
+        bus::write32(core, sp, val);
+    }
+    core.arm9.gpr_set(13, sp);
+
+    let handler = bus::read32(core, core.arm9.dtcm_base + HANDLER_OFFS);
+    if handler & 0b1 != 0 {
+        // there's no thumb interpreter, the interrupt stays requested.
+        warn!(core.arm9.logger, "skipping thumb IRQ handler {handler:08X}");
+        core.arm9.pc_set(IRQ_RETURN);
+        return;
+    }
+    core.arm9.lr_set(IRQ_RETURN);
+    core.arm9.pc_set(handler & !0b11);
+}
+
+/// `ldmfd sp!, {r0-r3, r12, lr}`, then `subs pc, lr, #4` back to the interrupted code.
+pub fn irq_return(core: &mut Core<Interpreter>) {
+    let mut sp = core.arm9.gpr(13);
+    for reg in SAVED_REGS {
+        let val = bus::read32(core, sp);
+        core.arm9.gpr_set(reg, val);
+        sp = sp.wrapping_add(4);
+    }
+    core.arm9.gpr_set(13, sp);
+
+    let return_adr = core.arm9.gpr(14).wrapping_sub(4);
+    core.arm9.restore_cpsr();
+    core.arm9.pc_set(return_adr);
+}
+
+/// SWI 0x04, wait until one of the interrupts in r1 was serviced, after discarding the ones that
+/// already were if r0 is set. SWI 0x05 is this with r0 and r1 set to 1 for VBlank.
+pub fn intr_wait(core: &mut Core<Interpreter>) {
+    if core.arm9.gpr(0) != 0 {
+        let mask = core.arm9.gpr(1);
+        irq::intr_wait_done::<Interpreter, true>(core, mask);
+    }
+    // the return address is kept on the stack while waiting, like the BIOS does on its own.
+    let sp = core.arm9.gpr(13).wrapping_sub(4);
+    let return_adr = core.arm9.gpr[15];
+    bus::write32(core, sp, return_adr);
+    core.arm9.gpr_set(13, sp);
+    core.arm9.pc_set(INTR_WAIT_LOOP);
+}
+
+/// SWI 0x05.
+pub fn vblank_intr_wait(core: &mut Core<Interpreter>) {
+    core.arm9.gpr_set(0, 1);
+    core.arm9.gpr_set(1, Interrupt::VBlank.mask());
+    intr_wait(core);
+}
+
+/// Halt with IME set until the check flags at DTCM+0x3FF8 have one of the interrupts in r1, then
+/// clear those and return.
+pub fn intr_wait_loop(core: &mut Core<Interpreter>) {
+    core.arm9.irq.ime_set(1);
+    let mask = core.arm9.gpr(1);
+    if !irq::intr_wait_done::<Interpreter, true>(core, mask) {
+        core.arm9.halted = true;
+        return;
+    }
+    let sp = core.arm9.gpr(13);
+    let return_adr = bus::read32(core, sp);
+    core.arm9.gpr_set(13, sp.wrapping_add(4));
+    core.arm9.pc_set(return_adr);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CODE: u32 = 0x0200_0000;
+    const HANDLER: u32 = 0x0200_1000;
+
+    /// A core running `instrs` from main memory with an IRQ handler that only returns.
+    fn core(instrs: &[u32]) -> Core<Interpreter> {
+        let mut core = Core::new(slog::Logger::root(slog::Discard, slog::o!()));
+        for (i, &instr) in instrs.iter().enumerate() {
+            bus::write32(&mut core, CODE + i as u32 * 4, instr);
+        }
+        // bx lr
+        bus::write32(&mut core, HANDLER, 0xE12F_FF1E);
+        let dtcm_base = core.arm9.dtcm_base;
+        bus::write32(&mut core, dtcm_base + HANDLER_OFFS, HANDLER);
+        core.arm9.boot(CODE, dtcm_base + 0x2F7C, dtcm_base + 0x3F80);
+        core.arm9.irq.ie_set(Interrupt::VBlank.mask());
+        core
+    }
+
+    /// Step like `interpreter::run`, without the scheduler.
+    fn step(core: &mut Core<Interpreter>, count: usize) {
+        for _ in 0..count {
+            core.arm9.check_irq();
+            if !core.arm9.halted() {
+                super::super::step(core);
+            }
+        }
+    }
+
+    fn check_flags_set(core: &mut Core<Interpreter>, val: u32) {
+        irq::check_flags_set::<Interpreter, true>(core, val);
+    }
+
+    #[test]
+    fn vblank_intr_wait_returns_once_the_flag_is_set() {
+        // swi 0x05, then b .
+        let mut core = core(&[0xEF05_0000, 0xEAFF_FFFE]);
+        let sp = core.arm9.gpr(13);
+        check_flags_set(&mut core, Interrupt::VBlank.mask());
+        step(&mut core, 2);
+        // the old flag is discarded.
+        assert!(core.arm9.halted());
+        assert_eq!(core.arm9.gpr[15], INTR_WAIT_LOOP);
+        assert_eq!(core.arm9.irq.ime(), 1);
+
+        // an interrupt whose handler doesn't set the flag keeps it waiting. it takes a step to
+        // enter the handler of the game, one to run it and one to return, the interrupt is
+        // acknowledged then so it isn't taken again.
+        core.arm9.irq.request(Interrupt::VBlank);
+        step(&mut core, 3);
+        core.arm9.irq.acknowledge(Interrupt::VBlank.mask());
+        step(&mut core, 1);
+        assert!(core.arm9.halted());
+        assert_eq!(core.arm9.gpr[15], INTR_WAIT_LOOP);
+
+        core.arm9.irq.request(Interrupt::VBlank);
+        check_flags_set(&mut core, Interrupt::VBlank.mask());
+        step(&mut core, 3);
+        core.arm9.irq.acknowledge(Interrupt::VBlank.mask());
+        step(&mut core, 1);
+        assert!(!core.arm9.halted());
+        assert_eq!(core.arm9.gpr[15], CODE + 4);
+        assert_eq!(core.arm9.gpr(13), sp);
+        assert_eq!(irq::check_flags::<Interpreter, true>(&mut core), 0);
+    }
+
+    #[test]
+    fn intr_wait_without_discard_returns_for_a_set_flag() {
+        // mov r0, #0; mov r1, #1; swi 0x04; b .
+        let mut core = core(&[0xE3A0_0000, 0xE3A0_1001, 0xEF04_0000, 0xEAFF_FFFE]);
+        check_flags_set(&mut core, Interrupt::VBlank.mask());
+        step(&mut core, 4);
+        assert!(!core.arm9.halted());
+        assert_eq!(core.arm9.gpr[15], CODE + 12);
+    }
+
+    #[test]
+    fn thumb_handler_is_skipped() {
+        let mut core = core(&[0xEAFF_FFFE]);
+        let dtcm_base = core.arm9.dtcm_base;
+        bus::write32(&mut core, dtcm_base + HANDLER_OFFS, HANDLER | 1);
+        core.arm9.irq.ime_set(1);
+        core.arm9.irq.request(Interrupt::VBlank);
+        step(&mut core, 1);
+        core.arm9.irq.acknowledge(Interrupt::VBlank.mask());
+        step(&mut core, 2);
+        assert_eq!(core.arm9.gpr[15], CODE);
+        assert!(!core.arm9.cpsr.i());
+    }
+}
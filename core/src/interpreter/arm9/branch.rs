@@ -15,13 +15,14 @@ pub fn blx_reg(core: &mut Core<Interpreter>, instr: u32) {
 
 pub fn bx(core: &mut Core<Interpreter>, instr: u32) {
     let rm = instr & 0xF;
-    if rm & 0b11 == 0b10 {
+    let target = core.arm9.gpr(rm as usize);
+    if target & 0b11 == 0b10 {
         super::misc::unpred(core, instr);
     }
-    if rm & 0b1 != 0 {
+    if target & 0b1 != 0 {
         panic!("cannot switch to thumb! unimplemented!")
     } else {
-        core.arm9.pc_set(rm & !0b11);
+        core.arm9.pc_set(target & !0b11);
     }
 }
 
@@ -30,7 +31,7 @@ pub fn b<const ARG: B>(core: &mut Core<Interpreter>, instr: u32) {
     let pc = core.arm9.pc();
     let new_pc = pc.wrapping_add_signed(imm);
     if ARG.link {
-        core.arm9.lr_set(core.arm9.pc());
+        core.arm9.lr_set(core.arm9.gpr[15]);
     }
     core.arm9.pc_set(new_pc);
 }
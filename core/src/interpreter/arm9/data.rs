@@ -146,6 +146,17 @@ pub fn dp<const ARG: Dp>(core: &mut Core<Interpreter>, instr: u32) {
             core.arm9.gpr_set(rdi, val);
         }
     }
+
+    // `subs pc, lr, #4` and the like return from exceptions.
+    if update_flags
+        && rdi == 15
+        && !matches!(
+            ARG.opc,
+            DpOpcTy::Tst | DpOpcTy::Teq | DpOpcTy::Cmp | DpOpcTy::Cmn
+        )
+    {
+        core.arm9.restore_cpsr();
+    }
 }
 
 pub fn clz(core: &mut Core<Interpreter>, instr: u32) {
@@ -228,6 +239,14 @@ pub fn cp_dp(_core: &mut Core<Interpreter>, _instr: u32) {
     unimplemented!()
 }
 
-pub fn cp_mov<const CP_MOV: CpMov>(_core: &mut Core<Interpreter>, _instr: u32) {
-    unimplemented!()
+pub fn cp_mov<const CP_MOV: CpMov>(core: &mut Core<Interpreter>, instr: u32) {
+    let cp = (instr >> 8) & 0xF;
+    let crn = (instr >> 16) & 0xF;
+    let crm = instr & 0xF;
+    let op2 = (instr >> 5) & 0b111;
+    match (cp, CP_MOV.arm_reg_load, crn, crm, op2) {
+        // CP15 wait for interrupt.
+        (15, false, 7, 0, 4) | (15, false, 7, 8, 2) => core.arm9.halted = true,
+        _ => unimplemented!(),
+    }
 }
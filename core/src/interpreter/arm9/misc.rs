@@ -12,6 +12,11 @@ pub fn bkpt(core: &mut Core<Interpreter>, _: u32) {
     unimplemented!()
 }
 
-pub fn swi(core: &mut Core<Interpreter>, _: u32) {
-    unimplemented!()
+/// The BIOS functions are emulated, the number is in bits 16-23 in ARM state.
+pub fn swi(core: &mut Core<Interpreter>, instr: u32) {
+    match (instr >> 16) & 0xFF {
+        0x04 => super::bios::intr_wait(core),
+        0x05 => super::bios::vblank_intr_wait(core),
+        func => unimplemented!("swi {func:02X}"),
+    }
 }
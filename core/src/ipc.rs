@@ -0,0 +1,163 @@
+use std::collections::VecDeque;
+
+use crate::irq::{self, Interrupt};
+use crate::{Core, Engine};
+
+const FIFO_LEN: usize = 16;
+
+/// IPCSYNC and the IPC FIFOs between the CPUs. Index 0 is the state of the ARM9, index 1 the
+/// state of the ARM7.
+pub struct Ipc {
+    /// IPCSYNC output (bits 8-11) and IRQ enable (bit 14).
+    sync: [u16; 2],
+    /// IPCFIFOCNT IRQ enables (bit 2 and 10), error (bit 14) and enable (bit 15).
+    fifo_cnt: [u16; 2],
+    /// Send FIFO of each CPU, which is the receive FIFO of the other one.
+    fifos: [VecDeque<u32>; 2],
+    /// Last word received, returned again when reading an empty FIFO.
+    last_recv: [u32; 2],
+}
+
+impl Default for Ipc {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Ipc {
+    pub fn new() -> Self {
+        Self {
+            sync: [0; 2],
+            fifo_cnt: [0; 2],
+            fifos: [
+                VecDeque::with_capacity(FIFO_LEN),
+                VecDeque::with_capacity(FIFO_LEN),
+            ],
+            last_recv: [0; 2],
+        }
+    }
+
+    #[inline]
+    fn enabled(&self, local: usize) -> bool {
+        get_bit!(self.fifo_cnt[local], 15)
+    }
+}
+
+#[inline]
+fn local<const ARM9: bool>() -> usize {
+    if ARM9 {
+        0
+    } else {
+        1
+    }
+}
+
+#[inline]
+fn remote<const ARM9: bool>() -> usize {
+    local::<ARM9>() ^ 1
+}
+
+/// Raise an interrupt on the other CPU.
+fn request_remote<E: Engine, const ARM9: bool>(core: &mut Core<E>, interrupt: Interrupt) {
+    if ARM9 {
+        irq::request::<E, false>(core, interrupt)
+    } else {
+        irq::request::<E, true>(core, interrupt)
+    }
+}
+
+pub fn sync<E: Engine, const ARM9: bool>(core: &mut Core<E>) -> u16 {
+    let ipc = &core.ipc;
+    let input = (ipc.sync[remote::<ARM9>()] >> 8) & 0xF;
+    input | ipc.sync[local::<ARM9>()]
+}
+
+pub fn sync_set<E: Engine, const ARM9: bool>(core: &mut Core<E>, val: u16) {
+    core.ipc.sync[local::<ARM9>()] = val & 0x4F00;
+    let remote_irq = get_bit!(core.ipc.sync[remote::<ARM9>()], 14);
+    if get_bit!(val, 13) && remote_irq {
+        request_remote::<E, ARM9>(core, Interrupt::IpcSync);
+    }
+}
+
+pub fn fifo_cnt<E: Engine, const ARM9: bool>(core: &mut Core<E>) -> u16 {
+    let ipc = &core.ipc;
+    let send = &ipc.fifos[local::<ARM9>()];
+    let recv = &ipc.fifos[remote::<ARM9>()];
+    let mut cnt = ipc.fifo_cnt[local::<ARM9>()];
+    toggle_bit!(cnt, 0, send.is_empty());
+    toggle_bit!(cnt, 1, send.len() == FIFO_LEN);
+    toggle_bit!(cnt, 8, recv.is_empty());
+    toggle_bit!(cnt, 9, recv.len() == FIFO_LEN);
+    cnt
+}
+
+pub fn fifo_cnt_set<E: Engine, const ARM9: bool>(core: &mut Core<E>, val: u16) {
+    let ipc = &mut core.ipc;
+    let old = ipc.fifo_cnt[local::<ARM9>()];
+    let mut cnt = (old & b!(14)) | (val & 0x8404);
+    // the error flag is acknowledged by writing 1.
+    if get_bit!(val, 14) {
+        unset_bit!(cnt, 14);
+    }
+    ipc.fifo_cnt[local::<ARM9>()] = cnt;
+    if get_bit!(val, 3) {
+        ipc.fifos[local::<ARM9>()].clear();
+    }
+
+    // the IRQs trigger when enabled while their condition holds.
+    let send_empty = ipc.fifos[local::<ARM9>()].is_empty();
+    let recv_not_empty = !ipc.fifos[remote::<ARM9>()].is_empty();
+    let rising = cnt & !old;
+    if get_bit!(cnt, 2) && send_empty && (get_bit!(rising, 2) || get_bit!(val, 3)) {
+        irq::request::<E, ARM9>(core, Interrupt::IpcSendFifoEmpty);
+    }
+    if get_bit!(rising, 10) && recv_not_empty {
+        irq::request::<E, ARM9>(core, Interrupt::IpcRecvFifoNotEmpty);
+    }
+}
+
+/// IPCFIFOSEND.
+pub fn fifo_send<E: Engine, const ARM9: bool>(core: &mut Core<E>, val: u32) {
+    let ipc = &mut core.ipc;
+    if !ipc.enabled(local::<ARM9>()) {
+        return;
+    }
+    let send = &mut ipc.fifos[local::<ARM9>()];
+    if send.len() == FIFO_LEN {
+        set_bit!(ipc.fifo_cnt[local::<ARM9>()], 14);
+        return;
+    }
+    let was_empty = send.is_empty();
+    send.push_back(val);
+    if was_empty && get_bit!(ipc.fifo_cnt[remote::<ARM9>()], 10) {
+        request_remote::<E, ARM9>(core, Interrupt::IpcRecvFifoNotEmpty);
+    }
+}
+
+/// IPCFIFORECV.
+pub fn fifo_recv<E: Engine, const ARM9: bool>(core: &mut Core<E>) -> u32 {
+    let ipc = &mut core.ipc;
+    if !ipc.enabled(local::<ARM9>()) {
+        return fifo_peek::<E, ARM9>(core);
+    }
+    let last = ipc.last_recv[local::<ARM9>()];
+    let Some(val) = ipc.fifos[remote::<ARM9>()].pop_front() else {
+        set_bit!(ipc.fifo_cnt[local::<ARM9>()], 14);
+        return last;
+    };
+    ipc.last_recv[local::<ARM9>()] = val;
+    if ipc.fifos[remote::<ARM9>()].is_empty() && get_bit!(ipc.fifo_cnt[remote::<ARM9>()], 2) {
+        request_remote::<E, ARM9>(core, Interrupt::IpcSendFifoEmpty);
+    }
+    val
+}
+
+/// IPCFIFORECV without removing the word, for debug reads.
+pub fn fifo_peek<E: Engine, const ARM9: bool>(core: &mut Core<E>) -> u32 {
+    let ipc = &core.ipc;
+    ipc.fifos[remote::<ARM9>()]
+        .front()
+        .copied()
+        .unwrap_or(ipc.last_recv[local::<ARM9>()])
+}
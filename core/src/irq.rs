@@ -0,0 +1,178 @@
+use crate::bus::{arm7, arm9};
+use crate::{Core, Engine};
+
+/// Interrupt sources, the discriminant is the bit in IE/IF.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum Interrupt {
+    VBlank = 0,
+    HBlank = 1,
+    VCount = 2,
+    Timer0 = 3,
+    Timer1 = 4,
+    Timer2 = 5,
+    Timer3 = 6,
+    /// Serial port and RTC (ARM7 only).
+    Rtc = 7,
+    Dma0 = 8,
+    Dma1 = 9,
+    Dma2 = 10,
+    Dma3 = 11,
+    Keypad = 12,
+    GbaSlot = 13,
+    IpcSync = 16,
+    IpcSendFifoEmpty = 17,
+    IpcRecvFifoNotEmpty = 18,
+    CartTransferComplete = 19,
+    CartIreqMc = 20,
+    /// ARM9 only.
+    GeometryFifo = 21,
+    /// ARM7 only.
+    ScreensUnfolding = 22,
+    /// ARM7 only.
+    Spi = 23,
+    /// ARM7 only.
+    Wifi = 24,
+}
+
+impl Interrupt {
+    #[inline]
+    pub const fn mask(self) -> u32 {
+        1 << self as u32
+    }
+
+    pub fn timer(index: usize) -> Self {
+        [Self::Timer0, Self::Timer1, Self::Timer2, Self::Timer3][index & 0b11]
+    }
+
+    pub fn dma(index: usize) -> Self {
+        [Self::Dma0, Self::Dma1, Self::Dma2, Self::Dma3][index & 0b11]
+    }
+}
+
+/// Interrupt controller (IME/IE/IF) of one of the CPUs.
+pub struct Irq {
+    ime: bool,
+    ie: u32,
+    if_: u32,
+    valid: u32,
+}
+
+impl Irq {
+    const ARM9_VALID: u32 = 0x003F_3F7F;
+    const ARM7_VALID: u32 = 0x01DF_3FFF;
+
+    pub fn new_arm9() -> Self {
+        Self::new(Self::ARM9_VALID)
+    }
+
+    pub fn new_arm7() -> Self {
+        Self::new(Self::ARM7_VALID)
+    }
+
+    fn new(valid: u32) -> Self {
+        Self {
+            ime: false,
+            ie: 0,
+            if_: 0,
+            valid,
+        }
+    }
+
+    /// Raise an interrupt, called by the peripherals.
+    #[inline]
+    pub fn request(&mut self, irq: Interrupt) {
+        self.if_ |= irq.mask() & self.valid;
+    }
+
+    /// Clear the requests in `mask`, this is what writing to IF does.
+    #[inline]
+    pub fn acknowledge(&mut self, mask: u32) {
+        self.if_ &= !mask;
+    }
+
+    /// An enabled interrupt is requested, regardless of IME.
+    #[inline]
+    pub fn requested(&self) -> bool {
+        self.ie & self.if_ != 0
+    }
+
+    /// An interrupt should be delivered to the CPU unless CPSR.I is set.
+    #[inline]
+    pub fn pending(&self) -> bool {
+        self.ime && self.requested()
+    }
+
+    pub fn ime(&self) -> u32 {
+        self.ime as u32
+    }
+
+    pub fn ime_set(&mut self, val: u32) {
+        self.ime = get_bit!(val, 0);
+    }
+
+    pub fn ie(&self) -> u32 {
+        self.ie
+    }
+
+    pub fn ie_set(&mut self, val: u32) {
+        self.ie = val & self.valid;
+    }
+
+    pub fn if_(&self) -> u32 {
+        self.if_
+    }
+}
+
+pub fn irq<E: Engine, const ARM9: bool>(core: &mut Core<E>) -> &mut Irq {
+    if ARM9 {
+        &mut core.arm9.irq
+    } else {
+        &mut core.arm7.irq
+    }
+}
+
+/// Raise an interrupt on one of the CPUs.
+#[inline]
+pub fn request<E: Engine, const ARM9: bool>(core: &mut Core<E>, interrupt: Interrupt) {
+    irq::<E, ARM9>(core).request(interrupt)
+}
+
+/// Address of the IRQ check flags the BIOS IntrWait functions poll.
+/// User IRQ handlers OR the serviced interrupts into it.
+pub fn check_flags_adr<E: Engine, const ARM9: bool>(core: &Core<E>) -> u32 {
+    if ARM9 {
+        core.arm9.dtcm_base + 0x3FF8
+    } else {
+        0x0380FFF8
+    }
+}
+
+pub fn check_flags<E: Engine, const ARM9: bool>(core: &mut Core<E>) -> u32 {
+    let adr = check_flags_adr::<E, ARM9>(core);
+    if ARM9 {
+        arm9::read32(core, adr)
+    } else {
+        arm7::read32(core, adr)
+    }
+}
+
+pub fn check_flags_set<E: Engine, const ARM9: bool>(core: &mut Core<E>, val: u32) {
+    let adr = check_flags_adr::<E, ARM9>(core);
+    if ARM9 {
+        arm9::write32(core, adr, val)
+    } else {
+        arm7::write32(core, adr, val)
+    }
+}
+
+/// Check done by IntrWait after each wake up, consumes the flags in `mask` if any is set.
+pub fn intr_wait_done<E: Engine, const ARM9: bool>(core: &mut Core<E>, mask: u32) -> bool {
+    let flags = check_flags::<E, ARM9>(core);
+    if flags & mask != 0 {
+        check_flags_set::<E, ARM9>(core, flags & !mask);
+        true
+    } else {
+        false
+    }
+}
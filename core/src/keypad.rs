@@ -0,0 +1,131 @@
+use crate::irq::{self, Interrupt};
+use crate::{Core, Engine};
+
+/// Key bits of [`Core::set_keys`], set while pressed.
+pub mod keys {
+    pub type Keys = u16;
+
+    pub const A: Keys = b!(0);
+    pub const B: Keys = b!(1);
+    pub const SELECT: Keys = b!(2);
+    pub const START: Keys = b!(3);
+    pub const RIGHT: Keys = b!(4);
+    pub const LEFT: Keys = b!(5);
+    pub const UP: Keys = b!(6);
+    pub const DOWN: Keys = b!(7);
+    pub const R: Keys = b!(8);
+    pub const L: Keys = b!(9);
+    pub const X: Keys = b!(10);
+    pub const Y: Keys = b!(11);
+    /// Debug button, only present on debug consoles.
+    pub const DEBUG: Keys = b!(12);
+}
+
+use keys::Keys;
+
+/// Input state shared by KEYINPUT/KEYCNT of both CPUs and EXTKEYIN of the ARM7.
+pub struct Keypad {
+    pressed: Keys,
+    /// Touch position in screen pixels while the pen is down.
+    touch: Option<(u8, u8)>,
+    lid_closed: bool,
+    /// KEYCNT of the ARM9 and the ARM7.
+    keycnt: [u16; 2],
+}
+
+impl Default for Keypad {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Keypad {
+    pub fn new() -> Self {
+        Self {
+            pressed: 0,
+            touch: None,
+            lid_closed: false,
+            keycnt: [0; 2],
+        }
+    }
+
+    /// KEYINPUT, a bit is cleared while the key is pressed.
+    pub fn keyinput(&self) -> u16 {
+        !self.pressed & 0x3FF
+    }
+
+    /// EXTKEYIN of the ARM7, bits 2, 4 and 5 always read as set.
+    pub fn extkeyin(&self) -> u16 {
+        let mut extkeyin = 0b11_0100;
+        toggle_bit!(extkeyin, 0, self.pressed & keys::X == 0);
+        toggle_bit!(extkeyin, 1, self.pressed & keys::Y == 0);
+        toggle_bit!(extkeyin, 3, self.pressed & keys::DEBUG == 0);
+        toggle_bit!(extkeyin, 6, self.touch.is_none());
+        toggle_bit!(extkeyin, 7, self.lid_closed);
+        extkeyin
+    }
+
+    pub fn touch(&self) -> Option<(u8, u8)> {
+        self.touch
+    }
+
+    pub fn lid_closed(&self) -> bool {
+        self.lid_closed
+    }
+
+    /// The KEYCNT IRQ condition of a CPU holds.
+    fn irq_condition(&self, cpu: usize) -> bool {
+        let keycnt = self.keycnt[cpu];
+        if !get_bit!(keycnt, 14) {
+            return false;
+        }
+        let selected = keycnt & 0x3FF;
+        let pressed = self.pressed & selected;
+        if get_bit!(keycnt, 15) {
+            selected != 0 && pressed == selected
+        } else {
+            pressed != 0
+        }
+    }
+}
+
+pub fn keycnt<E: Engine, const ARM9: bool>(core: &mut Core<E>) -> u16 {
+    core.keypad.keycnt[if ARM9 { 0 } else { 1 }]
+}
+
+pub fn keycnt_set<E: Engine, const ARM9: bool>(core: &mut Core<E>, val: u16) {
+    core.keypad.keycnt[if ARM9 { 0 } else { 1 }] = val & 0xC3FF;
+    check_irqs(core);
+}
+
+fn check_irqs<E: Engine>(core: &mut Core<E>) {
+    if core.keypad.irq_condition(0) {
+        irq::request::<E, true>(core, Interrupt::Keypad);
+    }
+    if core.keypad.irq_condition(1) {
+        irq::request::<E, false>(core, Interrupt::Keypad);
+    }
+}
+
+impl<E: Engine> Core<E> {
+    /// Set the keys that are held down, see [`keys`].
+    pub fn set_keys(&mut self, pressed: Keys) {
+        self.keypad.pressed = pressed;
+        check_irqs(self);
+    }
+
+    /// Set the position of the pen on the bottom screen, `None` when it's lifted.
+    pub fn set_touch(&mut self, touch: Option<(u8, u8)>) {
+        let touch = touch.map(|(x, y)| (x, y.min(191)));
+        self.keypad.touch = touch;
+        self.spi.tsc.set_touch(touch);
+    }
+
+    pub fn set_lid_closed(&mut self, closed: bool) {
+        let opened = self.keypad.lid_closed && !closed;
+        self.keypad.lid_closed = closed;
+        if opened {
+            irq::request::<E, false>(self, Interrupt::ScreensUnfolding);
+        }
+    }
+}
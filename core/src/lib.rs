@@ -13,6 +13,9 @@ mod macros;
 #[cfg(feature = "log")]
 extern crate slog;
 
+pub mod config;
+pub use config::{CoreConfig, MainMemorySize, MemoryFill, MicSource, RtcSource};
+
 pub mod debug;
 pub mod error;
 
@@ -20,17 +23,67 @@ pub mod interpreter;
 pub use interpreter::Interpreter;
 
 pub mod cpu;
+pub use cpu::arm7::Arm7;
 pub use cpu::arm9::Arm9;
 
 // components
 mod bus;
 
-mod cartridge;
+mod irq;
+pub use irq::Interrupt;
+
+mod dma;
+
+mod ipc;
+use ipc::Ipc;
+
+mod math;
+
+mod keypad;
+pub use keypad::keys;
+use keypad::Keypad;
+
+pub mod firmware;
+
+mod mic;
+use mic::Mic;
+
+mod spi;
+use spi::Spi;
+
+mod power;
+use power::Power;
+
+mod rtc;
+use rtc::Rtc;
+
+pub mod audio;
+
+mod spu;
+use spu::Spu;
+pub use spu::{Resampler, NATIVE_SAMPLE_RATE};
+
+mod wifi;
+use wifi::Wifi;
+pub use wifi::{ChannelTransport, UdpTransport, WifiTransport};
+
+mod scheduler;
+use scheduler::Scheduler;
+
+mod timers;
+
+mod gamecard;
+use gamecard::Gamecard;
+pub use gamecard::{SaveFile, SaveType};
+
+pub mod cartridge;
 pub use cartridge::{Cartridge, CartridgeHeader};
 
 // utility
+mod crc;
+
 mod mmap;
-use mmap::{MAIN_MEMORY_END, MAIN_MEMORY_START};
+use mmap::MAIN_MEMORY_START;
 
 mod unsafemem;
 use unsafemem::UnsafeMem;
@@ -53,7 +106,22 @@ pub trait Engine {
 pub struct Core<E: Engine> {
     global_data: E::GlobalData,
     pub arm9: Arm9<E>,
-    main_memory: UnsafeMem<[u8; mb!(4)]>,
+    pub arm7: Arm7<E>,
+    scheduler: Scheduler,
+    ipc: Ipc,
+    keypad: Keypad,
+    power: Power,
+    spi: Spi,
+    mic: Mic,
+    rtc: Rtc,
+    spu: Spu,
+    wifi: Wifi,
+    gamecard: Gamecard,
+    config: CoreConfig,
+    main_memory: UnsafeMem<[u8]>,
+    shared_wram: UnsafeMem<[u8; kb!(32)]>,
+    arm7_wram: UnsafeMem<[u8; kb!(64)]>,
+    vram: UnsafeMem<[u8; kb!(656)]>,
     logger: Logger,
 }
 
@@ -0,0 +1,273 @@
+use crate::scheduler::{Timestamp, BUS_CYCLE};
+
+/// The ARM9 hardware division (DIVCNT at 0x04000280) and square root (SQRTCNT at 0x040002B0)
+/// units. Results are calculated right away, the busy flags are kept set for the latency of
+/// the hardware.
+#[derive(Default)]
+pub struct Math {
+    div_cnt: u16,
+    div_numer: u64,
+    div_denom: u64,
+    div_result: u64,
+    divrem_result: u64,
+    div_done_at: Timestamp,
+    sqrt_cnt: u16,
+    sqrt_param: u64,
+    sqrt_result: u32,
+    sqrt_done_at: Timestamp,
+}
+
+impl Math {
+    const DIV_32_LATENCY: Timestamp = 18 * BUS_CYCLE;
+    const DIV_64_LATENCY: Timestamp = 34 * BUS_CYCLE;
+    const SQRT_LATENCY: Timestamp = 13 * BUS_CYCLE;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read the word at `adr`, which is in 0x04000280..=0x040002BC.
+    pub fn read(&self, now: Timestamp, adr: u32) -> u32 {
+        match adr & 0x3F {
+            0x00 => {
+                let mut cnt = self.div_cnt;
+                toggle_bit!(cnt, 14, self.div_denom == 0);
+                toggle_bit!(cnt, 15, now < self.div_done_at);
+                cnt as u32
+            }
+            0x10 => self.div_numer as u32,
+            0x14 => (self.div_numer >> 32) as u32,
+            0x18 => self.div_denom as u32,
+            0x1C => (self.div_denom >> 32) as u32,
+            0x20 => self.div_result as u32,
+            0x24 => (self.div_result >> 32) as u32,
+            0x28 => self.divrem_result as u32,
+            0x2C => (self.divrem_result >> 32) as u32,
+            0x30 => {
+                let mut cnt = self.sqrt_cnt;
+                toggle_bit!(cnt, 15, now < self.sqrt_done_at);
+                cnt as u32
+            }
+            0x34 => self.sqrt_result,
+            0x38 => self.sqrt_param as u32,
+            0x3C => (self.sqrt_param >> 32) as u32,
+            _ => 0,
+        }
+    }
+
+    /// Write the bits in `mask` of the word at `adr`, which is in 0x04000280..=0x040002BC.
+    /// Writing to the control or an operand restarts the unit.
+    pub fn write(&mut self, now: Timestamp, adr: u32, val: u32, mask: u32) {
+        fn masked_lo(old: u64, val: u32, mask: u32) -> u64 {
+            (old & !(mask as u64)) | (val & mask) as u64
+        }
+        fn masked_hi(old: u64, val: u32, mask: u32) -> u64 {
+            (old & !((mask as u64) << 32)) | (((val & mask) as u64) << 32)
+        }
+        match adr & 0x3F {
+            0x00 => {
+                self.div_cnt = (masked_lo(self.div_cnt as u64, val, mask) & 0b11) as u16;
+                self.divide(now);
+            }
+            0x10 => {
+                self.div_numer = masked_lo(self.div_numer, val, mask);
+                self.divide(now);
+            }
+            0x14 => {
+                self.div_numer = masked_hi(self.div_numer, val, mask);
+                self.divide(now);
+            }
+            0x18 => {
+                self.div_denom = masked_lo(self.div_denom, val, mask);
+                self.divide(now);
+            }
+            0x1C => {
+                self.div_denom = masked_hi(self.div_denom, val, mask);
+                self.divide(now);
+            }
+            0x30 => {
+                self.sqrt_cnt = (masked_lo(self.sqrt_cnt as u64, val, mask) & 0b1) as u16;
+                self.sqrt(now);
+            }
+            0x38 => {
+                self.sqrt_param = masked_lo(self.sqrt_param, val, mask);
+                self.sqrt(now);
+            }
+            0x3C => {
+                self.sqrt_param = masked_hi(self.sqrt_param, val, mask);
+                self.sqrt(now);
+            }
+            _ => {}
+        }
+    }
+
+    fn divide(&mut self, now: Timestamp) {
+        let (quot, rem, latency) = match self.div_cnt & 0b11 {
+            // 32/32
+            0 => {
+                let num = self.div_numer as u32 as i32;
+                let den = self.div_denom as u32 as i32;
+                let (quot, rem) = if den == 0 {
+                    // +-1 sign extended to 64 bits, with the upper word inverted.
+                    let quot = if num < 0 {
+                        0xFFFFFFFF_00000001
+                    } else {
+                        0x00000000_FFFFFFFF
+                    };
+                    (quot, num as i64 as u64)
+                } else if num == i32::MIN && den == -1 {
+                    (0x80000000, 0)
+                } else {
+                    ((num / den) as i64 as u64, (num % den) as i64 as u64)
+                };
+                (quot, rem, Self::DIV_32_LATENCY)
+            }
+            // 64/32 (mode 3 acts the same)
+            1 | 3 => {
+                let num = self.div_numer as i64;
+                let den = self.div_denom as u32 as i32 as i64;
+                let (quot, rem) = Self::divide64(num, den);
+                (quot, rem, Self::DIV_64_LATENCY)
+            }
+            // 64/64
+            2 => {
+                let num = self.div_numer as i64;
+                let den = self.div_denom as i64;
+                let (quot, rem) = Self::divide64(num, den);
+                (quot, rem, Self::DIV_64_LATENCY)
+            }
+            _ => unreachable!(),
+        };
+        self.div_result = quot;
+        self.divrem_result = rem;
+        self.div_done_at = now + latency;
+    }
+
+    fn divide64(num: i64, den: i64) -> (u64, u64) {
+        if den == 0 {
+            let quot: i64 = if num < 0 { 1 } else { -1 };
+            (quot as u64, num as u64)
+        } else if num == i64::MIN && den == -1 {
+            (num as u64, 0)
+        } else {
+            ((num / den) as u64, (num % den) as u64)
+        }
+    }
+
+    fn sqrt(&mut self, now: Timestamp) {
+        let param = if get_bit!(self.sqrt_cnt, 0) {
+            self.sqrt_param
+        } else {
+            self.sqrt_param as u32 as u64
+        };
+        self.sqrt_result = isqrt(param) as u32;
+        self.sqrt_done_at = now + Self::SQRT_LATENCY;
+    }
+}
+
+/// Integer square root rounded down.
+fn isqrt(val: u64) -> u64 {
+    let mut rem = val;
+    let mut root = 0;
+    let mut bit = 1 << 62;
+    while bit > val {
+        bit >>= 2;
+    }
+    while bit != 0 {
+        if rem >= root + bit {
+            rem -= root + bit;
+            root = (root >> 1) + bit;
+        } else {
+            root >>= 1;
+        }
+        bit >>= 2;
+    }
+    root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DONE: Timestamp = 1000 * BUS_CYCLE;
+
+    /// Divide with DIVCNT mode `mode`, returning DIVCNT once done, the quotient and remainder.
+    fn divide(mode: u32, num: u64, den: u64) -> (u32, u64, u64) {
+        let mut math = Math::new();
+        for (adr, val) in [
+            (0x10, num as u32),
+            (0x14, (num >> 32) as u32),
+            (0x18, den as u32),
+            (0x1C, (den >> 32) as u32),
+            (0x00, mode),
+        ] {
+            math.write(0, adr, val, u32::MAX);
+        }
+        let result = |lo| math.read(DONE, lo) as u64 | (math.read(DONE, lo + 4) as u64) << 32;
+        (math.read(DONE, 0x00), result(0x20), result(0x28))
+    }
+
+    #[test]
+    fn division() {
+        let neg = |val: i64| val as u64;
+        let cases = [
+            // 32/32, the division by zero checks the whole 64 bit denominator.
+            (0, 7, neg(-2), neg(-3), 1),
+            (0, 5, 0, 0x00000000_FFFFFFFF, 5),
+            (0, neg(-5), 0, 0xFFFFFFFF_00000001, neg(-5)),
+            (0, 5, 1 << 32, 0x00000000_FFFFFFFF, 5),
+            (0, i32::MIN as u32 as u64, u32::MAX as u64, 0x80000000, 0),
+            // 64/32
+            (1, 100, 7, 14, 2),
+            (1, 5, 0, neg(-1), 5),
+            (1, neg(-5), 0, 1, neg(-5)),
+            (1, i64::MIN as u64, u32::MAX as u64, i64::MIN as u64, 0),
+            (3, 100, 7, 14, 2),
+            // 64/64
+            (2, 1 << 40, 1 << 33, 1 << 7, 0),
+            (2, 5, 0, neg(-1), 5),
+            (2, i64::MIN as u64, neg(-1), i64::MIN as u64, 0),
+        ];
+        for (mode, num, den, quot, rem) in cases {
+            let (cnt, result_quot, result_rem) = divide(mode, num, den);
+            let case = format!("mode {mode}, {num:x} / {den:x}");
+            assert_eq!(result_quot, quot, "{case}");
+            assert_eq!(result_rem, rem, "{case}");
+            assert_eq!(cnt & 0b11, mode, "{case}");
+            assert_eq!(get_bit!(cnt, 14), den == 0, "{case}");
+            assert!(!get_bit!(cnt, 15), "{case}");
+        }
+    }
+
+    #[test]
+    fn busy_until_done() {
+        let mut math = Math::new();
+        math.write(0, 0x00, 2, u32::MAX);
+        assert!(get_bit!(math.read(0, 0x00), 15));
+        assert!(!get_bit!(math.read(Math::DIV_64_LATENCY, 0x00), 15));
+        math.write(0, 0x30, 0, u32::MAX);
+        assert!(get_bit!(math.read(0, 0x30), 15));
+        assert!(!get_bit!(math.read(Math::SQRT_LATENCY, 0x30), 15));
+    }
+
+    #[test]
+    fn square_root() {
+        let cases = [
+            // 32 bit, the upper word is ignored.
+            (0, 0x1_0000_0010, 4),
+            (0, 15, 3),
+            (0, u32::MAX as u64, 0xFFFF),
+            // 64 bit
+            (1, 0x1_0000_0000, 0x1_0000),
+            (1, u64::MAX, u32::MAX),
+            (1, 0, 0),
+        ];
+        for (mode, param, root) in cases {
+            let mut math = Math::new();
+            math.write(0, 0x38, param as u32, u32::MAX);
+            math.write(0, 0x3C, (param >> 32) as u32, u32::MAX);
+            math.write(0, 0x30, mode, u32::MAX);
+            assert_eq!(math.read(DONE, 0x34), root, "mode {mode}, sqrt {param:x}");
+        }
+    }
+}
@@ -0,0 +1,81 @@
+//! Samples of the microphone, read through the AUX channel of the touchscreen controller.
+
+use std::collections::VecDeque;
+
+use crate::scheduler::{Timestamp, ARM9_CLOCK};
+use crate::{Core, Engine, MicSource};
+
+/// Pushed samples kept before the oldest ones are dropped.
+const MAX_PUSHED: usize = 1 << 16;
+
+pub struct Mic {
+    source: MicSource,
+    /// Samples pushed through [`Core::push_mic_samples`], the front one is the current one.
+    pushed: VecDeque<i16>,
+    /// Position of the front of `pushed` in samples since power on.
+    consumed: u64,
+}
+
+impl Mic {
+    pub fn new(source: MicSource) -> Self {
+        Self {
+            source,
+            pushed: VecDeque::new(),
+            consumed: 0,
+        }
+    }
+
+    /// Position in samples of `sample_rate` at `now`.
+    fn position(now: Timestamp, sample_rate: u32) -> u64 {
+        (now as u128 * sample_rate as u128 / ARM9_CLOCK as u128) as u64
+    }
+
+    /// The 12 bit ADC value of the microphone at `now`.
+    pub fn adc(&mut self, now: Timestamp) -> u16 {
+        let sample = match &self.source {
+            MicSource::Silence => 0,
+            MicSource::Loop {
+                samples,
+                sample_rate,
+            } => match samples.len() {
+                0 => 0,
+                len => samples[(Self::position(now, *sample_rate) % len as u64) as usize],
+            },
+            MicSource::Pushed { sample_rate } => {
+                let pos = Self::position(now, *sample_rate);
+                while self.consumed < pos {
+                    if self.pushed.pop_front().is_none() {
+                        self.consumed = pos;
+                        break;
+                    }
+                    self.consumed += 1;
+                }
+                self.pushed.front().copied().unwrap_or(0)
+            }
+        };
+        ((sample as i32 + 0x8000) >> 4) as u16
+    }
+
+    fn push(&mut self, now: Timestamp, samples: &[i16]) {
+        let MicSource::Pushed { sample_rate } = self.source else {
+            return;
+        };
+        // samples pushed after running dry start playing now.
+        if self.pushed.is_empty() {
+            self.consumed = Self::position(now, sample_rate);
+        }
+        self.pushed.extend(samples);
+        let overflow = self.pushed.len().saturating_sub(MAX_PUSHED);
+        self.pushed.drain(..overflow);
+        self.consumed += overflow as u64;
+    }
+}
+
+impl<E: Engine> Core<E> {
+    /// Queue mono samples for the microphone, they are played at the rate of
+    /// [`MicSource::Pushed`] and ignored with other sources.
+    pub fn push_mic_samples(&mut self, samples: &[i16]) {
+        let now = self.scheduler.now();
+        self.mic.push(now, samples);
+    }
+}
@@ -2,5 +2,18 @@ pub mod arm9 {}
 
 pub mod arm7 {}
 
+/// VRAM banks A to I mapped for the CPU in LCDC mode, 656KiB.
+pub const VRAM_LCDC_START: u32 = 0x06800000;
+
+/// Shared WRAM mirrored up to the ARM7 WRAM, only the ARM7 sees it there.
+pub const SHARED_WRAM_START: u32 = 0x03000000;
+pub const ARM7_WRAM_START: u32 = 0x03800000;
+pub const ARM7_WRAM_REGION_END: u32 = 0x04000000;
+
 pub const MAIN_MEMORY_START: u32 = 0x02000000;
-pub const MAIN_MEMORY_END: u32 = 0x2400000;
+pub const MAIN_MEMORY_REGION_END: u32 = 0x3000000;
+
+/// End of the main memory for a given memory size, not including mirrors.
+pub const fn main_memory_end(len: usize) -> u32 {
+    MAIN_MEMORY_START + len as u32
+}
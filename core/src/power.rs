@@ -0,0 +1,75 @@
+//! POWCNT1 of the ARM9, POWCNT2 of the ARM7, POSTFLG of both CPUs and HALTCNT of the ARM7.
+
+use crate::cpu::arm7::Halt;
+use crate::{Core, Engine};
+
+/// Power control of the units inside the console, disabled units are stopped and their
+/// output is blank.
+pub struct Power {
+    powcnt1: u16,
+    powcnt2: u16,
+    /// POSTFLG of the ARM9 and the ARM7.
+    postflg: [u8; 2],
+}
+
+impl Default for Power {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Power {
+    pub fn new() -> Self {
+        Self {
+            powcnt1: 0,
+            powcnt2: 0,
+            postflg: [0; 2],
+        }
+    }
+
+    pub fn powcnt1(&self) -> u16 {
+        self.powcnt1
+    }
+
+    pub fn powcnt1_set(&mut self, val: u16) {
+        self.powcnt1 = val & 0x820F;
+    }
+
+    pub fn powcnt2(&self) -> u16 {
+        self.powcnt2
+    }
+
+    pub fn powcnt2_set(&mut self, val: u16) {
+        self.powcnt2 = val & 0b11;
+    }
+
+    pub fn sound_enabled(&self) -> bool {
+        get_bit!(self.powcnt2, 0)
+    }
+
+    pub fn wifi_enabled(&self) -> bool {
+        get_bit!(self.powcnt2, 1)
+    }
+}
+
+pub fn postflg<E: Engine, const ARM9: bool>(core: &mut Core<E>) -> u8 {
+    core.power.postflg[if ARM9 { 0 } else { 1 }]
+}
+
+/// Bit 0 can only be set, bit 1 only exists on the ARM9.
+pub fn postflg_set<E: Engine, const ARM9: bool>(core: &mut Core<E>, val: u8) {
+    let postflg = &mut core.power.postflg[if ARM9 { 0 } else { 1 }];
+    let bit1 = if ARM9 { val & 0b10 } else { 0 };
+    *postflg = (*postflg & 0b1) | (val & 0b1) | bit1;
+}
+
+/// HALTCNT, stops the ARM7 until an interrupt wakes it up.
+pub fn haltcnt_set<E: Engine>(core: &mut Core<E>, val: u8) {
+    match val >> 6 {
+        0 => {}
+        1 => warn!(core.arm7.logger, "switching to GBA mode is not supported"),
+        2 => core.arm7.halt = Halt::Halted,
+        3 => core.arm7.halt = Halt::Sleeping,
+        _ => unreachable!(),
+    }
+}
@@ -0,0 +1,443 @@
+//! The Seiko S-3511 real-time clock, driven bit by bit through the ARM7 RTC register at
+//! 0x04000138.
+
+use crate::irq::{self, Interrupt};
+use crate::scheduler::{Event, Timestamp, ARM9_CLOCK};
+use crate::{Core, Engine, RtcSource};
+
+/// Interval the INT pin conditions are checked at, fast enough for the 16Hz frequency interrupt.
+const TICK: Timestamp = ARM9_CLOCK / 32;
+
+/// Seconds from the Unix epoch to 2000-01-01, the earliest date the RTC can hold.
+const Y2K: i64 = 946_684_800;
+
+#[derive(Debug, Clone, Copy)]
+struct Command {
+    reg: u8,
+    read: bool,
+}
+
+pub struct Rtc {
+    source: RtcSource,
+    /// Seconds added to the time source, changed by writes of the date and time.
+    offset: i64,
+    status1: u8,
+    status2: u8,
+    /// Alarm 1 (also the INT1 frequency setting) and alarm 2: weekday, hour and minute.
+    alarms: [[u8; 3]; 2],
+    clock_adjust: u8,
+    free: u8,
+    /// Pin values and directions last written to the register.
+    pins: u8,
+    /// Data bit driven by the RTC.
+    output: bool,
+    command: Option<Command>,
+    shift: u8,
+    bits: u8,
+    /// Bytes of the register being transferred and the current one.
+    buf: [u8; 7],
+    pos: usize,
+    ticking: bool,
+    ticks: u64,
+    last_minute: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct DateTime {
+    year: i64,
+    month: u8,
+    day: u8,
+    /// 0 is sunday.
+    weekday: u8,
+    hour: u8,
+    minute: u8,
+    second: u8,
+}
+
+impl DateTime {
+    fn from_seconds(secs: i64) -> Self {
+        let days = secs.div_euclid(86400);
+        let time = secs.rem_euclid(86400);
+        let (year, month, day) = civil_from_days(days);
+        Self {
+            year,
+            month,
+            day,
+            weekday: (days + 4).rem_euclid(7) as u8,
+            hour: (time / 3600) as u8,
+            minute: (time / 60 % 60) as u8,
+            second: (time % 60) as u8,
+        }
+    }
+
+    fn to_seconds(self) -> i64 {
+        let days = days_from_civil(self.year, self.month, self.day);
+        days * 86400 + self.hour as i64 * 3600 + self.minute as i64 * 60 + self.second as i64
+    }
+}
+
+// date conversions from http://howardhinnant.github.io/date_algorithms.html
+fn days_from_civil(year: i64, month: u8, day: u8) -> i64 {
+    let (month, day) = (month as i64, day as i64);
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let doy = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+fn civil_from_days(days: i64) -> (i64, u8, u8) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let doe = days - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u8;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u8;
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    (year, month, day)
+}
+
+fn bcd(val: u8) -> u8 {
+    ((val / 10) << 4) | (val % 10)
+}
+
+fn from_bcd(val: u8) -> u8 {
+    (val >> 4) * 10 + (val & 0xF)
+}
+
+impl Rtc {
+    pub fn new(source: RtcSource) -> Self {
+        Self {
+            source,
+            offset: 0,
+            // 24 hour mode, the time is valid as if the firmware had set it.
+            status1: 0b10,
+            status2: 0,
+            alarms: [[0; 3]; 2],
+            clock_adjust: 0,
+            free: 0,
+            pins: 0,
+            output: false,
+            command: None,
+            shift: 0,
+            bits: 0,
+            buf: [0; 7],
+            pos: 0,
+            ticking: false,
+            ticks: 0,
+            last_minute: 0,
+        }
+    }
+
+    fn source_seconds(&self, now: Timestamp) -> i64 {
+        match self.source {
+            RtcSource::HostClock => std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|time| time.as_secs() as i64)
+                .unwrap_or(Y2K),
+            RtcSource::Fixed { start } => start + (now / ARM9_CLOCK) as i64,
+        }
+    }
+
+    /// Current time in seconds since the Unix epoch.
+    fn seconds(&self, now: Timestamp) -> i64 {
+        self.source_seconds(now) + self.offset
+    }
+
+    fn set_seconds(&mut self, now: Timestamp, secs: i64) {
+        self.offset = secs - self.source_seconds(now);
+    }
+
+    #[inline]
+    fn hour_24(&self) -> bool {
+        get_bit!(self.status1, 1)
+    }
+
+    #[inline]
+    fn int1_mode(&self) -> u8 {
+        self.status2 & 0xF
+    }
+
+    /// INT1 is in one of the selected frequency modes, alarm 1 holds the frequency instead.
+    #[inline]
+    fn int1_frequency(&self) -> bool {
+        self.int1_mode() & 0b1011 == 0b0001
+    }
+
+    fn interrupts_enabled(&self) -> bool {
+        let mode = self.int1_mode();
+        (mode != 0 && mode & 0b1000 == 0) || get_bit!(self.status2, 6)
+    }
+
+    fn encode_hour(&self, hour: u8) -> u8 {
+        // the AM/PM flag is set in both modes.
+        let pm = if hour >= 12 { 0x40 } else { 0 };
+        if self.hour_24() {
+            bcd(hour) | pm
+        } else {
+            bcd(hour % 12) | pm
+        }
+    }
+
+    fn decode_hour(&self, val: u8) -> u8 {
+        let hour = from_bcd(val & 0x3F);
+        if self.hour_24() {
+            hour.min(23)
+        } else {
+            hour % 12 + if get_bit!(val, 6) { 12 } else { 0 }
+        }
+    }
+
+    fn encode_time(&self, time: DateTime) -> [u8; 3] {
+        [
+            self.encode_hour(time.hour),
+            bcd(time.minute),
+            bcd(time.second),
+        ]
+    }
+
+    /// Bytes of register `reg`.
+    fn len(&self, reg: u8) -> usize {
+        match reg {
+            2 => 7,
+            3 | 5 => 3,
+            4 if self.int1_frequency() => 1,
+            4 => 3,
+            _ => 1,
+        }
+    }
+
+    fn reset(&mut self, now: Timestamp) {
+        self.status1 = 0;
+        self.status2 = 0;
+        self.alarms = [[0; 3]; 2];
+        self.clock_adjust = 0;
+        self.free = 0;
+        self.set_seconds(now, Y2K);
+    }
+
+    /// Load the register of a read command into the transfer buffer.
+    fn read_reg(&mut self, now: Timestamp, reg: u8) {
+        let time = DateTime::from_seconds(self.seconds(now));
+        let encoded_time = self.encode_time(time);
+        match reg {
+            0 => {
+                self.buf[0] = self.status1;
+                // the interrupt and power flags are cleared by reading.
+                self.status1 &= 0x0F;
+            }
+            1 => self.buf[0] = self.status2,
+            2 => {
+                self.buf[0] = bcd((time.year - 2000).rem_euclid(100) as u8);
+                self.buf[1] = bcd(time.month);
+                self.buf[2] = bcd(time.day);
+                self.buf[3] = time.weekday;
+                self.buf[4..7].copy_from_slice(&encoded_time);
+            }
+            3 => self.buf[..3].copy_from_slice(&encoded_time),
+            4 => self.buf[..3].copy_from_slice(&self.alarms[0]),
+            5 => self.buf[..3].copy_from_slice(&self.alarms[1]),
+            6 => self.buf[0] = self.clock_adjust,
+            _ => self.buf[0] = self.free,
+        }
+    }
+
+    /// Apply the register of a write command once all of its bytes were received.
+    fn write_reg(&mut self, now: Timestamp, reg: u8) {
+        let buf = self.buf;
+        match reg {
+            0 => {
+                if get_bit!(buf[0], 0) {
+                    self.reset(now);
+                } else {
+                    self.status1 = (self.status1 & 0xF0) | (buf[0] & 0x0E);
+                }
+            }
+            1 => self.status2 = buf[0],
+            2 => {
+                let time = DateTime {
+                    year: 2000 + from_bcd(buf[0]) as i64,
+                    month: from_bcd(buf[1]).clamp(1, 12),
+                    day: from_bcd(buf[2]).clamp(1, 31),
+                    // the weekday follows from the date.
+                    weekday: 0,
+                    hour: self.decode_hour(buf[4]),
+                    minute: from_bcd(buf[5]).min(59),
+                    second: from_bcd(buf[6]).min(59),
+                };
+                self.set_seconds(now, time.to_seconds());
+            }
+            3 => {
+                let time = DateTime {
+                    hour: self.decode_hour(buf[0]),
+                    minute: from_bcd(buf[1]).min(59),
+                    second: from_bcd(buf[2]).min(59),
+                    ..DateTime::from_seconds(self.seconds(now))
+                };
+                self.set_seconds(now, time.to_seconds());
+            }
+            4 => {
+                let len = self.len(4);
+                self.alarms[0][..len].copy_from_slice(&buf[..len]);
+            }
+            5 => self.alarms[1].copy_from_slice(&buf[..3]),
+            6 => self.clock_adjust = buf[0],
+            _ => self.free = buf[0],
+        }
+    }
+
+    /// A bit was clocked in by the CPU on a rising edge of SCK.
+    fn clock_in(&mut self, now: Timestamp, bit: bool) {
+        if self.command.is_some_and(|command| command.read) {
+            return;
+        }
+        self.shift |= (bit as u8) << self.bits;
+        self.bits += 1;
+        if self.bits < 8 {
+            return;
+        }
+        let byte = self.shift;
+        self.shift = 0;
+        self.bits = 0;
+
+        match self.command {
+            None => {
+                // the command is accepted in either bit order.
+                let byte = if byte & 0xF == 0x6 {
+                    byte
+                } else if byte >> 4 == 0x6 {
+                    byte.reverse_bits()
+                } else {
+                    return;
+                };
+                let command = Command {
+                    reg: (byte >> 4) & 0b111,
+                    read: get_bit!(byte, 7),
+                };
+                self.command = Some(command);
+                self.pos = 0;
+                if command.read {
+                    self.read_reg(now, command.reg);
+                }
+            }
+            Some(command) => {
+                let len = self.len(command.reg);
+                if self.pos < len {
+                    self.buf[self.pos] = byte;
+                    self.pos += 1;
+                    if self.pos == len {
+                        self.write_reg(now, command.reg);
+                    }
+                }
+            }
+        }
+    }
+
+    /// The next bit of a read is put on the data pin on a falling edge of SCK.
+    fn clock_out(&mut self) {
+        let Some(command) = self.command.filter(|command| command.read) else {
+            return;
+        };
+        self.output =
+            self.pos < self.len(command.reg) && (self.buf[self.pos] >> self.bits) & 1 != 0;
+        self.bits += 1;
+        if self.bits == 8 {
+            self.bits = 0;
+            self.pos += 1;
+        }
+    }
+
+    fn end_transfer(&mut self) {
+        self.command = None;
+        self.shift = 0;
+        self.bits = 0;
+        self.pos = 0;
+    }
+
+    /// The alarm `index` matches `time`, fields are only compared if their enable bit is set.
+    fn alarm_matches(&self, index: usize, time: DateTime) -> bool {
+        let [weekday, hour, minute] = self.alarms[index];
+        (!get_bit!(weekday, 7) || weekday & 0b111 == time.weekday)
+            && (!get_bit!(hour, 7) || self.decode_hour(hour & 0x7F) == time.hour)
+            && (!get_bit!(minute, 7) || from_bcd(minute & 0x7F) == time.minute)
+    }
+}
+
+pub fn cnt<E: Engine>(core: &mut Core<E>) -> u16 {
+    let rtc = &core.rtc;
+    let mut cnt = rtc.pins as u16;
+    // the data pin reads the RTC output while it's an input.
+    if !get_bit!(rtc.pins, 4) {
+        toggle_bit!(cnt, 0, rtc.output);
+    }
+    cnt
+}
+
+pub fn cnt_set<E: Engine>(core: &mut Core<E>, val: u16) {
+    let now = core.scheduler.now();
+    let rtc = &mut core.rtc;
+    let old = rtc.pins;
+    rtc.pins = val as u8 & 0x77;
+    if !get_bit!(rtc.pins, 2) {
+        rtc.end_transfer();
+    } else if get_bit!(old, 2) {
+        let (old_sck, sck) = (get_bit!(old, 1), get_bit!(rtc.pins, 1));
+        if !old_sck && sck {
+            rtc.clock_in(now, get_bit!(rtc.pins, 0));
+        } else if old_sck && !sck {
+            rtc.clock_out();
+        }
+    }
+
+    // start checking the INT pin conditions once an interrupt is enabled.
+    if rtc.interrupts_enabled() && !rtc.ticking {
+        rtc.ticking = true;
+        rtc.ticks = 0;
+        rtc.last_minute = rtc.seconds(now).div_euclid(60);
+        core.scheduler.schedule(now + TICK, Event::RtcTick);
+    }
+}
+
+/// Called by the scheduler while an interrupt is enabled, raises the RTC IRQ when the INT pin
+/// goes active.
+pub fn tick<E: Engine>(core: &mut Core<E>) {
+    let now = core.scheduler.now();
+    let rtc = &mut core.rtc;
+    if !rtc.interrupts_enabled() {
+        rtc.ticking = false;
+        return;
+    }
+    rtc.ticks += 1;
+    let secs = rtc.seconds(now);
+    let minute = secs.div_euclid(60);
+    let new_minute = minute != rtc.last_minute;
+    rtc.last_minute = minute;
+    let time = DateTime::from_seconds(secs);
+
+    let int1 = match rtc.int1_mode() {
+        // interrupts at the highest frequency selected in alarm 1.
+        0b0001 | 0b0101 => match rtc.alarms[0][0] & 0x1F {
+            0 => false,
+            freq => rtc
+                .ticks
+                .is_multiple_of((32 >> (7 - freq.leading_zeros())) as u64),
+        },
+        0b0010 | 0b0110 | 0b0011 | 0b0111 => new_minute,
+        0b0100 => new_minute && rtc.alarm_matches(0, time),
+        _ => false,
+    };
+    let int2 = get_bit!(rtc.status2, 6) && new_minute && rtc.alarm_matches(1, time);
+    if int1 {
+        set_bit!(rtc.status1, 4);
+    }
+    if int2 {
+        set_bit!(rtc.status1, 5);
+    }
+    core.scheduler.schedule(now + TICK, Event::RtcTick);
+    if int1 || int2 {
+        irq::request::<E, false>(core, Interrupt::Rtc);
+    }
+}
@@ -0,0 +1,95 @@
+use crate::{gamecard, rtc, spi, spu, timers, wifi, Core, Engine};
+
+/// Time in ARM9 cycles, the system bus runs at half this rate.
+pub type Timestamp = u64;
+
+pub const ARM9_CLOCK: u64 = 67_027_964;
+
+/// Timestamps per system bus cycle.
+pub const BUS_CYCLE: Timestamp = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    TimerOverflow { arm9: bool, index: usize },
+    SpiTransferDone,
+    RtcTick,
+    SpuSample,
+    WifiTick,
+    GamecardWord,
+}
+
+pub struct Scheduler {
+    now: Timestamp,
+    /// Pending events, sorted so the next one is last.
+    events: Vec<(Timestamp, Event)>,
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self {
+            now: 0,
+            events: Vec::new(),
+        }
+    }
+
+    #[inline]
+    pub fn now(&self) -> Timestamp {
+        self.now
+    }
+
+    #[inline]
+    pub fn advance(&mut self, cycles: Timestamp) {
+        self.now += cycles;
+    }
+
+    /// Move time forward to `at`, used while the CPUs are halted.
+    #[inline]
+    pub fn skip_to(&mut self, at: Timestamp) {
+        self.now = self.now.max(at);
+    }
+
+    /// Schedule `event` at `at`, replacing it if it is already pending.
+    pub fn schedule(&mut self, at: Timestamp, event: Event) {
+        self.cancel(event);
+        let index = self.events.partition_point(|(ts, _)| *ts > at);
+        self.events.insert(index, (at, event));
+    }
+
+    pub fn cancel(&mut self, event: Event) {
+        self.events.retain(|(_, pending)| *pending != event);
+    }
+
+    pub fn next_event_at(&self) -> Option<Timestamp> {
+        self.events.last().map(|(ts, _)| *ts)
+    }
+
+    fn pop_due(&mut self) -> Option<Event> {
+        match self.events.last() {
+            Some((ts, _)) if *ts <= self.now => self.events.pop().map(|(_, event)| event),
+            _ => None,
+        }
+    }
+}
+
+/// Handle every event that is due.
+pub fn handle_events<E: Engine>(core: &mut Core<E>) {
+    while let Some(event) = core.scheduler.pop_due() {
+        match event {
+            Event::TimerOverflow { arm9: true, index } => timers::overflow::<E, true>(core, index),
+            Event::TimerOverflow { arm9: false, index } => {
+                timers::overflow::<E, false>(core, index)
+            }
+            Event::SpiTransferDone => spi::transfer_done(core),
+            Event::RtcTick => rtc::tick(core),
+            Event::SpuSample => spu::sample(core),
+            Event::WifiTick => wifi::tick(core),
+            Event::GamecardWord => gamecard::word_ready(core),
+        }
+    }
+}
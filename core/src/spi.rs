@@ -0,0 +1,128 @@
+//! The ARM7 SPI bus (SPICNT/SPIDATA) and the devices on it.
+
+mod flash;
+pub use flash::Flash;
+
+mod power;
+pub use power::PowerMan;
+
+mod tsc;
+pub use tsc::Tsc;
+
+use crate::irq::{self, Interrupt};
+use crate::scheduler::{Event, Timestamp, BUS_CYCLE};
+use crate::{Core, Engine};
+
+/// A device on a serial bus, a transfer clocks a byte out while clocking one in.
+pub trait SpiDevice {
+    fn transfer(&mut self, val: u8) -> u8;
+
+    /// Chip select was released, ending the current command.
+    fn deselect(&mut self);
+}
+
+pub struct Spi {
+    cnt: u16,
+    data: u8,
+    /// Device whose chip select is being held.
+    selected: Option<usize>,
+    busy_until: Timestamp,
+    pub power: PowerMan,
+    pub firmware: Flash,
+    pub tsc: Tsc,
+}
+
+impl Spi {
+    pub fn new(firmware: Flash) -> Self {
+        Self {
+            cnt: 0,
+            data: 0,
+            selected: None,
+            busy_until: 0,
+            power: PowerMan::new(),
+            firmware,
+            tsc: Tsc::new(),
+        }
+    }
+
+    fn device(&mut self, index: usize) -> Option<&mut dyn SpiDevice> {
+        match index {
+            0 => Some(&mut self.power),
+            1 => Some(&mut self.firmware),
+            2 => Some(&mut self.tsc),
+            _ => None,
+        }
+    }
+
+    fn deselect(&mut self) {
+        if let Some(index) = self.selected.take() {
+            if let Some(device) = self.device(index) {
+                device.deselect();
+            }
+        }
+    }
+
+    #[inline]
+    fn enabled(&self) -> bool {
+        get_bit!(self.cnt, 15)
+    }
+}
+
+pub fn cnt<E: Engine>(core: &mut Core<E>) -> u16 {
+    let spi = &core.spi;
+    let mut cnt = spi.cnt;
+    toggle_bit!(cnt, 7, core.scheduler.now() < spi.busy_until);
+    cnt
+}
+
+pub fn cnt_set<E: Engine>(core: &mut Core<E>, val: u16) {
+    let spi = &mut core.spi;
+    spi.cnt = val & 0xCF03;
+    if !spi.enabled() {
+        spi.deselect();
+    }
+}
+
+pub fn data<E: Engine>(core: &mut Core<E>) -> u8 {
+    core.spi.data
+}
+
+/// Writing SPIDATA starts a transfer with the selected device.
+pub fn data_set<E: Engine>(core: &mut Core<E>, val: u8) {
+    let now = core.scheduler.now();
+    // the microphone is sampled when the touchscreen controller is accessed.
+    if (core.spi.cnt >> 8) & 0b11 == 2 {
+        let aux = core.mic.adc(now);
+        core.spi.tsc.set_aux(aux);
+    }
+    let spi = &mut core.spi;
+    if !spi.enabled() {
+        return;
+    }
+    let index = (spi.cnt as usize >> 8) & 0b11;
+    if spi.selected.is_some_and(|selected| selected != index) {
+        spi.deselect();
+    }
+    spi.data = match spi.device(index) {
+        Some(device) => device.transfer(val),
+        None => 0,
+    };
+    // chip select stays low after the transfer if it's held.
+    spi.selected = Some(index);
+    if !get_bit!(spi.cnt, 11) {
+        spi.deselect();
+    }
+
+    // 8 bits at 4MHz >> baudrate.
+    let bit_cycles = (8 << (spi.cnt & 0b11)) * BUS_CYCLE;
+    let busy_until = now + 8 * bit_cycles;
+    spi.busy_until = busy_until;
+    core.scheduler.schedule(busy_until, Event::SpiTransferDone);
+}
+
+/// Called by the scheduler when a transfer finishes.
+pub fn transfer_done<E: Engine>(core: &mut Core<E>) {
+    if get_bit!(core.spi.cnt, 14) {
+        irq::request::<E, false>(core, Interrupt::Spi);
+    }
+}
@@ -0,0 +1,154 @@
+use super::SpiDevice;
+
+/// Serial flash memory, used for the firmware.
+pub struct Flash {
+    data: Box<[u8]>,
+    jedec_id: [u8; 3],
+    status: u8,
+    cmd: Option<u8>,
+    adr: u32,
+    /// Bytes transferred since the command byte.
+    pos: usize,
+    powered_down: bool,
+    dirty: bool,
+}
+
+impl Flash {
+    const STATUS_WEL: u8 = b!(1);
+
+    const CMD_WREN: u8 = 0x06;
+    const CMD_WRDI: u8 = 0x04;
+    const CMD_RDID: u8 = 0x9F;
+    const CMD_RDSR: u8 = 0x05;
+    const CMD_READ: u8 = 0x03;
+    const CMD_FAST_READ: u8 = 0x0B;
+    const CMD_PW: u8 = 0x0A;
+    const CMD_PP: u8 = 0x02;
+    const CMD_PE: u8 = 0xDB;
+    const CMD_SE: u8 = 0xD8;
+    const CMD_DP: u8 = 0xB9;
+    const CMD_RDP: u8 = 0xAB;
+
+    const PAGE_LEN: u32 = 0x100;
+    const SECTOR_LEN: u32 = 0x10000;
+
+    /// `data` has to be a power of two long.
+    pub fn new(data: Box<[u8]>, jedec_id: [u8; 3]) -> Self {
+        debug_assert!(data.len().is_power_of_two());
+        Self {
+            data,
+            jedec_id,
+            status: 0,
+            cmd: None,
+            adr: 0,
+            pos: 0,
+            powered_down: false,
+            dirty: false,
+        }
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// The contents were changed since the last call.
+    pub fn take_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.dirty)
+    }
+
+    #[inline]
+    fn offs(&self) -> usize {
+        self.adr as usize & (self.data.len() - 1)
+    }
+
+    fn write_enabled(&self) -> bool {
+        self.status & Self::STATUS_WEL != 0
+    }
+
+    fn erase(&mut self, len: u32) {
+        if self.write_enabled() {
+            let start = self.offs() & !(len as usize - 1);
+            let end = (start + len as usize).min(self.data.len());
+            self.data[start..end].fill(0xFF);
+            self.dirty = true;
+        }
+    }
+
+    fn page_write(&mut self, val: u8, program: bool) {
+        if self.write_enabled() {
+            let offs = self.offs();
+            // programming can only clear bits.
+            self.data[offs] = if program { self.data[offs] & val } else { val };
+            self.dirty = true;
+        }
+        // the address wraps around within the page.
+        let page = self.adr & !(Self::PAGE_LEN - 1);
+        self.adr = page | (self.adr.wrapping_add(1) & (Self::PAGE_LEN - 1));
+    }
+}
+
+impl SpiDevice for Flash {
+    fn transfer(&mut self, val: u8) -> u8 {
+        let Some(cmd) = self.cmd else {
+            if self.powered_down && val != Self::CMD_RDP {
+                return 0xFF;
+            }
+            self.cmd = Some(val);
+            self.pos = 0;
+            self.adr = 0;
+            match val {
+                Self::CMD_WREN => self.status |= Self::STATUS_WEL,
+                Self::CMD_WRDI => self.status &= !Self::STATUS_WEL,
+                Self::CMD_DP => self.powered_down = true,
+                Self::CMD_RDP => self.powered_down = false,
+                _ => {}
+            }
+            return 0xFF;
+        };
+        self.pos += 1;
+        let pos = self.pos;
+        match cmd {
+            Self::CMD_RDSR => self.status,
+            Self::CMD_RDID => self.jedec_id.get(pos - 1).copied().unwrap_or(0xFF),
+            Self::CMD_READ | Self::CMD_FAST_READ | Self::CMD_PW | Self::CMD_PP => {
+                let dummy = (cmd == Self::CMD_FAST_READ) as usize;
+                if pos <= 3 {
+                    self.adr = (self.adr << 8) | val as u32;
+                    0xFF
+                } else if pos <= 3 + dummy {
+                    0xFF
+                } else if matches!(cmd, Self::CMD_READ | Self::CMD_FAST_READ) {
+                    let read = self.data[self.offs()];
+                    self.adr = self.adr.wrapping_add(1);
+                    read
+                } else {
+                    self.page_write(val, cmd == Self::CMD_PP);
+                    0xFF
+                }
+            }
+            Self::CMD_PE | Self::CMD_SE => {
+                if pos <= 3 {
+                    self.adr = (self.adr << 8) | val as u32;
+                    if pos == 3 {
+                        let len = if cmd == Self::CMD_PE {
+                            Self::PAGE_LEN
+                        } else {
+                            Self::SECTOR_LEN
+                        };
+                        self.erase(len);
+                    }
+                }
+                0xFF
+            }
+            _ => 0xFF,
+        }
+    }
+
+    fn deselect(&mut self) {
+        // writes and erases finish when chip select is released, which clears the latch.
+        if let Some(Self::CMD_PW | Self::CMD_PP | Self::CMD_PE | Self::CMD_SE) = self.cmd {
+            self.status &= !Self::STATUS_WEL;
+        }
+        self.cmd = None;
+    }
+}
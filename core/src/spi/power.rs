@@ -0,0 +1,79 @@
+use super::SpiDevice;
+
+/// The power management chip, registers are accessed with an index byte (bit 7 set to read)
+/// followed by the data byte.
+pub struct PowerMan {
+    regs: [u8; 5],
+    index: Option<u8>,
+    power_off: bool,
+}
+
+impl Default for PowerMan {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PowerMan {
+    const CONTROL: usize = 0;
+    const BATTERY: usize = 1;
+
+    pub fn new() -> Self {
+        Self {
+            // sound amplifier and both backlights on.
+            regs: [0b1101, 0, 0, 0, 0],
+            index: None,
+            power_off: false,
+        }
+    }
+
+    /// The system power off bit was written.
+    pub fn power_off(&self) -> bool {
+        self.power_off
+    }
+
+    pub fn sound_amp_enabled(&self) -> bool {
+        get_bit!(self.regs[Self::CONTROL], 0)
+    }
+
+    pub fn backlights(&self) -> (bool, bool) {
+        let control = self.regs[Self::CONTROL];
+        (get_bit!(control, 3), get_bit!(control, 2))
+    }
+
+    /// Set the battery low flag.
+    pub fn battery_low_set(&mut self, low: bool) {
+        self.regs[Self::BATTERY] = low as u8;
+    }
+}
+
+impl SpiDevice for PowerMan {
+    fn transfer(&mut self, val: u8) -> u8 {
+        let Some(index) = self.index else {
+            self.index = Some(val);
+            return 0;
+        };
+        let reg = (index & 0x7F) as usize;
+        if reg >= self.regs.len() {
+            return 0;
+        }
+        if get_bit!(index, 7) {
+            self.regs[reg]
+        } else {
+            match reg {
+                Self::CONTROL => {
+                    self.regs[reg] = val & 0x7F;
+                    self.power_off |= get_bit!(val, 6);
+                }
+                // battery status is read only.
+                Self::BATTERY => {}
+                _ => self.regs[reg] = val,
+            }
+            0
+        }
+    }
+
+    fn deselect(&mut self) {
+        self.index = None;
+    }
+}
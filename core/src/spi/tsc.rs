@@ -0,0 +1,88 @@
+use super::SpiDevice;
+
+/// The TSC2046 touchscreen controller. A control byte with bit 7 set starts a conversion
+/// that's clocked out over the next two bytes.
+pub struct Tsc {
+    control: u8,
+    result: u16,
+    pos: u8,
+    touch: Option<(u8, u8)>,
+    /// Microphone sample on the AUX channel.
+    aux: u16,
+}
+
+impl Default for Tsc {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tsc {
+    const TEMP0: u16 = 0x2C0;
+    const TEMP1: u16 = 0x2F0;
+
+    pub fn new() -> Self {
+        Self {
+            control: 0,
+            result: 0,
+            pos: 0,
+            touch: None,
+            aux: 0x800,
+        }
+    }
+
+    /// Set the touch position in screen pixels, the ADC values are the pixels scaled by 16.
+    pub fn set_touch(&mut self, touch: Option<(u8, u8)>) {
+        self.touch = touch;
+    }
+
+    /// Set the 12 bit sample read from the AUX (microphone) channel.
+    pub fn set_aux(&mut self, sample: u16) {
+        self.aux = sample & 0xFFF;
+    }
+
+    fn convert(&self, channel: u8) -> u16 {
+        match (channel, self.touch) {
+            (0, _) => Self::TEMP0,
+            (1, Some((_, y))) => (y as u16) << 4,
+            (1, None) => 0xFFF,
+            // battery, not connected.
+            (2, _) => 0,
+            (3, Some(_)) => 0x800,
+            (3, None) => 0,
+            (4, Some(_)) => 0x800,
+            (4, None) => 0xFFF,
+            (5, Some((x, _))) => (x as u16) << 4,
+            (5, None) => 0,
+            (6, _) => self.aux,
+            (7, _) => Self::TEMP1,
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl SpiDevice for Tsc {
+    fn transfer(&mut self, val: u8) -> u8 {
+        let reply = match self.pos {
+            1 => (self.result >> 5) as u8,
+            2 => (self.result << 3) as u8,
+            _ => 0,
+        };
+        if get_bit!(val, 7) {
+            self.control = val;
+            self.result = self.convert((val >> 4) & 0b111);
+            // 8 bit mode drops the low bits.
+            if get_bit!(val, 3) {
+                self.result &= 0xFF0;
+            }
+            self.pos = 1;
+        } else if self.pos != 0 {
+            self.pos += 1;
+        }
+        reply
+    }
+
+    fn deselect(&mut self) {
+        self.pos = 0;
+    }
+}
@@ -0,0 +1,265 @@
+//! The sound unit of the ARM7, 16 channels mixed into a stereo output at ~32.7kHz.
+
+mod capture;
+use capture::Capture;
+
+mod channel;
+use channel::Channel;
+
+mod resample;
+pub use resample::Resampler;
+
+use std::collections::VecDeque;
+
+use crate::audio::{AudioSink, SinkOutput};
+use crate::scheduler::{Event, Timestamp, ARM9_CLOCK, BUS_CYCLE};
+use crate::{Core, Engine};
+
+/// Bus cycles per output sample.
+const SAMPLE_CYCLES: u32 = 1024;
+pub const SAMPLE_PERIOD: Timestamp = SAMPLE_CYCLES as Timestamp * BUS_CYCLE;
+/// Rate the mixer outputs at, about 32728.5Hz.
+pub const NATIVE_SAMPLE_RATE: f64 = ARM9_CLOCK as f64 / SAMPLE_PERIOD as f64;
+
+/// Samples kept while the output isn't drained, about a second.
+const MAX_BUFFERED: usize = 32768 * 2;
+
+pub struct Spu {
+    channels: [Channel; 16],
+    captures: [Capture; 2],
+    soundcnt: u16,
+    soundbias: u16,
+    /// Interleaved stereo output at the native rate.
+    output: VecDeque<i16>,
+    /// Converts the output to the rate of the config, if one is set.
+    resampler: Option<Resampler>,
+    /// Sink the output is pushed to instead of being buffered, if one is attached.
+    sink: Option<SinkOutput>,
+}
+
+impl Spu {
+    pub fn new(sample_rate: Option<u32>) -> Self {
+        Self {
+            channels: Default::default(),
+            captures: Default::default(),
+            soundcnt: 0,
+            // as set by the firmware.
+            soundbias: 0x200,
+            output: VecDeque::with_capacity(MAX_BUFFERED),
+            resampler: sample_rate.map(|rate| Resampler::new(NATIVE_SAMPLE_RATE, rate as f64)),
+            sink: None,
+        }
+    }
+
+    #[inline]
+    fn enabled(&self) -> bool {
+        get_bit!(self.soundcnt, 15)
+    }
+
+    /// Master volume 0-127, applied as N/128.
+    #[inline]
+    fn master_volume(&self) -> i32 {
+        (self.soundcnt & 0x7F) as i32
+    }
+
+    /// Read the word at `adr`, which is in 0x04000400..=0x0400051C.
+    pub fn read(&self, adr: u32) -> u32 {
+        match adr {
+            // everything but SOUNDxCNT is write only.
+            0x04000400..=0x040004FC if adr & 0xF == 0 => {
+                self.channels[(adr as usize >> 4) & 0xF].cnt
+            }
+            0x04000500 => self.soundcnt as u32,
+            0x04000504 => self.soundbias as u32,
+            0x04000508 => self.captures[0].cnt as u32 | (self.captures[1].cnt as u32) << 8,
+            _ => 0,
+        }
+    }
+
+    /// Write the bits in `mask` of the word at `adr`, which is in 0x04000400..=0x0400051C.
+    pub fn write(&mut self, adr: u32, val: u32, mask: u32) {
+        fn masked(old: u32, val: u32, mask: u32) -> u32 {
+            (old & !mask) | (val & mask)
+        }
+        match adr {
+            0x04000400..=0x040004FC => {
+                let channel = &mut self.channels[(adr as usize >> 4) & 0xF];
+                match adr & 0xF {
+                    0x0 => {
+                        let was_busy = channel.busy();
+                        channel.cnt = masked(channel.cnt, val, mask) & 0xFF7F837F;
+                        if channel.busy() && !was_busy {
+                            channel.start();
+                        } else if !channel.busy() && was_busy {
+                            channel.stop();
+                        }
+                    }
+                    0x4 => channel.sad = masked(channel.sad, val, mask) & 0x07FFFFFC,
+                    0x8 => {
+                        let tmr_pnt = channel.tmr as u32 | (channel.pnt as u32) << 16;
+                        let tmr_pnt = masked(tmr_pnt, val, mask);
+                        channel.tmr = tmr_pnt as u16;
+                        channel.pnt = (tmr_pnt >> 16) as u16;
+                    }
+                    _ => channel.len = masked(channel.len, val, mask) & 0x3FFFFF,
+                }
+            }
+            0x04000500 => self.soundcnt = masked(self.soundcnt as u32, val, mask) as u16 & 0xBF7F,
+            0x04000504 => self.soundbias = masked(self.soundbias as u32, val, mask) as u16 & 0x3FF,
+            0x04000508 => {
+                for (i, capture) in self.captures.iter_mut().enumerate() {
+                    let shift = i * 8;
+                    if (mask >> shift) & 0xFF != 0 {
+                        capture.cnt_set((val >> shift) as u8);
+                    }
+                }
+            }
+            0x04000510 | 0x04000518 => {
+                let capture = &mut self.captures[(adr as usize >> 3) & 0b1];
+                capture.dad = masked(capture.dad, val, mask) & 0x07FFFFFC;
+            }
+            0x04000514 | 0x0400051C => {
+                let capture = &mut self.captures[(adr as usize >> 3) & 0b1];
+                capture.len = masked(capture.len as u32, val, mask) as u16;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Called by the scheduler for every output sample.
+pub fn sample<E: Engine>(core: &mut Core<E>) {
+    let now = core.scheduler.now();
+    core.scheduler
+        .schedule(now + SAMPLE_PERIOD, Event::SpuSample);
+    let frame = if core.power.sound_enabled() && core.spu.enabled() {
+        mix(core)
+    } else {
+        [0; 2]
+    };
+    if let Some(sink) = &mut core.spu.sink {
+        sink.push_frame(frame);
+        return;
+    }
+    let output = &mut core.spu.output;
+    if output.len() >= MAX_BUFFERED {
+        output.drain(..2);
+    }
+    output.extend(frame);
+}
+
+/// Run the channels and capture units for a sample and mix the output of the channels.
+fn mix<E: Engine>(core: &mut Core<E>) -> [i16; 2] {
+    let soundcnt = core.spu.soundcnt;
+    let mut values = [0; 16];
+    for (index, value) in values.iter_mut().enumerate() {
+        if core.spu.channels[index].busy() {
+            channel::run(core, index, SAMPLE_CYCLES);
+        }
+        *value = core.spu.channels[index].value();
+    }
+
+    // the capture units can add channel 1 and 3 to channel 0 and 2.
+    let [add0, add1] = core.spu.captures.map(|capture| capture.add());
+    if add0 {
+        values[0] += values[1];
+    }
+    if add1 {
+        values[2] += values[3];
+    }
+
+    let mut mixer = [0; 2];
+    let mut ch1 = [0; 2];
+    let mut ch3 = [0; 2];
+    for (index, &value) in values.iter().enumerate() {
+        let (left, right) = core.spu.channels[index].pan(value);
+        match index {
+            1 => ch1 = [left, right],
+            3 => ch3 = [left, right],
+            _ => {}
+        }
+        // channel 1 and 3 can bypass the mixer.
+        let bypass = (index == 1 && (get_bit!(soundcnt, 12) || add0))
+            || (index == 3 && (get_bit!(soundcnt, 13) || add1));
+        if !bypass {
+            mixer[0] += left;
+            mixer[1] += right;
+        }
+    }
+
+    // capture units run at the rate of channel 1 and 3.
+    for index in 0..2 {
+        let capture = core.spu.captures[index];
+        if !capture.busy() {
+            continue;
+        }
+        let source = if capture.channel_source() {
+            values[index * 2]
+        } else {
+            mixer[index]
+        };
+        let sample = source.clamp(i16::MIN as i32, i16::MAX as i32) as i16;
+        let period = core.spu.channels[index * 2 + 1].period();
+        capture::run(core, index, SAMPLE_CYCLES, period, sample);
+    }
+
+    let select = |sel: u16, side: usize| match sel & 0b11 {
+        0 => mixer[side],
+        1 => ch1[side],
+        2 => ch3[side],
+        _ => ch1[side] + ch3[side],
+    };
+    let master = core.spu.master_volume();
+    let bias = core.spu.soundbias as i32;
+    [select(soundcnt >> 8, 0), select(soundcnt >> 10, 1)].map(|out| {
+        // the master volume is applied as N/128/64, the result is clipped to 10 bits.
+        let out = ((out * master / 128) >> 6) + bias;
+        ((out.clamp(0, 0x3FF) - 0x200) << 6) as i16
+    })
+}
+
+impl<E: Engine> Core<E> {
+    /// Move the audio output since the last call into `out`, as interleaved stereo samples at
+    /// the sample rate of the config or [`NATIVE_SAMPLE_RATE`] if it has none.
+    pub fn drain_audio(&mut self, out: &mut Vec<i16>) {
+        let spu = &mut self.spu;
+        let samples = spu.output.make_contiguous();
+        match &mut spu.resampler {
+            Some(resampler) => resampler.process(samples, out),
+            None => out.extend_from_slice(samples),
+        }
+        spu.output.clear();
+    }
+
+    /// Push the audio output to `sink` from now on, resampled to its rate, instead of
+    /// buffering it for [`Core::drain_audio`].
+    pub fn set_audio_sink(&mut self, sink: Box<dyn AudioSink>) {
+        self.spu.sink = Some(SinkOutput::new(sink));
+    }
+
+    /// Detach the audio sink after pushing the remaining output to it.
+    pub fn take_audio_sink(&mut self) -> Option<Box<dyn AudioSink>> {
+        self.spu.sink.take().map(SinkOutput::into_sink)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{scheduler, Interpreter};
+
+    #[test]
+    fn output_rate() {
+        assert_eq!(SAMPLE_PERIOD, 2048);
+        assert!((NATIVE_SAMPLE_RATE - 32728.5).abs() < 0.1);
+
+        let mut core = Core::<Interpreter>::new(slog::Logger::root(slog::Discard, slog::o!()));
+        while let Some(at) = core.scheduler.next_event_at().filter(|&at| at < ARM9_CLOCK) {
+            core.scheduler.skip_to(at);
+            scheduler::handle_events(&mut core);
+        }
+        let mut out = Vec::new();
+        core.drain_audio(&mut out);
+        assert_eq!(out.len(), 32728 * 2);
+    }
+}
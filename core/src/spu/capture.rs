@@ -0,0 +1,84 @@
+use crate::bus::arm7;
+use crate::{Core, Engine};
+
+/// Registers and state of a capture unit (SNDCAPxCNT/DAD/LEN). Unit 0 belongs to channels 0 and
+/// 1 and the left mixer, unit 1 to channels 2 and 3 and the right mixer.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Capture {
+    pub cnt: u8,
+    pub dad: u32,
+    pub len: u16,
+    /// Bytes written.
+    pos: u32,
+    /// Bus cycles towards the next sample.
+    counter: u32,
+}
+
+impl Capture {
+    #[inline]
+    pub fn busy(&self) -> bool {
+        get_bit!(self.cnt, 7)
+    }
+
+    /// The output of channel 1 (3) is added to channel 0 (2) instead of being output as such.
+    #[inline]
+    pub fn add(&self) -> bool {
+        self.busy() && get_bit!(self.cnt, 0)
+    }
+
+    /// Channel 0 (2) is captured instead of the left (right) mixer.
+    #[inline]
+    pub fn channel_source(&self) -> bool {
+        get_bit!(self.cnt, 1)
+    }
+
+    #[inline]
+    fn one_shot(&self) -> bool {
+        get_bit!(self.cnt, 2)
+    }
+
+    #[inline]
+    fn pcm8(&self) -> bool {
+        get_bit!(self.cnt, 3)
+    }
+
+    /// Buffer length in bytes, a length of 0 acts as 1 word.
+    #[inline]
+    fn len_bytes(&self) -> u32 {
+        self.len.max(1) as u32 * 4
+    }
+
+    pub fn cnt_set(&mut self, val: u8) {
+        let was_busy = self.busy();
+        self.cnt = val & 0x8F;
+        if self.busy() && !was_busy {
+            self.pos = 0;
+            self.counter = 0;
+        }
+    }
+}
+
+/// Advance the busy capture unit `index` by `cycles` bus cycles at the rate of `period`, writing
+/// `sample` to the buffer at every step.
+pub fn run<E: Engine>(core: &mut Core<E>, index: usize, cycles: u32, period: u32, sample: i16) {
+    let mut capture = core.spu.captures[index];
+    capture.counter += cycles;
+    while capture.counter >= period && capture.busy() {
+        capture.counter -= period;
+        let adr = capture.dad + capture.pos;
+        if capture.pcm8() {
+            arm7::write8(core, adr, (sample >> 8) as u8);
+            capture.pos += 1;
+        } else {
+            arm7::write16(core, adr, sample as u16);
+            capture.pos += 2;
+        }
+        if capture.pos >= capture.len_bytes() {
+            capture.pos = 0;
+            if capture.one_shot() {
+                unset_bit!(capture.cnt, 7);
+            }
+        }
+    }
+    core.spu.captures[index] = capture;
+}
@@ -0,0 +1,284 @@
+use crate::bus::arm7;
+use crate::{Core, Engine};
+
+const ADPCM_STEPS: [u16; 89] = [
+    0x0007, 0x0008, 0x0009, 0x000A, 0x000B, 0x000C, 0x000D, 0x000E, 0x0010, 0x0011, 0x0013, 0x0015,
+    0x0017, 0x0019, 0x001C, 0x001F, 0x0022, 0x0025, 0x0029, 0x002D, 0x0032, 0x0037, 0x003C, 0x0042,
+    0x0049, 0x0050, 0x0058, 0x0061, 0x006B, 0x0076, 0x0082, 0x008F, 0x009D, 0x00AD, 0x00BE, 0x00D1,
+    0x00E6, 0x00FD, 0x0117, 0x0133, 0x0151, 0x0173, 0x0198, 0x01C1, 0x01EE, 0x0220, 0x0256, 0x0292,
+    0x02D4, 0x031C, 0x036C, 0x03C3, 0x0424, 0x048E, 0x0502, 0x0583, 0x0610, 0x06AB, 0x0756, 0x0812,
+    0x08E0, 0x09C3, 0x0ABD, 0x0BD0, 0x0CFF, 0x0E4C, 0x0FBA, 0x114C, 0x1307, 0x14EE, 0x1706, 0x1954,
+    0x1BDC, 0x1EA5, 0x21B6, 0x2515, 0x28CA, 0x2CDF, 0x315B, 0x364B, 0x3BB9, 0x41B2, 0x4844, 0x4F7E,
+    0x5771, 0x602F, 0x69CE, 0x7462, 0x7FFF,
+];
+
+const ADPCM_INDEX_STEPS: [i8; 8] = [-1, -1, -1, -1, 2, 4, 6, 8];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Pcm8,
+    Pcm16,
+    Adpcm,
+    /// Square waves on channels 8-13, noise on channels 14 and 15, nothing on the others.
+    PsgNoise,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Repeat {
+    /// Keeps playing past the end until stopped.
+    Manual,
+    Loop,
+    OneShot,
+}
+
+/// Decoder state of an IMA-ADPCM stream.
+#[derive(Debug, Default, Clone, Copy)]
+struct Adpcm {
+    value: i16,
+    index: u8,
+}
+
+impl Adpcm {
+    fn decode(&mut self, nibble: u8) {
+        let step = ADPCM_STEPS[self.index as usize] as i32;
+        let mut diff = step / 8;
+        if get_bit!(nibble, 0) {
+            diff += step / 4;
+        }
+        if get_bit!(nibble, 1) {
+            diff += step / 2;
+        }
+        if get_bit!(nibble, 2) {
+            diff += step;
+        }
+        let value = self.value as i32;
+        self.value = if get_bit!(nibble, 3) {
+            (value - diff).max(-0x7FFF)
+        } else {
+            (value + diff).min(0x7FFF)
+        } as i16;
+        let index = self.index as i32 + ADPCM_INDEX_STEPS[nibble as usize & 0b111] as i32;
+        self.index = index.clamp(0, 88) as u8;
+    }
+}
+
+/// Registers and playback state of one channel (SOUNDxCNT/SAD/TMR/PNT/LEN).
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Channel {
+    pub cnt: u32,
+    pub sad: u32,
+    pub tmr: u16,
+    pub pnt: u16,
+    pub len: u32,
+    /// Samples played, ADPCM samples are counted from after the header.
+    pos: u32,
+    /// Bus cycles towards the next sample.
+    counter: u32,
+    sample: i16,
+    adpcm: Adpcm,
+    /// ADPCM state at the loop start, restored when looping.
+    adpcm_loop: Adpcm,
+    psg_step: u8,
+    lfsr: u16,
+}
+
+impl Channel {
+    #[inline]
+    pub fn busy(&self) -> bool {
+        get_bit!(self.cnt, 31)
+    }
+
+    pub fn format(&self) -> Format {
+        match (self.cnt >> 29) & 0b11 {
+            0 => Format::Pcm8,
+            1 => Format::Pcm16,
+            2 => Format::Adpcm,
+            3 => Format::PsgNoise,
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn repeat(&self) -> Repeat {
+        match (self.cnt >> 27) & 0b11 {
+            0 => Repeat::Manual,
+            1 => Repeat::Loop,
+            // 3 is prohibited.
+            _ => Repeat::OneShot,
+        }
+    }
+
+    #[inline]
+    fn hold(&self) -> bool {
+        get_bit!(self.cnt, 15)
+    }
+
+    /// Volume factor 0-127, applied as N/128.
+    #[inline]
+    fn volume(&self) -> i32 {
+        (self.cnt & 0x7F) as i32
+    }
+
+    /// Right shift of the volume divider.
+    #[inline]
+    fn volume_shift(&self) -> u32 {
+        [0, 1, 2, 4][(self.cnt as usize >> 8) & 0b11]
+    }
+
+    /// Panning 0 (left) to 127 (right).
+    #[inline]
+    fn panning(&self) -> i32 {
+        ((self.cnt >> 16) & 0x7F) as i32
+    }
+
+    #[inline]
+    fn duty(&self) -> u8 {
+        ((self.cnt >> 24) & 0b111) as u8
+    }
+
+    /// Bus cycles per sample, the timer counts at half the bus clock.
+    #[inline]
+    pub fn period(&self) -> u32 {
+        (0x10000 - self.tmr as u32) * 2
+    }
+
+    /// Samples of the loop start and of the whole sound.
+    fn bounds(&self) -> (u32, u32) {
+        let start = self.pnt as u32 * 4;
+        let end = start + self.len * 4;
+        match self.format() {
+            Format::Pcm8 => (start, end),
+            Format::Pcm16 => (start / 2, end / 2),
+            Format::Adpcm => (start.saturating_sub(4) * 2, end.saturating_sub(4) * 2),
+            Format::PsgNoise => (0, u32::MAX),
+        }
+    }
+
+    /// Called when the channel is started, the ADPCM header is read by [`step`].
+    pub fn start(&mut self) {
+        self.pos = 0;
+        self.counter = 0;
+        self.sample = 0;
+        self.psg_step = 0;
+        self.lfsr = 0x7FFF;
+    }
+
+    /// Called when the channel is stopped by clearing the busy bit.
+    pub fn stop(&mut self) {
+        if !self.hold() {
+            self.sample = 0;
+        }
+    }
+
+    /// Output after the volume, before the panning.
+    pub fn value(&self) -> i32 {
+        (self.sample as i32 >> self.volume_shift()) * self.volume() / 128
+    }
+
+    /// Split `value` into the left and right output with the panning of the channel.
+    pub fn pan(&self, value: i32) -> (i32, i32) {
+        let pan = self.panning();
+        (value * (128 - pan) / 128, value * pan / 128)
+    }
+}
+
+/// Advance the busy channel `index` by `cycles` bus cycles.
+pub fn run<E: Engine>(core: &mut Core<E>, index: usize, cycles: u32) {
+    let mut channel = core.spu.channels[index];
+    channel.counter += cycles;
+    let period = channel.period();
+    while channel.counter >= period && channel.busy() {
+        channel.counter -= period;
+        step(core, index, &mut channel);
+    }
+    core.spu.channels[index] = channel;
+}
+
+/// Play the next sample of `channel`.
+fn step<E: Engine>(core: &mut Core<E>, index: usize, channel: &mut Channel) {
+    let (loop_start, end) = channel.bounds();
+    let sad = channel.sad;
+    let pos = channel.pos;
+    channel.sample = match channel.format() {
+        Format::Pcm8 => (arm7::read8(core, sad + pos) as i8 as i16) << 8,
+        Format::Pcm16 => arm7::read16(core, sad + pos * 2) as i16,
+        Format::Adpcm => {
+            if pos == 0 {
+                let header = arm7::read32(core, sad);
+                channel.adpcm = Adpcm {
+                    value: header as i16,
+                    index: ((header >> 16) & 0x7F).min(88) as u8,
+                };
+            }
+            if pos == loop_start {
+                channel.adpcm_loop = channel.adpcm;
+            }
+            let byte = arm7::read8(core, sad + 4 + pos / 2);
+            channel.adpcm.decode(if pos & 0b1 == 0 {
+                byte & 0xF
+            } else {
+                byte >> 4
+            });
+            channel.adpcm.value
+        }
+        Format::PsgNoise => match index {
+            8..=13 => {
+                channel.psg_step = (channel.psg_step + 1) & 0b111;
+                let duty = channel.duty();
+                if duty != 7 && channel.psg_step <= duty {
+                    0x7FFF
+                } else {
+                    -0x7FFF
+                }
+            }
+            14 | 15 => {
+                let carry = get_bit!(channel.lfsr, 0);
+                channel.lfsr >>= 1;
+                if carry {
+                    channel.lfsr ^= 0x6000;
+                    -0x7FFF
+                } else {
+                    0x7FFF
+                }
+            }
+            _ => 0,
+        },
+    };
+
+    channel.pos = channel.pos.wrapping_add(1);
+    if channel.pos >= end {
+        match channel.repeat() {
+            Repeat::Manual => {}
+            Repeat::Loop => {
+                channel.pos = loop_start;
+                channel.adpcm = channel.adpcm_loop;
+            }
+            Repeat::OneShot => {
+                unset_bit!(channel.cnt, 31);
+                if !channel.hold() {
+                    channel.sample = 0;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spu::SAMPLE_CYCLES;
+    use crate::Interpreter;
+
+    #[test]
+    fn timer_counts_at_half_the_bus_clock() {
+        // SOUNDxTMR = -(33513982 / 2) / freq, 0xFC00 plays at ~16364Hz, half the output rate.
+        let mut core = Core::<Interpreter>::new(slog::Logger::root(slog::Discard, slog::o!()));
+        let channel = &mut core.spu.channels[8];
+        channel.cnt = 1 << 31 | 3 << 29 | 3 << 24 | 0x7F;
+        channel.tmr = 0xFC00;
+        channel.start();
+        for _ in 0..1024 {
+            run(&mut core, 8, SAMPLE_CYCLES);
+        }
+        assert_eq!(core.spu.channels[8].pos, 512);
+    }
+}
@@ -0,0 +1,41 @@
+/// Linear interpolating resampler for interleaved stereo samples.
+#[derive(Debug, Clone)]
+pub struct Resampler {
+    /// Input frames per output frame.
+    step: f64,
+    /// Position of the next output frame between `prev` and the next input frame.
+    frac: f64,
+    prev: [i16; 2],
+}
+
+impl Resampler {
+    pub fn new(in_rate: f64, out_rate: f64) -> Self {
+        Self {
+            step: in_rate / out_rate,
+            frac: 0.0,
+            prev: [0; 2],
+        }
+    }
+
+    /// Change the ratio without resetting the position, for dynamic rate control.
+    pub fn set_rates(&mut self, in_rate: f64, out_rate: f64) {
+        self.step = in_rate / out_rate;
+    }
+
+    /// Resample `input` and append the result to `out`.
+    pub fn process(&mut self, input: &[i16], out: &mut Vec<i16>) {
+        fn lerp(a: i16, b: i16, t: f64) -> i16 {
+            (a as f64 + (b as f64 - a as f64) * t) as i16
+        }
+
+        for frame in input.chunks_exact(2) {
+            while self.frac < 1.0 {
+                out.push(lerp(self.prev[0], frame[0], self.frac));
+                out.push(lerp(self.prev[1], frame[1], self.frac));
+                self.frac += self.step;
+            }
+            self.frac -= 1.0;
+            self.prev = [frame[0], frame[1]];
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use crate::irq::{self, Interrupt};
+use crate::scheduler::{Event, Timestamp, BUS_CYCLE};
+use crate::{Core, Engine};
+
+/// One of the four timers (TMxCNT_L/H) of a CPU. The counter is only brought up to date
+/// when it's accessed or overflows.
+#[derive(Default, Clone, Copy)]
+pub struct Timer {
+    reload: u16,
+    cnt: u16,
+    counter: u16,
+    /// Time `counter` was last brought up to date, always a multiple of the period away from
+    /// when the timer was started.
+    last_update: Timestamp,
+}
+
+impl Timer {
+    /// Timestamps per count, for each prescaler selection.
+    const PERIODS: [Timestamp; 4] = [BUS_CYCLE, BUS_CYCLE * 64, BUS_CYCLE * 256, BUS_CYCLE * 1024];
+
+    #[inline]
+    fn running(&self) -> bool {
+        get_bit!(self.cnt, 7)
+    }
+
+    #[inline]
+    fn count_up(&self) -> bool {
+        get_bit!(self.cnt, 2)
+    }
+
+    #[inline]
+    fn irq(&self) -> bool {
+        get_bit!(self.cnt, 6)
+    }
+
+    #[inline]
+    fn period(&self) -> Timestamp {
+        Self::PERIODS[self.cnt as usize & 0b11]
+    }
+
+    /// The timer counts with time, as opposed to being stopped or counting overflows.
+    #[inline]
+    fn ticking(&self, index: usize) -> bool {
+        self.running() && !(index != 0 && self.count_up())
+    }
+
+    /// Add `counts` to the counter, reloading on overflow.
+    fn add(&mut self, counts: u64) {
+        let total = self.counter as u64 + counts;
+        self.counter = if total > 0xFFFF {
+            let reload = self.reload as u64;
+            (reload + (total - 0x10000) % (0x10000 - reload)) as u16
+        } else {
+            total as u16
+        };
+    }
+
+    fn sync(&mut self, index: usize, now: Timestamp) {
+        if self.ticking(index) {
+            let period = self.period();
+            let counts = (now - self.last_update) / period;
+            self.last_update += counts * period;
+            self.add(counts);
+        }
+    }
+
+    fn next_overflow(&self) -> Timestamp {
+        self.last_update + (0x10000 - self.counter as u64) * self.period()
+    }
+}
+
+#[derive(Default)]
+pub struct Timers {
+    timers: [Timer; 4],
+}
+
+impl Timers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+fn timers<E: Engine, const ARM9: bool>(core: &mut Core<E>) -> &mut Timers {
+    if ARM9 {
+        &mut core.arm9.timers
+    } else {
+        &mut core.arm7.timers
+    }
+}
+
+fn reschedule<E: Engine, const ARM9: bool>(core: &mut Core<E>, index: usize) {
+    let event = Event::TimerOverflow { arm9: ARM9, index };
+    let timer = timers::<E, ARM9>(core).timers[index];
+    if timer.ticking(index) {
+        core.scheduler.schedule(timer.next_overflow(), event);
+    } else {
+        core.scheduler.cancel(event);
+    }
+}
+
+/// TMxCNT_L reads return the current counter.
+pub fn counter<E: Engine, const ARM9: bool>(core: &mut Core<E>, index: usize) -> u16 {
+    let now = core.scheduler.now();
+    let timer = &mut timers::<E, ARM9>(core).timers[index];
+    timer.sync(index, now);
+    timer.counter
+}
+
+pub fn cnt<E: Engine, const ARM9: bool>(core: &mut Core<E>, index: usize) -> u16 {
+    timers::<E, ARM9>(core).timers[index].cnt
+}
+
+pub fn reload<E: Engine, const ARM9: bool>(core: &mut Core<E>, index: usize) -> u16 {
+    timers::<E, ARM9>(core).timers[index].reload
+}
+
+/// TMxCNT_L writes only set the reload value, it's used on the next start or overflow.
+pub fn reload_set<E: Engine, const ARM9: bool>(core: &mut Core<E>, index: usize, val: u16) {
+    timers::<E, ARM9>(core).timers[index].reload = val;
+}
+
+pub fn cnt_set<E: Engine, const ARM9: bool>(core: &mut Core<E>, index: usize, val: u16) {
+    let now = core.scheduler.now();
+    let timer = &mut timers::<E, ARM9>(core).timers[index];
+    timer.sync(index, now);
+    let was_running = timer.running();
+    let old_cnt = timer.cnt;
+    timer.cnt = val & 0xC7;
+    if timer.running() && !was_running {
+        timer.counter = timer.reload;
+        timer.last_update = now;
+    } else if (old_cnt ^ timer.cnt) & 0b111 != 0 {
+        // changing the prescaler or count-up restarts the prescaler.
+        timer.last_update = now;
+    }
+    reschedule::<E, ARM9>(core, index);
+}
+
+/// Called by the scheduler when a ticking timer overflows.
+pub fn overflow<E: Engine, const ARM9: bool>(core: &mut Core<E>, index: usize) {
+    let now = core.scheduler.now();
+    let timer = &mut timers::<E, ARM9>(core).timers[index];
+    timer.sync(index, now);
+    reschedule::<E, ARM9>(core, index);
+    overflowed::<E, ARM9>(core, index);
+}
+
+/// Raise the IRQ of a timer that overflowed and count up the next one if it cascades.
+fn overflowed<E: Engine, const ARM9: bool>(core: &mut Core<E>, index: usize) {
+    let timer = timers::<E, ARM9>(core).timers[index];
+    if timer.irq() {
+        irq::request::<E, ARM9>(core, Interrupt::timer(index));
+    }
+    let next = index + 1;
+    if next < 4 {
+        let next_timer = &mut timers::<E, ARM9>(core).timers[next];
+        if next_timer.running() && next_timer.count_up() {
+            let counter = next_timer.counter;
+            next_timer.add(1);
+            if counter == 0xFFFF {
+                overflowed::<E, ARM9>(core, next);
+            }
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use std::cell::UnsafeCell;
 
-pub struct UnsafeMem<T> {
+pub struct UnsafeMem<T: ?Sized> {
     inner: Box<UnsafeCell<T>>,
 }
 
@@ -10,10 +10,12 @@ impl<T> UnsafeMem<T> {
             inner: Box::new(UnsafeCell::new(value)),
         }
     }
+}
 
+impl<T: ?Sized> UnsafeMem<T> {
     pub unsafe fn from_ptr(ptr: *mut T) -> Self {
         Self {
-            inner: Box::from_raw(ptr as *mut UnsafeCell<_>),
+            inner: Box::from_raw(ptr as *mut UnsafeCell<T>),
         }
     }
 
@@ -0,0 +1,616 @@
+//! The wifi unit of the ARM7 at 0x04800000, the MAC registers with its 8KiB of RAM and the serial
+//! interfaces of the baseband and RF chips.
+//!
+//! Only what is needed for the firmware and games to initialize it and to exchange frames with
+//! other cores through a [`WifiTransport`] is emulated, there is no timing for transfers and
+//! neither multiplay replies nor power saving are handled.
+
+mod transport;
+pub use transport::{ChannelTransport, UdpTransport, WifiTransport};
+
+use crate::bus::Access;
+use crate::irq::{self, Interrupt};
+use crate::scheduler::{Event, Timestamp, ARM9_CLOCK};
+use crate::{Core, Engine};
+
+/// Interval of [`Event::WifiTick`], in which received frames and beacons are handled (~122us).
+pub const TICK_PERIOD: Timestamp = ARM9_CLOCK / 8192;
+
+const W_ID: usize = 0x000;
+const W_MODE_RST: usize = 0x004;
+const W_IF: usize = 0x010;
+const W_IE: usize = 0x012;
+const W_RXCNT: usize = 0x030;
+const W_RF_PINS: usize = 0x034;
+const W_POWERSTATE: usize = 0x03C;
+const W_POWERFORCE: usize = 0x040;
+const W_RANDOM: usize = 0x044;
+const W_RXBUF_BEGIN: usize = 0x050;
+const W_RXBUF_END: usize = 0x052;
+const W_RXBUF_WRCSR: usize = 0x054;
+const W_RXBUF_WR_ADDR: usize = 0x056;
+const W_RXBUF_RD_ADDR: usize = 0x058;
+const W_RXBUF_READCSR: usize = 0x05A;
+const W_RXBUF_COUNT: usize = 0x05C;
+const W_RXBUF_RD_DATA: usize = 0x060;
+const W_RXBUF_GAP: usize = 0x062;
+const W_RXBUF_GAPDISP: usize = 0x064;
+const W_TXBUF_WR_ADDR: usize = 0x068;
+const W_TXBUF_COUNT: usize = 0x06C;
+const W_TXBUF_WR_DATA: usize = 0x070;
+const W_TXBUF_GAP: usize = 0x074;
+const W_TXBUF_GAPDISP: usize = 0x076;
+const W_TXBUF_BEACON: usize = 0x080;
+const W_BEACONINT: usize = 0x08C;
+const W_TXBUF_CMD: usize = 0x090;
+const W_TXBUF_LOC1: usize = 0x0A0;
+const W_TXBUF_LOC2: usize = 0x0A4;
+const W_TXBUF_LOC3: usize = 0x0A8;
+const W_TXREQ_RESET: usize = 0x0AC;
+const W_TXREQ_SET: usize = 0x0AE;
+const W_TXREQ_READ: usize = 0x0B0;
+const W_TXBUSY: usize = 0x0B6;
+const W_TXSTAT: usize = 0x0B8;
+const W_US_COUNTCNT: usize = 0x0E8;
+const W_US_COUNT0: usize = 0x0F8;
+const W_US_COUNT3: usize = 0x0FE;
+const W_BB_CNT: usize = 0x158;
+const W_BB_WRITE: usize = 0x15A;
+const W_BB_READ: usize = 0x15C;
+const W_BB_BUSY: usize = 0x15E;
+const W_RF_DATA2: usize = 0x17C;
+const W_RF_DATA1: usize = 0x17E;
+const W_RF_BUSY: usize = 0x180;
+const W_RF_STATUS: usize = 0x214;
+const W_IF_SET: usize = 0x21C;
+
+/// W_IF bits.
+const IF_RX_COMPLETE: u16 = 1 << 0;
+const IF_TX_COMPLETE: u16 = 1 << 1;
+const IF_BEACON: u16 = 1 << 14;
+
+/// Bytes of the headers in front of the frames in the TX and RX buffers.
+const TX_HEADER_LEN: usize = 12;
+const RX_HEADER_LEN: usize = 12;
+
+pub struct Wifi {
+    /// Registers at 0x000..0x1000, accessed as halfwords.
+    regs: Box<[u16; 0x800]>,
+    ram: Box<[u8; kb!(8)]>,
+    bb: [u8; 0x100],
+    rf: [u32; 0x20],
+    /// Microsecond counter (W_US_COUNT) at `us_counter_at`, it runs while W_US_COUNTCNT is set.
+    us_counter: u64,
+    us_counter_at: Timestamp,
+    /// Value of the microsecond counter at which the next beacon is sent.
+    next_beacon: u64,
+    transport: Option<Box<dyn WifiTransport>>,
+}
+
+impl Wifi {
+    pub fn new() -> Self {
+        let mut wifi = Self {
+            regs: Box::new([0; 0x800]),
+            ram: Box::new([0; kb!(8)]),
+            bb: [0; 0x100],
+            rf: [0; 0x20],
+            us_counter: 0,
+            us_counter_at: 0,
+            next_beacon: 0,
+            transport: None,
+        };
+        wifi.reset();
+        wifi
+    }
+
+    /// Reset to the power on state, keeping the transport.
+    pub fn reset(&mut self) {
+        self.regs.fill(0);
+        self.ram.fill(0);
+        self.bb.fill(0);
+        self.rf.fill(0);
+        self.us_counter = 0;
+        self.us_counter_at = 0;
+        self.next_beacon = 0;
+
+        // DS and DS lite have 0xC340 instead.
+        self.regs[W_ID / 2] = 0x1440;
+        self.regs[W_RF_PINS / 2] = 0x0004;
+        self.regs[W_POWERSTATE / 2] = 0x0200;
+        self.regs[W_RANDOM / 2] = 0x07FF;
+        self.regs[W_RXBUF_BEGIN / 2] = 0x4000;
+        self.regs[W_RXBUF_END / 2] = 0x4800;
+        self.regs[W_TXREQ_READ / 2] = 0x0010;
+        self.regs[W_BEACONINT / 2] = 0x0064;
+        self.regs[W_RF_STATUS / 2] = 0x0009;
+        // read only registers of the baseband chip, 0x00 is the chip ID checked by the firmware.
+        self.bb[0x00] = 0x6D;
+        self.bb[0x5D] = 0x01;
+        self.bb[0x64] = 0xFF;
+    }
+
+    #[inline]
+    fn reg(&self, offs: usize) -> u16 {
+        self.regs[offs / 2]
+    }
+
+    #[inline]
+    fn reg_set(&mut self, offs: usize, val: u16) {
+        self.regs[offs / 2] = val;
+    }
+
+    /// Byte offset into the RAM of a buffer register, which holds an address at 0x4000.
+    #[inline]
+    fn ram_offs(&self, reg: usize) -> usize {
+        self.reg(reg) as usize & 0x1FFE
+    }
+
+    fn ram16(&self, offs: usize) -> u16 {
+        let offs = offs & 0x1FFE;
+        u16::from_le_bytes([self.ram[offs], self.ram[offs + 1]])
+    }
+
+    fn ram16_set(&mut self, offs: usize, val: u16) {
+        let offs = offs & 0x1FFE;
+        self.ram[offs..offs + 2].copy_from_slice(&val.to_le_bytes());
+    }
+
+    fn us_counting(&self) -> bool {
+        get_bit!(self.reg(W_US_COUNTCNT), 0)
+    }
+
+    /// Bring the microsecond counter up to `now`.
+    fn sync_us_counter(&mut self, now: Timestamp) {
+        if self.us_counting() {
+            let elapsed = now - self.us_counter_at;
+            let us = (elapsed as u128 * 1_000_000 / ARM9_CLOCK as u128) as u64;
+            self.us_counter += us;
+            // keep the remainder so the counter doesn't drift.
+            self.us_counter_at += (us as u128 * ARM9_CLOCK as u128 / 1_000_000) as u64;
+        } else {
+            self.us_counter_at = now;
+        }
+    }
+
+    /// Transfer over the serial interface of the baseband chip, started by writing W_BB_CNT.
+    fn bb_transfer(&mut self, cnt: u16) {
+        let index = cnt as usize & 0xFF;
+        match cnt >> 12 {
+            5 => {
+                if !matches!(index, 0x00 | 0x5D | 0x64) {
+                    self.bb[index] = self.reg(W_BB_WRITE) as u8;
+                }
+            }
+            6 => self.reg_set(W_BB_READ, self.bb[index] as u16),
+            _ => {}
+        }
+    }
+
+    /// Transfer over the serial interface of the RF2958, started by writing W_RF_DATA1.
+    fn rf_transfer(&mut self) {
+        let data2 = self.reg(W_RF_DATA2);
+        let index = (data2 as usize >> 2) & 0x1F;
+        if get_bit!(data2, 7) {
+            let val = self.rf[index];
+            self.reg_set(W_RF_DATA1, val as u16);
+            self.reg_set(W_RF_DATA2, (data2 & !0b11) | ((val >> 16) as u16 & 0b11));
+        } else {
+            self.rf[index] = self.reg(W_RF_DATA1) as u32 | ((data2 as u32 & 0b11) << 16);
+        }
+    }
+
+    /// Advance a buffer address by a halfword, wrapping from W_RXBUF_END to W_RXBUF_BEGIN.
+    fn rx_advance(&self, offs: usize) -> usize {
+        let offs = offs + 2;
+        if offs >= self.ram_offs(W_RXBUF_END) {
+            self.ram_offs(W_RXBUF_BEGIN)
+        } else {
+            offs
+        }
+    }
+
+    /// Queue a received frame in the RX buffer, returns false if there is no room for it.
+    fn receive(&mut self, frame: &[u8]) -> bool {
+        let begin = self.ram_offs(W_RXBUF_BEGIN);
+        let end = self.ram_offs(W_RXBUF_END);
+        if end <= begin {
+            return false;
+        }
+        let write = (self.reg(W_RXBUF_WRCSR) as usize * 2) & 0x1FFE;
+        let read = (self.reg(W_RXBUF_READCSR) as usize * 2) & 0x1FFE;
+        let len = (RX_HEADER_LEN + frame.len()).next_multiple_of(4);
+        let free =
+            (read as isize - write as isize - 1).rem_euclid((end - begin) as isize) as usize + 1;
+        if len >= free {
+            return false;
+        }
+
+        let frame_control = frame.first().copied().unwrap_or(0);
+        let frame_type = match (frame_control >> 2) & 0b11 {
+            // beacons have their own type.
+            0 if frame_control >> 4 == 8 => 1,
+            0 => 0,
+            1 => 5,
+            _ => 8,
+        };
+        let mut header = [0; RX_HEADER_LEN];
+        header[0..2].copy_from_slice(&(frame_type as u16 | 0x0010).to_le_bytes());
+        header[2..4].copy_from_slice(&0x0040u16.to_le_bytes());
+        // 2Mbit/s.
+        header[6..8].copy_from_slice(&0x0014u16.to_le_bytes());
+        header[8..10].copy_from_slice(&(frame.len() as u16).to_le_bytes());
+        header[10] = 0x20;
+        header[11] = 0x20;
+
+        let mut bytes = header.iter().chain(frame).copied();
+        let mut offs = write;
+        for _ in 0..len / 2 {
+            let lo = bytes.next().unwrap_or(0);
+            let hi = bytes.next().unwrap_or(0);
+            self.ram16_set(offs, u16::from_le_bytes([lo, hi]));
+            offs = self.rx_advance(offs);
+        }
+        self.reg_set(W_RXBUF_WRCSR, (offs / 2) as u16);
+        true
+    }
+
+    /// The frame queued at `loc`, without the TX header and the FCS.
+    fn tx_frame(&self, loc: u16) -> Vec<u8> {
+        let offs = (loc as usize & 0xFFF) * 2;
+        let len = (self.ram16(offs + 10) as usize).saturating_sub(4);
+        (0..len)
+            .map(|i| self.ram[(offs + TX_HEADER_LEN + i) & 0x1FFF])
+            .collect()
+    }
+
+    fn send(&mut self, frame: &[u8]) {
+        if let Some(transport) = &mut self.transport {
+            transport.send(frame);
+        }
+    }
+}
+
+impl Default for Wifi {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Set bits of W_IF, requesting the wifi interrupt if one of them is enabled.
+fn if_set<E: Engine>(core: &mut Core<E>, bits: u16) {
+    let wifi = &mut core.wifi;
+    wifi.reg_set(W_IF, wifi.reg(W_IF) | bits);
+    if wifi.reg(W_IE) & bits != 0 {
+        irq::request::<E, false>(core, Interrupt::Wifi);
+    }
+}
+
+/// Send the frames of the TX slots that are requested and enabled.
+fn transmit<E: Engine>(core: &mut Core<E>) {
+    // W_TXREQ bit 0 is LOC1, 1 the multiplay command, 2 LOC2 and 3 LOC3.
+    const SLOTS: [(u16, usize); 4] = [
+        (1 << 3, W_TXBUF_LOC3),
+        (1 << 1, W_TXBUF_CMD),
+        (1 << 2, W_TXBUF_LOC2),
+        (1 << 0, W_TXBUF_LOC1),
+    ];
+    let mut sent = false;
+    for (slot, (bit, reg)) in SLOTS.into_iter().enumerate() {
+        let wifi = &mut core.wifi;
+        let loc = wifi.reg(reg);
+        if wifi.reg(W_TXREQ_READ) & bit == 0 || !get_bit!(loc, 15) {
+            continue;
+        }
+        let frame = wifi.tx_frame(loc);
+        wifi.send(&frame);
+        // mark the frame as sent in its header.
+        let offs = (loc as usize & 0xFFF) * 2;
+        wifi.ram16_set(offs, 0x0001);
+        wifi.reg_set(reg, loc & 0x7FFF);
+        wifi.reg_set(W_TXSTAT, 0x0001 | (slot as u16) << 12);
+        sent = true;
+    }
+    if sent {
+        if_set(core, IF_TX_COMPLETE);
+    }
+}
+
+/// Read the halfword at `adr`, which is in 0x04800000..0x05000000. Only CPU reads advance the
+/// random generator and the receive buffer.
+pub fn read<E: Engine, A: Access>(core: &mut Core<E>, adr: u32) -> u16 {
+    let now = core.scheduler.now();
+    let wifi = &mut core.wifi;
+    let offs = adr as usize & 0x7FFE;
+    match offs {
+        0x4000..=0x5FFF => return wifi.ram16(offs),
+        0x2000..=0x3FFF | 0x6000..=0x7FFF => return 0xFFFF,
+        _ => {}
+    }
+    let offs = offs & 0xFFF;
+    match offs {
+        W_RANDOM => {
+            let random = wifi.reg(W_RANDOM);
+            if !A::CPU {
+                return random;
+            }
+            let next = (random & 1) ^ (((random & 0x3FF) << 1) | (random >> 10));
+            wifi.reg_set(W_RANDOM, next & 0x7FF);
+            random
+        }
+        W_RXBUF_RD_DATA => {
+            let rd_addr = wifi.ram_offs(W_RXBUF_RD_ADDR);
+            let val = wifi.ram16(rd_addr);
+            if !A::CPU {
+                return val;
+            }
+            let mut next = wifi.rx_advance(rd_addr);
+            if next == wifi.ram_offs(W_RXBUF_GAP) {
+                next += wifi.reg(W_RXBUF_GAPDISP) as usize * 2;
+                if next >= wifi.ram_offs(W_RXBUF_END) {
+                    next = next - wifi.ram_offs(W_RXBUF_END) + wifi.ram_offs(W_RXBUF_BEGIN);
+                }
+            }
+            wifi.reg_set(W_RXBUF_RD_ADDR, next as u16);
+            let count = wifi.reg(W_RXBUF_COUNT);
+            wifi.reg_set(W_RXBUF_COUNT, count.saturating_sub(1));
+            val
+        }
+        W_US_COUNT0..=W_US_COUNT3 => {
+            wifi.sync_us_counter(now);
+            (wifi.us_counter >> ((offs - W_US_COUNT0) * 8)) as u16
+        }
+        W_BB_BUSY | W_RF_BUSY | W_TXBUSY => 0,
+        _ => wifi.reg(offs),
+    }
+}
+
+/// Write the halfword at `adr`, which is in 0x04800000..0x05000000.
+pub fn write<E: Engine>(core: &mut Core<E>, adr: u32, val: u16) {
+    let now = core.scheduler.now();
+    let wifi = &mut core.wifi;
+    let offs = adr as usize & 0x7FFE;
+    match offs {
+        0x4000..=0x5FFF => return wifi.ram16_set(offs, val),
+        0x2000..=0x3FFF | 0x6000..=0x7FFF => return,
+        _ => {}
+    }
+    let offs = offs & 0xFFF;
+    match offs {
+        W_ID | W_RANDOM | W_TXREQ_READ | W_BB_READ | W_BB_BUSY | W_RF_BUSY | W_TXBUSY => {}
+        W_MODE_RST => {
+            let old = wifi.reg(W_MODE_RST);
+            wifi.reg_set(W_MODE_RST, val);
+            if get_bit!(val, 0) && !get_bit!(old, 0) {
+                wifi.reg_set(W_RF_STATUS, 0x0001);
+                wifi.reg_set(W_RF_PINS, 0x0046);
+            } else if !get_bit!(val, 0) && get_bit!(old, 0) {
+                wifi.reg_set(W_RF_STATUS, 0x0009);
+                wifi.reg_set(W_RF_PINS, 0x0084);
+            }
+        }
+        W_IF => wifi.reg_set(W_IF, wifi.reg(W_IF) & !val),
+        W_IF_SET => if_set(core, val),
+        W_IE => {
+            wifi.reg_set(W_IE, val);
+            if val & wifi.reg(W_IF) != 0 {
+                irq::request::<E, false>(core, Interrupt::Wifi);
+            }
+        }
+        W_RXCNT => {
+            wifi.reg_set(W_RXCNT, val & 0xFF0E);
+            // latches W_RXBUF_WR_ADDR as the write cursor.
+            if get_bit!(val, 0) {
+                wifi.reg_set(W_RXBUF_WRCSR, wifi.reg(W_RXBUF_WR_ADDR) & 0x0FFF);
+            }
+        }
+        W_POWERSTATE => {
+            // waking up is instant.
+            if get_bit!(val, 1) {
+                wifi.reg_set(W_POWERSTATE, 0x0000);
+            }
+        }
+        W_POWERFORCE => {
+            wifi.reg_set(W_POWERFORCE, val & 0x8001);
+            if get_bit!(val, 15) {
+                let state = if get_bit!(val, 0) { 0x0200 } else { 0x0000 };
+                wifi.reg_set(W_POWERSTATE, state);
+            }
+        }
+        W_TXBUF_WR_DATA => {
+            let wr_addr = wifi.ram_offs(W_TXBUF_WR_ADDR);
+            wifi.ram16_set(wr_addr, val);
+            let mut next = wr_addr + 2;
+            if next == wifi.ram_offs(W_TXBUF_GAP) {
+                next += wifi.reg(W_TXBUF_GAPDISP) as usize * 2;
+            }
+            wifi.reg_set(W_TXBUF_WR_ADDR, (next & 0x1FFE) as u16);
+            let count = wifi.reg(W_TXBUF_COUNT);
+            wifi.reg_set(W_TXBUF_COUNT, count.saturating_sub(1));
+        }
+        W_TXREQ_RESET => {
+            let txreq = wifi.reg(W_TXREQ_READ) & !(val & 0xF);
+            wifi.reg_set(W_TXREQ_READ, txreq);
+        }
+        W_TXREQ_SET => {
+            let txreq = wifi.reg(W_TXREQ_READ) | (val & 0xF);
+            wifi.reg_set(W_TXREQ_READ, txreq);
+            transmit(core);
+        }
+        W_TXBUF_LOC1 | W_TXBUF_LOC2 | W_TXBUF_LOC3 | W_TXBUF_CMD => {
+            wifi.reg_set(offs, val);
+            transmit(core);
+        }
+        W_US_COUNTCNT => {
+            wifi.sync_us_counter(now);
+            wifi.reg_set(W_US_COUNTCNT, val & 0b1);
+        }
+        W_US_COUNT0..=W_US_COUNT3 => {
+            wifi.sync_us_counter(now);
+            let shift = (offs - W_US_COUNT0) * 8;
+            wifi.us_counter = (wifi.us_counter & !(0xFFFF << shift)) | (val as u64) << shift;
+        }
+        W_BB_CNT => {
+            wifi.reg_set(W_BB_CNT, val);
+            wifi.bb_transfer(val);
+        }
+        W_RF_DATA1 => {
+            wifi.reg_set(W_RF_DATA1, val);
+            wifi.rf_transfer();
+        }
+        _ => wifi.reg_set(offs, val),
+    }
+}
+
+/// Called by the scheduler every [`TICK_PERIOD`] to send beacons and queue received frames.
+pub fn tick<E: Engine>(core: &mut Core<E>) {
+    let now = core.scheduler.now();
+    core.scheduler.schedule(now + TICK_PERIOD, Event::WifiTick);
+    // nothing is sent or received while the wifi unit is powered off through POWCNT2.
+    if !core.power.wifi_enabled() {
+        return;
+    }
+    let wifi = &mut core.wifi;
+    wifi.sync_us_counter(now);
+
+    // beacons are sent every W_BEACONINT TUs (1024us) while the microsecond counter runs.
+    let beacon = wifi.reg(W_TXBUF_BEACON);
+    if wifi.us_counting() && get_bit!(beacon, 15) && wifi.us_counter >= wifi.next_beacon {
+        let interval = wifi.reg(W_BEACONINT).max(1) as u64 * 1024;
+        wifi.next_beacon = wifi.us_counter + interval;
+        let mut frame = wifi.tx_frame(beacon);
+        // the timestamp of the beacon is inserted by the hardware.
+        if let Some(timestamp) = frame.get_mut(24..32) {
+            timestamp.copy_from_slice(&wifi.us_counter.to_le_bytes());
+        }
+        wifi.send(&frame);
+        if_set(core, IF_BEACON);
+    }
+
+    let wifi = &mut core.wifi;
+    let frames: Vec<_> = match &mut wifi.transport {
+        Some(transport) => std::iter::from_fn(|| transport.recv()).collect(),
+        None => return,
+    };
+    // frames are dropped while receiving is disabled.
+    let mut received = false;
+    for frame in frames {
+        if get_bit!(wifi.reg(W_RXCNT), 15) && wifi.receive(&frame) {
+            received = true;
+        }
+    }
+    if received {
+        if_set(core, IF_RX_COMPLETE);
+    }
+}
+
+impl<E: Engine> Core<E> {
+    /// Exchange wifi frames with other cores through `transport` from now on.
+    pub fn set_wifi_transport(&mut self, transport: Box<dyn WifiTransport>) {
+        self.wifi.transport = Some(transport);
+    }
+
+    pub fn take_wifi_transport(&mut self) -> Option<Box<dyn WifiTransport>> {
+        self.wifi.transport.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::{CPUAccess, DebugAccess};
+    use crate::Interpreter;
+
+    const BASE: u32 = 0x0480_0000;
+
+    fn core() -> Core<Interpreter> {
+        let mut core = Core::new(slog::Logger::root(slog::Discard, slog::o!()));
+        core.power.powcnt2_set(0b10);
+        core
+    }
+
+    fn read(core: &mut Core<Interpreter>, offs: usize) -> u16 {
+        super::read::<Interpreter, CPUAccess>(core, BASE + offs as u32)
+    }
+
+    fn write(core: &mut Core<Interpreter>, offs: usize, val: u16) {
+        super::write(core, BASE + offs as u32, val);
+    }
+
+    #[test]
+    fn random_sequence() {
+        let mut core = core();
+        // X = (X AND 1) XOR (X ROL 1), 11 bits.
+        let debug = super::read::<Interpreter, DebugAccess>(&mut core, BASE + W_RANDOM as u32);
+        assert_eq!(debug, 0x7FF);
+        let sequence = [0; 5].map(|_| read(&mut core, W_RANDOM));
+        assert_eq!(sequence, [0x7FF, 0x7FE, 0x7FD, 0x7FA, 0x7F5]);
+    }
+
+    #[test]
+    fn rx_buffer_reads_wrap_and_skip_the_gap() {
+        let mut core = core();
+        for i in 0..8 {
+            write(&mut core, 0x4100 + i * 2, i as u16);
+        }
+        write(&mut core, W_RXBUF_BEGIN, 0x4100);
+        write(&mut core, W_RXBUF_END, 0x4110);
+        write(&mut core, W_RXBUF_GAP, 0x4104);
+        write(&mut core, W_RXBUF_GAPDISP, 2);
+        write(&mut core, W_RXBUF_RD_ADDR, 0x10C);
+        write(&mut core, W_RXBUF_COUNT, 10);
+
+        // a debug read doesn't advance.
+        let debug = super::read::<Interpreter, DebugAccess>(&mut core, BASE + 0x060);
+        assert_eq!(debug, 6);
+        let values = [0; 5].map(|_| read(&mut core, W_RXBUF_RD_DATA));
+        assert_eq!(values, [6, 7, 0, 1, 4]);
+        assert_eq!(read(&mut core, W_RXBUF_RD_ADDR), 0x10A);
+        assert_eq!(read(&mut core, W_RXBUF_COUNT), 5);
+    }
+
+    #[test]
+    fn frames_are_exchanged_between_cores() {
+        let (transport_a, transport_b) = ChannelTransport::pair();
+        let mut a = core();
+        let mut b = core();
+        a.set_wifi_transport(Box::new(transport_a));
+        b.set_wifi_transport(Box::new(transport_b));
+
+        // a data frame in the TX buffer behind its header, the length includes the FCS.
+        let frame: Vec<u8> = [0x08, 0x00].into_iter().chain(0..22).collect();
+        let loc = 0x4200;
+        // enabled, with the halfword offset in the RAM.
+        let loc1 = 0x8000 | ((loc & 0x1FFF) / 2) as u16;
+        write(&mut a, loc + 10, frame.len() as u16 + 4);
+        for (i, pair) in frame.chunks(2).enumerate() {
+            let val = u16::from_le_bytes([pair[0], pair[1]]);
+            write(&mut a, loc + TX_HEADER_LEN + i * 2, val);
+        }
+        write(&mut a, W_IE, IF_TX_COMPLETE);
+        write(&mut a, W_TXBUF_LOC1, loc1);
+        write(&mut a, W_TXREQ_SET, 0b1);
+        assert_eq!(read(&mut a, W_IF), IF_TX_COMPLETE);
+        assert_eq!(read(&mut a, W_TXBUF_LOC1) & 0x8000, 0);
+        assert_ne!(a.arm7.irq.if_() & Interrupt::Wifi.mask(), 0);
+
+        // received frames are only queued while receiving is enabled.
+        tick(&mut b);
+        assert_eq!(read(&mut b, W_IF), 0);
+        write(&mut b, W_IE, IF_RX_COMPLETE);
+        write(&mut b, W_RXCNT, 0x8001);
+        write(&mut a, W_TXBUF_LOC1, loc1);
+        write(&mut a, W_TXREQ_SET, 0b1);
+        tick(&mut b);
+        assert_eq!(read(&mut b, W_IF), IF_RX_COMPLETE);
+        assert_ne!(b.arm7.irq.if_() & Interrupt::Wifi.mask(), 0);
+
+        let len = (RX_HEADER_LEN + frame.len()).next_multiple_of(4);
+        assert_eq!(read(&mut b, W_RXBUF_WRCSR) as usize, len / 2);
+        write(&mut b, W_RXBUF_RD_ADDR, 0);
+        let received: Vec<u8> = (0..len / 2)
+            .flat_map(|_| read(&mut b, W_RXBUF_RD_DATA).to_le_bytes())
+            .collect();
+        assert_eq!(received[8..10], (frame.len() as u16).to_le_bytes());
+        assert_eq!(received[RX_HEADER_LEN..RX_HEADER_LEN + frame.len()], frame);
+    }
+}
@@ -0,0 +1,68 @@
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::mpsc::{self, Receiver, Sender};
+
+/// Carries 802.11 frames, without their FCS, between the wifi units of cores.
+pub trait WifiTransport {
+    /// Send `frame` to the other cores.
+    fn send(&mut self, frame: &[u8]);
+
+    /// The next frame sent by another core, without blocking.
+    fn recv(&mut self) -> Option<Vec<u8>>;
+}
+
+/// Transport between two cores in the same process.
+pub struct ChannelTransport {
+    tx: Sender<Vec<u8>>,
+    rx: Receiver<Vec<u8>>,
+}
+
+impl ChannelTransport {
+    /// Two connected ends, one for each core.
+    pub fn pair() -> (Self, Self) {
+        let (tx_a, rx_b) = mpsc::channel();
+        let (tx_b, rx_a) = mpsc::channel();
+        (Self { tx: tx_a, rx: rx_a }, Self { tx: tx_b, rx: rx_b })
+    }
+}
+
+impl WifiTransport for ChannelTransport {
+    fn send(&mut self, frame: &[u8]) {
+        // the other end being gone is the same as nobody listening.
+        let _ = self.tx.send(frame.to_vec());
+    }
+
+    fn recv(&mut self) -> Option<Vec<u8>> {
+        self.rx.try_recv().ok()
+    }
+}
+
+/// Transport over UDP, one datagram per frame, e.g. between two processes on the loopback
+/// interface.
+pub struct UdpTransport {
+    socket: UdpSocket,
+}
+
+impl UdpTransport {
+    /// Bind to `local` and exchange frames with the transport bound to `peer`.
+    pub fn bind(local: SocketAddr, peer: SocketAddr) -> io::Result<Self> {
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(peer)?;
+        socket.set_nonblocking(true)?;
+        Ok(Self { socket })
+    }
+}
+
+impl WifiTransport for UdpTransport {
+    fn send(&mut self, frame: &[u8]) {
+        // frames are lost while the peer isn't up, like over the air.
+        let _ = self.socket.send(frame);
+    }
+
+    fn recv(&mut self) -> Option<Vec<u8>> {
+        // frames are at most 2346 bytes.
+        let mut buf = [0; 2400];
+        let len = self.socket.recv(&mut buf).ok()?;
+        Some(buf[..len].to_vec())
+    }
+}
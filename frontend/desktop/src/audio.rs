@@ -0,0 +1,57 @@
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use nds::audio::{RingBufferConsumer, RingBufferSink};
+use slog::Logger;
+
+/// Fraction of a second of audio the ring buffer holds at most.
+const BUFFER_FRACTION: usize = 5;
+
+/// Play the audio pushed to the returned sink on the default output device, until the stream
+/// is dropped.
+pub fn open(logger: Logger) -> anyhow::Result<(cpal::Stream, RingBufferSink)> {
+    let device = cpal::default_host()
+        .default_output_device()
+        .ok_or_else(|| anyhow!("no audio output device"))?;
+    let supported = device.default_output_config()?;
+    let config = supported.config();
+    let sample_rate = config.sample_rate.0;
+    let channels = config.channels as usize;
+    let (sink, consumer) = RingBufferSink::new(sample_rate, sample_rate as usize / BUFFER_FRACTION);
+
+    let on_error = move |err: cpal::StreamError| warn!(logger, "audio stream error: {err}");
+    let stream = match supported.sample_format() {
+        cpal::SampleFormat::I16 => device.build_output_stream(
+            &config,
+            writer(consumer, channels, |sample| sample),
+            on_error,
+            None,
+        )?,
+        cpal::SampleFormat::F32 => device.build_output_stream(
+            &config,
+            writer(consumer, channels, |sample| sample as f32 / 32768.0),
+            on_error,
+            None,
+        )?,
+        format => bail!("unsupported sample format {format:?}"),
+    };
+    stream.play()?;
+    Ok((stream, sink))
+}
+
+/// Fill the buffers of the device with the stereo frames of `consumer`, a mono device gets the
+/// left channel and extra channels repeat the right one.
+fn writer<T: cpal::SizedSample>(
+    consumer: RingBufferConsumer,
+    channels: usize,
+    convert: impl Fn(i16) -> T + Send + 'static,
+) -> impl FnMut(&mut [T], &cpal::OutputCallbackInfo) + Send + 'static {
+    let mut frames = Vec::new();
+    move |data, _| {
+        frames.resize(data.len() / channels * 2, 0);
+        consumer.pop(&mut frames);
+        for (out, frame) in data.chunks_mut(channels).zip(frames.chunks_exact(2)) {
+            for (channel, sample) in out.iter_mut().enumerate() {
+                *sample = convert(frame[channel.min(1)]);
+            }
+        }
+    }
+}
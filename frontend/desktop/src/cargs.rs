@@ -1,3 +1,4 @@
+use std::net::SocketAddr;
 use std::path::PathBuf;
 
 #[derive(argh::FromArgs)]
@@ -6,6 +7,45 @@ pub struct CArgs {
     #[argh(option)]
     /// rom path
     pub rom: Option<PathBuf>,
+    #[argh(switch)]
+    /// emulate the 8MiB main memory of debug consoles
+    pub debug_ram: bool,
+    #[argh(option)]
+    /// fill memory with pseudo-random bytes from this seed on power on
+    pub ram_fill_seed: Option<u64>,
+    #[argh(option)]
+    /// firmware image path
+    pub firmware: Option<PathBuf>,
+    #[argh(option)]
+    /// ARM7 BIOS path, used for the KEY1 encryption of the gamecard
+    pub bios7: Option<PathBuf>,
+    #[argh(option)]
+    /// backup chip of the gamecard, e.g. eeprom-64k, flash-512k or fram-32k, detected when omitted
+    pub save_type: Option<nds::SaveType>,
+    #[argh(option)]
+    /// directory of files served in place of the files of the rom at the same paths
+    pub overlay: Option<PathBuf>,
+    #[argh(option)]
+    /// nickname of the synthesised firmware, used without a firmware image
+    pub nickname: Option<String>,
+    #[argh(option)]
+    /// start the real-time clock at this unix time instead of the host time
+    pub rtc_start: Option<i64>,
+    #[argh(option)]
+    /// write the audio output to this wav file instead of playing it
+    pub wav: Option<PathBuf>,
+    #[argh(option)]
+    /// loop this wav file as the microphone input
+    pub mic_wav: Option<PathBuf>,
+    #[argh(option)]
+    /// exchange wifi frames over UDP from this local address, requires --wifi-peer
+    pub wifi_bind: Option<SocketAddr>,
+    #[argh(option)]
+    /// address of the other instance to exchange wifi frames with
+    pub wifi_peer: Option<SocketAddr>,
+    #[argh(option)]
+    /// run for this many emulated seconds without opening a window
+    pub headless: Option<u64>,
 }
 
 pub fn from_env() -> CArgs {
@@ -0,0 +1,115 @@
+use std::fs;
+use std::path::PathBuf;
+
+use nds::Interpreter;
+use slog::Logger;
+
+use crate::cargs::CArgs;
+use crate::gui::Drain;
+
+/// Create a core configured by the command line arguments, with the rom loaded.
+pub fn create_core(cargs: &CArgs) -> nds::Core<Interpreter> {
+    let config = nds::CoreConfig {
+        main_memory_size: if cargs.debug_ram {
+            nds::MainMemorySize::Debug
+        } else {
+            nds::MainMemorySize::Retail
+        },
+        memory_fill: match cargs.ram_fill_seed {
+            Some(seed) => nds::MemoryFill::Random { seed: Some(seed) },
+            None => nds::MemoryFill::Zero,
+        },
+        firmware: cargs
+            .firmware
+            .as_ref()
+            .map(|path| fs::read(path).expect("failed to read firmware")),
+        firmware_settings: nds::firmware::FirmwareSettings {
+            nickname: cargs.nickname.clone().unwrap_or_else(|| "vargds".into()),
+            ..Default::default()
+        },
+        rtc: match cargs.rtc_start {
+            Some(start) => nds::RtcSource::Fixed { start },
+            None => nds::RtcSource::HostClock,
+        },
+        audio_sample_rate: None,
+        mic: match &cargs.mic_wav {
+            Some(path) => {
+                let data = fs::read(path).expect("failed to read microphone wav");
+                let (samples, sample_rate) =
+                    nds::audio::decode_wav(&data).expect("failed to decode microphone wav");
+                nds::MicSource::Loop {
+                    samples,
+                    sample_rate,
+                }
+            }
+            None => nds::MicSource::Silence,
+        },
+        arm7_bios: cargs
+            .bios7
+            .as_ref()
+            .map(|path| fs::read(path).expect("failed to read ARM7 BIOS")),
+        file_overlay: cargs.overlay.as_ref().map(|dir| {
+            nds::cartridge::FileOverlay::from_dir(dir).expect("failed to read replacement files")
+        }),
+    };
+    let mut core = nds::Core::<nds::Interpreter>::with_config(
+        config,
+        Logger::root(Drain, slog::o!("core" => "core")),
+    );
+    if let Err(err) = unsafe {
+        core.load_unvalidated_rom(
+            fs::read(cargs.rom.as_ref().expect("didn't supply rom"))
+                .expect("failed to read rom")
+                .into_boxed_slice(),
+        )
+    } {
+        panic!("failed to load rom\n{err}");
+    }
+    if let (Some(local), Some(peer)) = (cargs.wifi_bind, cargs.wifi_peer) {
+        let transport = nds::UdpTransport::bind(local, peer).expect("failed to bind wifi socket");
+        core.set_wifi_transport(Box::new(transport));
+    }
+    let save = fs::read(save_path(cargs)).unwrap_or_default();
+    match cargs.save_type {
+        Some(save_type) => core.load_save(save_type, &nds::SaveFile::parse(&save).data),
+        None => core.load_save_file(&save),
+    }
+    core
+}
+
+/// "vargds - " followed by the first line of the title in the banner, in the language of the
+/// firmware, or by the title in the header for games without a banner.
+pub fn window_title(core: &nds::Core<Interpreter>) -> String {
+    let Some(cartridge) = core.cartridge() else {
+        return "vargds".into();
+    };
+    let language = core.config().firmware_settings.language;
+    let banner = cartridge.banner().ok().flatten();
+    let game = match banner.as_ref().and_then(|banner| banner.title(language)) {
+        Some(title) => title.lines().next().unwrap_or_default().to_owned(),
+        None => cartridge.header().game_title().to_owned(),
+    };
+    if game.is_empty() {
+        "vargds".into()
+    } else {
+        format!("vargds - {game}")
+    }
+}
+
+/// The .sav file next to the rom.
+pub fn save_path(cargs: &CArgs) -> PathBuf {
+    cargs
+        .rom
+        .as_ref()
+        .expect("didn't supply rom")
+        .with_extension("sav")
+}
+
+/// Write the backup chip to its .sav file if it changed, or regardless if `force` is set.
+pub fn flush_save(core: &mut nds::Core<Interpreter>, cargs: &CArgs, logger: &Logger, force: bool) {
+    if (core.take_save_dirty() || force) && !core.save_data().is_empty() {
+        if let Err(err) = fs::write(save_path(cargs), core.save_file()) {
+            error!(logger, "failed to write save file: {err}");
+        }
+    }
+}
@@ -5,26 +5,30 @@ use nds::Interpreter;
 use slog::Level;
 use slog::Logger;
 
-use std::fs;
 use std::mem::ManuallyDrop;
 
 use egui::TexturesDelta;
 use egui_winit::egui;
 use egui_winit::winit;
+use egui_winit::winit::event::ElementState;
 use egui_winit::winit::event::Event;
+use egui_winit::winit::event::KeyboardInput;
+use egui_winit::winit::event::VirtualKeyCode;
 use egui_winit::winit::event::WindowEvent;
 
-use crate::cargs;
+use crate::{audio, cargs, emu};
 
 fn run<S: 'static>(
     state: S,
+    title: &str,
     windows_logger: Logger,
-    mut on_kbd: impl FnMut(&mut S) + 'static,
+    mut on_kbd: impl FnMut(&mut S, KeyboardInput) + 'static,
     mut on_frame: impl FnMut(&mut S, &egui::Context) + 'static,
     mut on_exit: impl FnMut(S) + 'static,
 ) -> ! {
     let event_loop = winit::event_loop::EventLoop::new();
-    let mut window = gfx::Window::new(&event_loop, windows_logger).expect("failed to open window");
+    let mut window =
+        gfx::Window::new(&event_loop, title, windows_logger).expect("failed to open window");
     let mut egui_state = egui_winit::State::new(&event_loop);
     let egui_ctx = egui::Context::default();
     let mut state = ManuallyDrop::new(state);
@@ -36,7 +40,7 @@ fn run<S: 'static>(
                 let _ = egui_state.on_event(&egui_ctx, &event);
                 match event {
                     WindowEvent::Resized(_) => window.resized(),
-                    WindowEvent::KeyboardInput { .. } => on_kbd(&mut state),
+                    WindowEvent::KeyboardInput { input, .. } => on_kbd(&mut state, input),
                     WindowEvent::CloseRequested => ctrl_flow.set_exit(),
                     _ => {}
                 }
@@ -100,37 +104,103 @@ impl slog::Drain for Drain {
     }
 }
 
-pub fn main() {
-    let cargs = cargs::from_env();
+fn map_key(key: VirtualKeyCode) -> Option<nds::keys::Keys> {
+    Some(match key {
+        VirtualKeyCode::X => nds::keys::A,
+        VirtualKeyCode::Z => nds::keys::B,
+        VirtualKeyCode::S => nds::keys::X,
+        VirtualKeyCode::A => nds::keys::Y,
+        VirtualKeyCode::Q => nds::keys::L,
+        VirtualKeyCode::W => nds::keys::R,
+        VirtualKeyCode::Return => nds::keys::START,
+        VirtualKeyCode::RShift => nds::keys::SELECT,
+        VirtualKeyCode::Up => nds::keys::UP,
+        VirtualKeyCode::Down => nds::keys::DOWN,
+        VirtualKeyCode::Left => nds::keys::LEFT,
+        VirtualKeyCode::Right => nds::keys::RIGHT,
+        _ => return None,
+    })
+}
+
+pub fn main(cargs: cargs::CArgs) {
     let logger = Logger::root(Drain, o!("vargds" => "vds"));
-    let mut core =
-        nds::Core::<nds::Interpreter>::new(Logger::root(Drain, slog::o!("core" => "core")));
-    if let Err(err) = unsafe {
-        core.load_unvalidated_rom(
-            fs::read(&cargs.rom.expect("didn't supply rom"))
-                .expect("failed to read rom")
-                .into_boxed_slice(),
-        )
-    } {
-        panic!("failed to load rom\n{err}");
-    }
+    let mut core = emu::create_core(&cargs);
+    let audio = match &cargs.wav {
+        Some(path) => {
+            let sink = nds::audio::WavSink::create(path, 48000).expect("failed to create wav file");
+            core.set_audio_sink(Box::new(sink));
+            None
+        }
+        None => match audio::open(logger.new(o!("audio" => "audio"))) {
+            Ok((stream, sink)) => {
+                core.set_audio_sink(Box::new(sink));
+                Some(stream)
+            }
+            Err(err) => {
+                warn!(logger, "playing without sound: {err}");
+                None
+            }
+        },
+    };
     struct State {
         core: nds::Core<Interpreter>,
+        cargs: cargs::CArgs,
+        keys: nds::keys::Keys,
+        banner: Option<nds::cartridge::Banner>,
+        icon: Option<egui::TextureHandle>,
+        /// Plays the audio while it's alive.
+        _audio: Option<cpal::Stream>,
         logger: Logger,
     }
     let window_logger = logger.new(o!("window" => "window"));
+    let title = emu::window_title(&core);
+    let banner = core
+        .cartridge()
+        .and_then(|cartridge| cartridge.banner().ok().flatten());
     run(
-        State { core, logger },
+        State {
+            core,
+            cargs,
+            keys: 0,
+            banner,
+            icon: None,
+            _audio: audio,
+            logger,
+        },
+        &title,
         window_logger,
-        |state| {
-            info!(state.logger, "kbd input");
+        |state, input| {
+            if let Some(key) = input.virtual_keycode.and_then(map_key) {
+                match input.state {
+                    ElementState::Pressed => state.keys |= key,
+                    ElementState::Released => state.keys &= !key,
+                }
+                state.core.set_keys(state.keys);
+            }
         },
         |state, ctx| {
-            egui::Window::new("test").show(ctx, |ui| {
-                ui.label("hello");
-            });
+            if let Some(banner) = &state.banner {
+                let icon = state.icon.get_or_insert_with(|| {
+                    let size = [nds::cartridge::ICON_SIZE; 2];
+                    let image = egui::ColorImage::from_rgba_unmultiplied(size, &banner.icon);
+                    ctx.load_texture("icon", image, egui::TextureOptions::NEAREST)
+                });
+                let language = state.core.config().firmware_settings.language;
+                egui::Window::new("cartridge").show(ctx, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.image(icon.id(), [64.0, 64.0]);
+                        ui.label(banner.title(language).unwrap_or_default());
+                    });
+                });
+            }
             nds::interpreter::run(&mut state.core);
+            emu::flush_save(&mut state.core, &state.cargs, &state.logger, false);
+        },
+        |mut state| {
+            // finishes the wav file.
+            drop(state.core.take_audio_sink());
+            emu::flush_save(&mut state.core, &state.cargs, &state.logger, true);
+            info!(state.logger, "exiting")
         },
-        |state| info!(state.logger, "exiting"),
     );
 }
@@ -28,9 +28,12 @@ pub struct Window {
 impl Window {
     pub fn new<T>(
         event_loop: &winit::event_loop::EventLoop<T>,
+        title: &str,
         logger: Logger,
     ) -> anyhow::Result<Self> {
-        let winit_window = winit::window::Window::new(event_loop)?;
+        let winit_window = winit::window::WindowBuilder::new()
+            .with_title(title)
+            .build(event_loop)?;
         let egui_wgpu::WgpuConfiguration {
             device_descriptor,
             backends,
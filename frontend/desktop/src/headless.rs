@@ -0,0 +1,24 @@
+use std::time::Duration;
+
+use slog::Logger;
+
+use crate::cargs::CArgs;
+use crate::emu;
+use crate::gui::Drain;
+
+/// Run the core for `seconds` of emulated time without a window, writing the audio to the wav
+/// file if one was given.
+pub fn main(cargs: CArgs, seconds: u64) {
+    let logger = Logger::root(Drain, o!("vargds" => "vds"));
+    let mut core = emu::create_core(&cargs);
+    if let Some(path) = &cargs.wav {
+        let sink = nds::audio::WavSink::create(path, 48000).expect("failed to create wav file");
+        core.set_audio_sink(Box::new(sink));
+    }
+    while core.elapsed() < Duration::from_secs(seconds) {
+        nds::interpreter::run(&mut core);
+        emu::flush_save(&mut core, &cargs, &logger, false);
+    }
+    emu::flush_save(&mut core, &cargs, &logger, true);
+    drop(core.take_audio_sink());
+}
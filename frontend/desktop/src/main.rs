@@ -6,8 +6,11 @@ extern crate slog;
 
 extern crate vargds_core as nds;
 
+mod audio;
 mod cargs;
+mod emu;
 mod gui;
+mod headless;
 
 fn main() {
     env_logger::builder()
@@ -16,5 +19,9 @@ fn main() {
         .filter_module("nds::*", log::LevelFilter::max())
         .init();
 
-    gui::main();
+    let cargs = cargs::from_env();
+    match cargs.headless {
+        Some(seconds) => headless::main(cargs, seconds),
+        None => gui::main(cargs),
+    }
 }